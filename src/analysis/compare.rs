@@ -0,0 +1,203 @@
+use chrono::Datelike;
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use crate::analysis::{db, yield_rate};
+use crate::analysis::mytime::{self, DateShift, Shift};
+use crate::analysis::yield_rate::YieldDefine;
+
+// compare the counts and yields of two periods of a line, the first period is the base.
+// a change of yield is significant when the two-proportion z-test say so, not by a fixed percent,
+// a cell tested 10 times jump 10 % easily.
+
+// (start, pass, fail, unlock)
+type Rcounts = (u32, u32, u32, u32);
+
+// |z| of 95 % confidence
+static Z_SIGNIFICANT: f64 = 1.96;
+
+/// [start, end) in seconds, a shift or whole days
+#[derive(Debug, Clone)]
+pub struct Period {
+    // "2024-01-04 DAY", "2024-01-01 ~ 2024-01-07"
+    pub label: String,
+    pub start: i64,
+    pub end: i64,
+}
+
+/// shift is "DAY" or "NIGHT" for the shift of the date, or "DATES" for the days from date to date_to
+pub fn period(date: &str, shift: &str, date_to: &str) -> Option<Period> {
+    let day = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    let shift = match shift {
+        "DAY" => Shift::Day,
+        "NIGHT" => Shift::Night,
+        "DATES" => {
+            let start = mytime::vn_str2ts(date)?;
+            let end = mytime::vn_str2ts(date_to)? + 24 * 3600;
+            if end <= start {
+                return None;
+            }
+            return Some(Period { label: format!("{date} ~ {date_to}"), start, end });
+        }
+        _ => return None,
+    };
+    let dateshift = DateShift(day.year(), day.month(), day.day(), shift);
+    let (start, end) = mytime::start_end_of_shift(&dateshift);
+    Some(Period { label: format!("{} {}", dateshift.date(), dateshift.shift()), start, end })
+}
+
+/// (a, b, "+20", "+50.0 %") of a count, the relative is "" when a is 0
+pub type Rdelta = (u32, u32, String, String);
+
+pub fn count_delta(a: u32, b: u32) -> Rdelta {
+    let diff = b as i64 - a as i64;
+    let relative = if a == 0 { "".to_string() } else { format!("{:+.1} %", 100.0 * diff as f64 / a as f64) };
+    (a, b, format!("{diff:+}"), relative)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct YieldDelta {
+    // "97.5 %", "" if nothing tested
+    pub a: String,
+    pub b: String,
+    // "-2.3" percentage point
+    pub points: String,
+    // "-2.4 %" of the yield a
+    pub relative: String,
+    // "compare_worse", "compare_better" when significant, defined in general.css
+    pub class: &'static str,
+}
+
+/// z of the two-proportion test, None if any side has nothing tested or no variance
+pub fn z_score(a: (u32, u32), b: (u32, u32)) -> Option<f64> {
+    if a.1 == 0 || b.1 == 0 {
+        return None;
+    }
+    let (pa, pb) = (a.0 as f64 / a.1 as f64, b.0 as f64 / b.1 as f64);
+    let pooled = (a.0 + b.0) as f64 / (a.1 + b.1) as f64;
+    let se = (pooled * (1.0 - pooled) * (1.0 / a.1 as f64 + 1.0 / b.1 as f64)).sqrt();
+    if se == 0.0 {
+        return None;
+    }
+    Some((pb - pa) / se)
+}
+
+pub fn yield_delta(define: &YieldDefine, a: Rcounts, b: Rcounts) -> YieldDelta {
+    let text = |r: Option<f32>| r.map(|x| format!("{x:.1} %")).unwrap_or_default();
+    let (ra, rb) = (define.rate(a), define.rate(b));
+    let mut delta = YieldDelta { a: text(ra), b: text(rb), ..Default::default() };
+    let (ra, rb) = match (ra, rb) {
+        (Some(ra), Some(rb)) => (ra, rb),
+        _ => return delta,
+    };
+    delta.points = format!("{:+.1}", rb - ra);
+    if ra > 0.0 {
+        delta.relative = format!("{:+.1} %", 100.0 * (rb - ra) / ra);
+    }
+    // the numerator may count a code twice, a proportion is at most 1
+    let (pa, pb) = (define.parts(a), define.parts(b));
+    if pa.0 <= pa.1 && pb.0 <= pb.1 {
+        if let Some(z) = z_score(pa, pb).filter(|z| z.abs() >= Z_SIGNIFICANT) {
+            delta.class = if (z > 0.0) != define.lower_is_better { "compare_better" } else { "compare_worse" };
+        }
+    }
+    delta
+}
+
+/// one row of the comparison table, a station or a cell
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub name: String,
+    pub start: Rdelta,
+    pub pass: Rdelta,
+    pub fail: Rdelta,
+    pub unlock: Rdelta,
+    pub yield_delta: YieldDelta,
+}
+
+pub fn row(name: &str, define: Option<&YieldDefine>, a: Rcounts, b: Rcounts) -> Row {
+    Row {
+        name: name.to_string(),
+        start: count_delta(a.0, b.0),
+        pass: count_delta(a.1, b.1),
+        fail: count_delta(a.2, b.2),
+        unlock: count_delta(a.3, b.3),
+        yield_delta: define.map(|d| yield_delta(d, a, b)).unwrap_or_default(),
+    }
+}
+
+/// the fail messages of b never seen in a, the most one first
+pub fn new_messages(a: &[(String, u32)], b: &[(String, u32)]) -> Vec<(String, u32)> {
+    let seen: HashSet<&str> = a.iter().map(|x| x.0.as_str()).collect();
+    let mut v: Vec<(String, u32)> = b.iter().filter(|x| !seen.contains(x.0.as_str())).cloned().collect();
+    v.sort_by(|x, y| y.1.cmp(&x.1).then(x.0.cmp(&y.0)));
+    v
+}
+
+pub struct StationCompare {
+    pub row: Row,
+    // every cell tested in a or b, sorted by cell
+    pub cells: Vec<Row>,
+    pub new_messages: Vec<(String, u32)>,
+}
+
+pub fn compare_station(line: &str, station: &str, a: &Period, b: &Period) -> Result<StationCompare, Box<dyn Error>> {
+    let define = yield_rate::station_define(station);
+    let mut cells: BTreeMap<String, (Rcounts, Rcounts)> = BTreeMap::new();
+    for (cell, counts) in db::cell_counts(line, station, a.start, a.end)? {
+        cells.entry(cell).or_default().0 = counts;
+    }
+    for (cell, counts) in db::cell_counts(line, station, b.start, b.end)? {
+        cells.entry(cell).or_default().1 = counts;
+    }
+    let sum = |x: Rcounts, y: Rcounts| {
+        (x.0.saturating_add(y.0), x.1.saturating_add(y.1), x.2.saturating_add(y.2), x.3.saturating_add(y.3))
+    };
+    let (total_a, total_b) = cells.values().fold(((0, 0, 0, 0), (0, 0, 0, 0)), |t, x| (sum(t.0, x.0), sum(t.1, x.1)));
+    let messages_a = db::fail_msg_counts(line, station, a.start, a.end)?;
+    let messages_b = db::fail_msg_counts(line, station, b.start, b.end)?;
+    Ok(StationCompare {
+        row: row(station, define.as_ref(), total_a, total_b),
+        cells: cells.iter().map(|(cell, x)| row(cell, define.as_ref(), x.0, x.1)).collect(),
+        new_messages: new_messages(&messages_a, &messages_b),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        assert_eq!(count_delta(40, 60), (40, 60, "+20".to_string(), "+50.0 %".to_string()));
+        assert_eq!(count_delta(0, 3).3, "");
+        assert_eq!(count_delta(5, 0).2, "-5");
+
+        let defines = yield_rate::default_defines();
+        let pass_rate = defines.get("pass_rate").unwrap();
+        // 95 % -> 85 % of 200 tests is significant
+        let d = yield_delta(pass_rate, (200, 190, 10, 0), (200, 170, 30, 0));
+        assert_eq!((d.a.as_str(), d.b.as_str(), d.points.as_str()), ("95.0 %", "85.0 %", "-10.0"));
+        assert_eq!(d.class, "compare_worse");
+        // the same change of 20 tests is not
+        assert_eq!(yield_delta(pass_rate, (20, 19, 1, 0), (20, 17, 3, 0)).class, "");
+        // less fail is better for fail rate
+        let fail_rate = defines.get("fail_rate").unwrap();
+        assert_eq!(yield_delta(fail_rate, (200, 170, 30, 0), (200, 190, 10, 0)).class, "compare_better");
+        assert_eq!(yield_delta(pass_rate, (0, 0, 0, 0), (10, 9, 1, 0)).points, "");
+        // a month of a busy cell is more than u16
+        let d = yield_delta(pass_rate, (90000, 88000, 2000, 0), (90000, 85000, 5000, 0));
+        assert_eq!((d.a.as_str(), d.b.as_str(), d.class), ("97.8 %", "94.4 %", "compare_worse"));
+        assert_eq!(count_delta(70000, 140000).2, "+70000");
+
+        let a = vec![("KEY_1 press fail".to_string(), 3)];
+        let b = vec![("KEY_1 press fail".to_string(), 5), ("KEY_STAR stuck".to_string(), 2)];
+        assert_eq!(new_messages(&a, &b), vec![("KEY_STAR stuck".to_string(), 2)]);
+
+        let p = period("2024-01-04", "NIGHT", "").unwrap();
+        assert_eq!((p.label.as_str(), p.end - p.start), ("2024-01-04 NIGHT", 12 * 3600));
+        let p = period("2024-01-01", "DATES", "2024-01-07").unwrap();
+        assert_eq!((p.label.as_str(), p.end - p.start), ("2024-01-01 ~ 2024-01-07", 7 * 24 * 3600));
+        assert!(period("2024-01-07", "DATES", "2024-01-01").is_none());
+        assert!(period("2024-13-01", "DAY", "").is_none());
+    }
+}
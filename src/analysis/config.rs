@@ -1,11 +1,18 @@
 use once_cell::sync::OnceCell;
 use serde::Deserialize;
-use std::env::current_exe;
-use std::fs::File;
+use std::collections::HashMap;
+use std::fs::{self, File};
 use std::io::Read;
-use std::path::PathBuf;
-use std::process;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
 use toml;
+use tracing::{error, info, warn};
+use crate::cli::Paths;
+use crate::analysis::fail_class::FailRule;
+use crate::analysis::user::Role;
+use crate::analysis::yield_rate::{self, StationThreshold, YieldDefine};
 
 #[derive(Deserialize, Debug)]
 pub struct Bst {
@@ -21,16 +28,360 @@ pub struct Fst {
     pub key_db: PathBuf,
 }
 
+/// https is used when [tls] is in config.toml, the relative path is under the dir of config.toml
+#[derive(Deserialize, Debug, Clone)]
+pub struct Tls {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    // plain http port, every request redirect to https
+    pub redirect_port: Option<u16>,
+}
+
+fn default_server_db() -> PathBuf {
+    PathBuf::from("server.db")
+}
+
+fn default_anonymous() -> String {
+    "viewer".to_string()
+}
+
+fn default_session_hours() -> u32 {
+    12
+}
+
+/// login and role, the users and api tokens are in the server-owned db, see `server user --help`
+#[derive(Deserialize, Debug, Clone)]
+pub struct Auth {
+    // relative path is under the dir of config.toml
+    #[serde(default = "default_server_db")]
+    pub server_db: PathBuf,
+    // role of the visitor without login: "none", "viewer", "engineer" or "admin"
+    #[serde(default = "default_anonymous")]
+    pub anonymous: String,
+    #[serde(default = "default_session_hours")]
+    pub session_hours: u32,
+}
+
+impl Default for Auth {
+    fn default() -> Self {
+        Auth { server_db: default_server_db(), anonymous: default_anonymous(), session_hours: default_session_hours() }
+    }
+}
+
+fn default_check_interval() -> u64 {
+    60
+}
+
+fn default_check_timeout() -> u64 {
+    2000
+}
+
+/// tcp connect to every fixture port of the port config page, see port_check.rs
+#[derive(Deserialize, Debug, Clone)]
+pub struct PortCheck {
+    // seconds between two checks, 0 is disabled
+    #[serde(default = "default_check_interval")]
+    pub interval_secs: u64,
+    #[serde(default = "default_check_timeout")]
+    pub timeout_ms: u64,
+}
+
+impl Default for PortCheck {
+    fn default() -> Self {
+        PortCheck { interval_secs: default_check_interval(), timeout_ms: default_check_timeout() }
+    }
+}
+
+fn default_command_timeout() -> u64 {
+    5000
+}
+
+fn default_line_end() -> String {
+    "\r\n".to_string()
+}
+
+fn default_transcript_dir() -> PathBuf {
+    PathBuf::from("transcripts")
+}
+
+/// the fixture command sent to the terminal server port, see fixture.rs
+#[derive(Deserialize, Debug, Clone)]
+pub struct Fixture {
+    // wait for the expected reply at most
+    #[serde(default = "default_command_timeout")]
+    pub command_timeout_ms: u64,
+    // appended to the command, and the line typed in the console
+    #[serde(default = "default_line_end")]
+    pub line_end: String,
+    // the console sessions are saved here, relative path is under the dir of config.toml
+    #[serde(default = "default_transcript_dir")]
+    pub transcript_dir: PathBuf,
+}
+
+impl Default for Fixture {
+    fn default() -> Self {
+        Fixture {
+            command_timeout_ms: default_command_timeout(),
+            line_end: default_line_end(),
+            transcript_dir: default_transcript_dir(),
+        }
+    }
+}
+
+fn default_smtp_port() -> u16 {
+    25
+}
+
+fn default_smtp_tls() -> String {
+    "none".to_string()
+}
+
+/// the mail server of the shift report, a local smtp sink with tls "none" is enough to test
+#[derive(Deserialize, Debug, Clone)]
+pub struct Smtp {
+    pub host: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    // "none", "starttls" or "tls"
+    #[serde(default = "default_smtp_tls")]
+    pub tls: String,
+    // no login if empty
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+fn default_report_lines() -> Vec<String> {
+    ["bst1", "bst2", "fst1", "fst2"].iter().map(|x| x.to_string()).collect()
+}
+
+fn default_report_delay() -> u64 {
+    300
+}
+
+fn default_worst_cells() -> usize {
+    3
+}
+
+fn default_top_messages() -> usize {
+    5
+}
+
+fn default_retry() -> u32 {
+    3
+}
+
+fn default_retry_interval() -> u64 {
+    60
+}
+
+/// the summary of every line sent at the end of a shift, see shift_report.rs
+#[derive(Deserialize, Debug, Clone)]
+pub struct ShiftReport {
+    #[serde(default = "default_report_lines")]
+    pub lines: Vec<String>,
+    // wait after the end of shift, for the last records written into the db
+    #[serde(default = "default_report_delay")]
+    pub delay_secs: u64,
+    #[serde(default = "default_worst_cells")]
+    pub worst_cells: usize,
+    #[serde(default = "default_top_messages")]
+    pub top_messages: usize,
+    // times to try again when a delivery fail
+    #[serde(default = "default_retry")]
+    pub retry: u32,
+    #[serde(default = "default_retry_interval")]
+    pub retry_interval_secs: u64,
+    // the report is POSTed as json to every url
+    #[serde(default)]
+    pub webhooks: Vec<String>,
+    pub smtp: Option<Smtp>,
+}
+
+impl Default for ShiftReport {
+    fn default() -> Self {
+        ShiftReport {
+            lines: default_report_lines(),
+            delay_secs: default_report_delay(),
+            worst_cells: default_worst_cells(),
+            top_messages: default_top_messages(),
+            retry: default_retry(),
+            retry_interval_secs: default_retry_interval(),
+            webhooks: Vec::new(),
+            smtp: None,
+        }
+    }
+}
+
+fn default_kiosk_interval() -> u64 {
+    30
+}
+
+fn default_kiosk_refresh() -> u64 {
+    60
+}
+
+fn default_kiosk_retry() -> u64 {
+    10
+}
+
+/// a page of the kiosk playlist, e.g. "/en-US/fst1/pf_data"
+#[derive(Deserialize, Debug, Clone)]
+pub struct KioskPage {
+    pub path: String,
+    // seconds shown, 0 is kiosk.interval_secs
+    #[serde(default)]
+    pub secs: u64,
+}
+
+/// the wall-mounted display of a line, /kiosk/<name> cycles through the pages of kiosk.playlists.<name>
+#[derive(Deserialize, Debug, Clone)]
+pub struct Kiosk {
+    #[serde(default = "default_kiosk_interval")]
+    pub interval_secs: u64,
+    // the page shown is fetched again
+    #[serde(default = "default_kiosk_refresh")]
+    pub refresh_secs: u64,
+    // wait before fetching again when the server or the db is unavailable, the last good page is kept
+    #[serde(default = "default_kiosk_retry")]
+    pub retry_secs: u64,
+    #[serde(default)]
+    pub playlists: HashMap<String, Vec<KioskPage>>,
+}
+
+impl Default for Kiosk {
+    fn default() -> Self {
+        Kiosk {
+            interval_secs: default_kiosk_interval(),
+            refresh_secs: default_kiosk_refresh(),
+            retry_secs: default_kiosk_retry(),
+            playlists: HashMap::new(),
+        }
+    }
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+fn default_log_rotation() -> String {
+    "daily".to_string()
+}
+
+fn default_log_max_files() -> usize {
+    14
+}
+
+/// the tracing output, see logging.rs
+#[derive(Deserialize, Debug, Clone)]
+pub struct Log {
+    // "info", "debug", or the directives of EnvFilter like "info,server::analysis::db=debug",
+    // env RUST_LOG overrides it. changed without restart
+    #[serde(default = "default_log_level")]
+    pub level: String,
+    // json lines are written here too, relative to the dir of config.toml. no file if not set
+    pub file: Option<PathBuf>,
+    // "minutely", "hourly", "daily" or "never"
+    #[serde(default = "default_log_rotation")]
+    pub rotation: String,
+    // the rotated files kept, the older ones are deleted
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+}
+
+impl Default for Log {
+    fn default() -> Self {
+        Log {
+            level: default_log_level(),
+            file: None,
+            rotation: default_log_rotation(),
+            max_files: default_log_max_files(),
+        }
+    }
+}
+
+fn default_closed_after() -> u64 {
+    600
+}
+
+fn default_max_entries() -> usize {
+    2000
+}
+
+fn default_persist_days() -> u32 {
+    90
+}
+
+/// the query results of the closed shifts, see query_cache.rs
+#[derive(Deserialize, Debug, Clone)]
+pub struct Cache {
+    // a shift is closed this long after its end, the last records may be written late
+    #[serde(default = "default_closed_after")]
+    pub closed_after_secs: u64,
+    // the most results kept in memory, the least used is dropped
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+    // keep the results in server.db too, they survive a restart
+    #[serde(default)]
+    pub persist: bool,
+    // the persisted results older than this are deleted from server.db
+    #[serde(default = "default_persist_days")]
+    pub persist_days: u32,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Cache {
+            closed_after_secs: default_closed_after(),
+            max_entries: default_max_entries(),
+            persist: false,
+            persist_days: default_persist_days(),
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct TomlConfig {
-    pub current_exe_path:PathBuf,
-    pub current_config_path:PathBuf,
-    pub current_db_path:PathBuf,
-    pub port: i32,
+    // resolved from command line when running, see cli.rs
+    #[serde(skip)]
+    pub current_exe_path: PathBuf,
+    #[serde(skip)]
+    pub current_config_path: PathBuf,
+    #[serde(skip)]
+    pub current_db_path: PathBuf,
+    #[serde(skip)]
+    pub current_locales_path: PathBuf,
+    pub port: u16,
+    // a file here replaces the built-in static file of the same path, relative to the dir of config.toml
+    pub static_dir: Option<PathBuf>,
+    pub tls: Option<Tls>,
+    #[serde(default)]
+    pub auth: Auth,
+    #[serde(default)]
+    pub port_check: PortCheck,
+    #[serde(default)]
+    pub fixture: Fixture,
+    #[serde(default)]
+    pub shift_report: ShiftReport,
+    #[serde(default)]
+    pub kiosk: Kiosk,
+    #[serde(default)]
+    pub cache: Cache,
+    #[serde(default)]
+    pub log: Log,
     pub bst1: Bst,
     pub bst2: Bst,
     pub fst1: Fst,
     pub fst2: Fst,
+    #[serde(default = "yield_rate::default_defines")]
+    pub yield_define: HashMap<String, YieldDefine>,
+    #[serde(default)]
+    pub yield_station: HashMap<String, StationThreshold>,
+    #[serde(default)]
+    pub fail_rule: Vec<FailRule>,
 }
 
 impl TomlConfig {
@@ -71,48 +422,41 @@ impl TomlConfig {
     }
 }
 
-pub static CONFIG: OnceCell<TomlConfig> = OnceCell::new();
+// the running config, replaced as a whole by reload_config(), a request keep the snapshot it got
+pub static CONFIG: OnceCell<RwLock<Arc<TomlConfig>>> = OnceCell::new();
 
-pub fn init_config() {
-    let exe_path = current_exe().unwrap();
-    println!("path of this running program: {exe_path:?}       --exist");
-    let toml_path = exe_path.parent().unwrap().join("config.toml");
-    print!("expected path of config.toml: {toml_path:?}");
-    if !toml_path.exists() {
-        println!("  --Error: not exist !");
-        process::exit(0);
-    } else {
-        println!("  --exist");
-    }
-    let db_dir = exe_path.parent().unwrap().join("db");
-    print!("expected path of db file dir: {db_dir:?}");
-    if !db_dir.exists() {
-        println!("  --Error: not exist !");
-        process::exit(0);
-    } else {
-        println!("           --exist");
-    }
-    let mut toml = match File::open(&toml_path) {
-        Ok(f) => f,
-        Err(e) => {
-            eprintln!("Error: open config.toml file fail, {}", e);
-            process::exit(0);
-        }
-    };
-    let mut contents = String::new();
+// increased by every reload, a part of the ETag of the pages
+static GENERATION: AtomicU64 = AtomicU64::new(0);
 
-    if let Err(e) = toml.read_to_string(&mut contents) {
-        eprintln!("Error: read config.toml content fail, {}", e);
-        process::exit(0);
-    }
+// how often watch_config() check the modified time of config.toml
+static WATCH_INTERVAL: Duration = Duration::from_secs(3);
+
+pub fn get_config() -> Arc<TomlConfig> {
+    CONFIG.get().unwrap().read().unwrap().clone()
+}
 
-    let mut config: TomlConfig = match toml::from_str(&contents) {
-        Ok(c) => c,
-        Err(e) => {
-            eprintln!("Error: deserialize config.toml fail: {}", e);
-            process::exit(0);
+/// how many times the config is reloaded
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::Relaxed)
+}
+
+/// read, deserialize and validate config.toml, nothing is applied if any error
+pub fn load_config(paths: &Paths) -> Result<TomlConfig, String> {
+    let db_dir = &paths.db_dir;
+    let mut toml = File::open(&paths.config_path).map_err(|e| format!("open config.toml file fail, {e}"))?;
+    let mut contents = String::new();
+    toml.read_to_string(&mut contents).map_err(|e| format!("read config.toml content fail, {e}"))?;
+    let mut config: TomlConfig = toml::from_str(&contents).map_err(|e| format!("deserialize config.toml fail: {e}"))?;
+    for (name, define) in config.yield_define.iter() {
+        if !define.valid() {
+            return Err(format!("yield_define.{name} is invalid, result code should be one of S, P, F, U"));
         }
-    };
+    }
+    for (station, threshold) in config.yield_station.iter() {
+        if !config.yield_define.contains_key(&threshold.define) {
+            return Err(format!("yield_station.{station} use undefined yield_define.{}", threshold.define));
+        }
+    }
     config.bst1.bst_db = db_dir.join("bst1").join(config.bst1.bst_db);
     config.bst2.bst_db = db_dir.join("bst2").join(config.bst2.bst_db);
     config.fst1.lcd_db = db_dir.join("fst1").join(config.fst1.lcd_db);
@@ -121,9 +465,161 @@ pub fn init_config() {
     config.fst2.lcd_db = db_dir.join("fst2").join(config.fst2.lcd_db);
     config.fst2.diag_db = db_dir.join("fst2").join(config.fst2.diag_db);
     config.fst2.key_db = db_dir.join("fst2").join(config.fst2.key_db);
-    config.current_exe_path = exe_path;
-    config.current_config_path = toml_path;
-    config.current_db_path = db_dir;
+    for (i, rule) in config.fail_rule.iter_mut().enumerate() {
+        rule.compile().map_err(|e| format!("fail_rule {} ({}) is invalid, {e}", i + 1, rule.category))?;
+    }
+    if config.auth.anonymous != "none" && Role::from_str(&config.auth.anonymous).is_none() {
+        return Err(format!("auth.anonymous {} is invalid, should be none, viewer, engineer or admin",
+                           config.auth.anonymous));
+    }
+    if !(1..=30000).contains(&config.port_check.timeout_ms) {
+        return Err(format!("port_check.timeout_ms {} is invalid, should be 1~30000", config.port_check.timeout_ms));
+    }
+    if !(1..=60000).contains(&config.fixture.command_timeout_ms) {
+        return Err(format!("fixture.command_timeout_ms {} is invalid, should be 1~60000",
+                           config.fixture.command_timeout_ms));
+    }
+    for line in config.shift_report.lines.iter() {
+        if config.get_hostname(line).is_none() {
+            return Err(format!("shift_report.lines {line} is invalid, should be bst1, bst2, fst1 or fst2"));
+        }
+    }
+    for url in config.shift_report.webhooks.iter() {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(format!("shift_report.webhooks {url} is invalid, should be a http or https url"));
+        }
+    }
+    if let Some(smtp) = config.shift_report.smtp.as_ref() {
+        if !["none", "starttls", "tls"].contains(&smtp.tls.as_str()) {
+            return Err(format!("shift_report.smtp.tls {} is invalid, should be none, starttls or tls", smtp.tls));
+        }
+        for address in std::iter::once(&smtp.from).chain(smtp.to.iter()) {
+            address.parse::<lettre::message::Mailbox>()
+                .map_err(|e| format!("shift_report.smtp address {address} is invalid, {e}"))?;
+        }
+        if smtp.to.is_empty() {
+            return Err("shift_report.smtp.to is empty".to_string());
+        }
+    }
+    if config.kiosk.interval_secs < 5 || config.kiosk.refresh_secs < 5 || config.kiosk.retry_secs < 1 {
+        return Err("kiosk interval_secs and refresh_secs should be 5 at least, retry_secs 1 at least".to_string());
+    }
+    for (name, pages) in config.kiosk.playlists.iter() {
+        if pages.is_empty() {
+            return Err(format!("kiosk.playlists.{name} is empty"));
+        }
+        if let Some(page) = pages.iter().find(|x| !x.path.starts_with('/') || x.path.starts_with("/kiosk")) {
+            return Err(format!("kiosk.playlists.{name} path {} is invalid, should be a page of this server",
+                               page.path));
+        }
+    }
+    if tracing_subscriber::EnvFilter::try_new(&config.log.level).is_err() {
+        return Err(format!("log.level {} is invalid, should be like \"info\" or \"info,server=debug\"",
+                           config.log.level));
+    }
+    if !["minutely", "hourly", "daily", "never"].contains(&config.log.rotation.as_str()) {
+        return Err(format!("log.rotation {} is invalid, should be minutely, hourly, daily or never",
+                           config.log.rotation));
+    }
+    if config.cache.max_entries == 0 {
+        return Err("cache.max_entries should be 1 at least".to_string());
+    }
+    if config.cache.persist_days == 0 {
+        return Err("cache.persist_days should be 1 at least".to_string());
+    }
+    let config_dir = paths.config_path.parent().unwrap_or(Path::new(""));
+    config.auth.server_db = config_dir.join(&config.auth.server_db);
+    config.fixture.transcript_dir = config_dir.join(&config.fixture.transcript_dir);
+    if let Some(dir) = config.static_dir.as_mut() {
+        *dir = config_dir.join(&dir);
+    }
+    if let Some(file) = config.log.file.as_mut() {
+        *file = config_dir.join(&file);
+    }
+    if let Some(tls) = config.tls.as_mut() {
+        tls.cert = config_dir.join(&tls.cert);
+        tls.key = config_dir.join(&tls.key);
+    }
+    config.current_exe_path = paths.exe_path.to_owned();
+    config.current_config_path = paths.config_path.to_owned();
+    config.current_db_path = paths.db_dir.to_owned();
+    config.current_locales_path = paths.locales_dir.to_owned();
+    Ok(config)
+}
+
+/// load config.toml again and replace the running config, the running config is kept if the new file is broken
+pub fn reload_config() -> Result<Arc<TomlConfig>, String> {
+    let old = get_config();
+    let paths = Paths {
+        exe_path: old.current_exe_path.to_owned(),
+        config_path: old.current_config_path.to_owned(),
+        db_dir: old.current_db_path.to_owned(),
+        locales_dir: old.current_locales_path.to_owned(),
+    };
+    let new = load_config(&paths)?;
+    if new.port != old.port {
+        warn!("port changed from {} to {}, it takes effect after restart", old.port, new.port);
+    }
+    if new.tls.is_some() != old.tls.is_some() {
+        warn!("[tls] changed, it takes effect after restart");
+    }
+    if new.auth.server_db != old.auth.server_db {
+        warn!("auth.server_db changed, the tables are created after restart");
+    }
+    if new.log.file != old.log.file || new.log.rotation != old.log.rotation || new.log.max_files != old.log.max_files {
+        warn!("[log] file changed, it takes effect after restart, the level is changed now");
+    }
+    for (_, hostname, db_path) in new.get_all_db() {
+        if !db_path.exists() {
+            warn!("reload config.toml, {hostname} db_path {db_path:?} not exist!");
+        }
+    }
+    let new = Arc::new(new);
+    *CONFIG.get().unwrap().write().unwrap() = new.clone();
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+    Ok(new)
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// reload the config when config.toml is modified, run forever in a spawned task
+pub async fn watch_config() {
+    let toml_path = get_config().current_config_path.clone();
+    let mut last = modified_time(&toml_path);
+    loop {
+        tokio::time::sleep(WATCH_INTERVAL).await;
+        let now = modified_time(&toml_path);
+        if now.is_none() || now == last {
+            continue;
+        }
+        last = now;
+        match reload_config() {
+            Ok(_) => info!("config.toml changed, reload success"),
+            Err(e) => error!("config.toml changed, reload fail, keep running with the old config: {e}"),
+        }
+    }
+}
+
+pub fn init_config(paths: &Paths) -> Result<(), String> {
+    println!("path of this running program: {:?}       --exist", paths.exe_path);
+    print!("expected path of config.toml: {:?}", paths.config_path);
+    if !paths.config_path.exists() {
+        println!("  --Error: not exist !");
+        return Err(format!("config.toml {:?} not exist", paths.config_path));
+    } else {
+        println!("  --exist");
+    }
+    print!("expected path of db file dir: {:?}", paths.db_dir);
+    if !paths.db_dir.exists() {
+        println!("  --Error: not exist !");
+        return Err(format!("db file dir {:?} not exist", paths.db_dir));
+    } else {
+        println!("           --exist");
+    }
+    let config = load_config(paths)?;
     // println!("toml config is: {:?}", config);
-    CONFIG.get_or_init(|| config);
+    CONFIG.get_or_init(|| RwLock::new(Arc::new(config)));
+    Ok(())
 }
@@ -0,0 +1,169 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::analysis::{db, mytime, yield_rate};
+use crate::analysis::compare::Period;
+use crate::analysis::yield_rate::YieldView;
+
+// the same station of the lines running the same layout, bst1/bst2 and fst1/fst2, cell by cell.
+// a fail of every line and many cells is likely the test software, a fail of one cell is the fixture.
+
+static TOP_FAILS: usize = 10;
+// a fail seen on every line (two at least) and at least this many cells is line-wide
+static LINE_WIDE_CELLS: usize = 3;
+// a fail mostly on one cell is a fixture problem, the percent of the top cell
+static ONE_CELL_PERCENT: u32 = 80;
+
+/// the lines with the same stations and cell layout
+pub fn family(line: &str) -> [&'static str; 2] {
+    if line.contains("bst") { ["bst1", "bst2"] } else { ["fst1", "fst2"] }
+}
+
+/// the quantity and yield of a line, or of a cell of a line
+#[derive(Debug, Clone, Default)]
+pub struct LineCount {
+    pub start: u32,
+    pub pass: u32,
+    pub fail: u32,
+    pub unlock: u32,
+    // start per hour of the period, "12.5"
+    pub per_hour: String,
+    pub yield_view: YieldView,
+}
+
+/// a fail msg on every line
+#[derive(Debug, Clone)]
+pub struct TopFail {
+    pub msg: String,
+    // (quantity, the cell most failed, quantity of the cell) of every line
+    pub lines: Vec<(u32, String, u32)>,
+    // "line-wide", "one cell" or ""
+    pub hint: &'static str,
+}
+
+pub struct CrossStation {
+    // the lines compared, a line failed to read is in errors
+    pub lines: Vec<String>,
+    pub totals: Vec<LineCount>,
+    // (cell, every line)
+    pub cells: Vec<(String, Vec<LineCount>)>,
+    pub top_fails: Vec<TopFail>,
+    pub errors: Vec<String>,
+}
+
+fn line_count(station: &str, counts: (u32, u32, u32, u32), hours: f64) -> LineCount {
+    LineCount {
+        start: counts.0,
+        pass: counts.1,
+        fail: counts.2,
+        unlock: counts.3,
+        per_hour: if hours > 0.0 { format!("{:.1}", counts.0 as f64 / hours) } else { "".to_string() },
+        yield_view: yield_rate::station_yield(station, counts),
+    }
+}
+
+/// "line-wide" when on every line of two or more and many cells, "one cell" when the top cell has most of it
+fn hint(lines: &[(u32, String, u32)], cells: usize) -> &'static str {
+    let total: u32 = lines.iter().map(|x| x.0).sum();
+    let top = lines.iter().map(|x| x.2).max().unwrap_or(0);
+    if lines.len() >= 2 && lines.iter().all(|x| x.0 > 0) && cells >= LINE_WIDE_CELLS {
+        "line-wide"
+    } else if total > 0 && top * 100 >= total * ONE_CELL_PERCENT {
+        "one cell"
+    } else {
+        ""
+    }
+}
+
+/// (line, [(msg, cell, quantity)]) -> the most failed msg of all lines first
+fn top_fails(per_line: &[(String, Vec<db::RmsgCell>)]) -> Vec<TopFail> {
+    let mut sum: HashMap<&str, u32> = HashMap::new();
+    for (_, counts) in per_line {
+        for (msg, _, n) in counts {
+            *sum.entry(msg.as_str()).or_insert(0) += *n;
+        }
+    }
+    let mut msgs: Vec<(&str, u32)> = sum.into_iter().collect();
+    msgs.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    msgs.truncate(TOP_FAILS);
+    msgs.into_iter().map(|(msg, _)| {
+        let mut cells = HashSet::new();
+        let lines: Vec<(u32, String, u32)> = per_line.iter().map(|(line, counts)| {
+            let mut quantity = 0u32;
+            let mut top = ("".to_string(), 0u32);
+            for (_, cell, n) in counts.iter().filter(|x| x.0 == msg) {
+                quantity = quantity.saturating_add(*n);
+                cells.insert((line.as_str(), cell.as_str()));
+                if *n > top.1 {
+                    top = (cell.to_owned(), *n);
+                }
+            }
+            (quantity, top.0, top.1)
+        }).collect();
+        TopFail { msg: msg.to_string(), hint: hint(&lines, cells.len()), lines }
+    }).collect()
+}
+
+/// the station on every line of the family of the line, the lines are the ones the visitor can view
+pub fn cross_station(lines: &[&str], station: &str, period: &Period) -> CrossStation {
+    let end = period.end.min(mytime::now_ts());
+    let hours = (end - period.start) as f64 / 3600.0;
+    let mut cross = CrossStation { lines: Vec::new(), totals: Vec::new(), cells: Vec::new(), top_fails: Vec::new(),
+                                   errors: Vec::new() };
+    let mut cells: BTreeMap<String, Vec<(u32, u32, u32, u32)>> = BTreeMap::new();
+    let mut fails = Vec::new();
+    for line in lines {
+        let counts = db::cell_counts(line, station, period.start, period.end);
+        let msgs = db::fail_msg_cell_counts(line, station, period.start, period.end);
+        let (counts, msgs) = match (counts, msgs) {
+            (Ok(c), Ok(m)) => (c, m),
+            (Err(e), _) | (_, Err(e)) => {
+                cross.errors.push(format!("{line} {station}: {e}"));
+                continue;
+            }
+        };
+        let i = cross.lines.len();
+        cross.lines.push(line.to_string());
+        let mut total = (0u32, 0u32, 0u32, 0u32);
+        for (cell, x) in counts {
+            total = (total.0.saturating_add(x.0), total.1.saturating_add(x.1),
+                     total.2.saturating_add(x.2), total.3.saturating_add(x.3));
+            let v = cells.entry(cell).or_default();
+            v.resize(i + 1, (0, 0, 0, 0));
+            v[i] = x;
+        }
+        cross.totals.push(line_count(station, total, hours));
+        fails.push((line.to_string(), msgs));
+    }
+    let n = cross.lines.len();
+    cross.cells = cells.into_iter().map(|(cell, mut v)| {
+        v.resize(n, (0, 0, 0, 0));
+        (cell, v.into_iter().map(|x| line_count(station, x, hours)).collect())
+    }).collect();
+    cross.top_fails = top_fails(&fails);
+    cross
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_fails() {
+        let m = |msg: &str, cell: &str, n: u32| (msg.to_string(), cell.to_string(), n);
+        let per_line = vec![
+            ("fst1".to_string(), vec![m("timeout", "CELL_69", 3), m("timeout", "CELL_71", 2), m("KEY_1", "CELL_73", 9),
+                                      m("timeout", "CELL_75", 1)]),
+            ("fst2".to_string(), vec![m("timeout", "CELL_69", 4), m("KEY_1", "CELL_73", 1), m("LED", "CELL_81", 2)]),
+        ];
+        let v = top_fails(&per_line);
+        assert_eq!(v[0].msg, "KEY_1");
+        assert_eq!(v[0].lines, vec![(9, "CELL_73".to_string(), 9), (1, "CELL_73".to_string(), 1)]);
+        assert_eq!(v[0].hint, "one cell");
+        assert_eq!((v[1].msg.as_str(), v[1].hint), ("timeout", "line-wide"));
+        assert_eq!(v[1].lines[0], (6, "CELL_69".to_string(), 3));
+        assert_eq!(v[2].lines[0].0, 0);
+        assert_eq!(v[2].hint, "one cell");
+        // the other line is not viewable, one line is never line-wide
+        assert_eq!(top_fails(&per_line[..1])[1].hint, "");
+        assert_eq!(family("bst2"), ["bst1", "bst2"]);
+    }
+}
@@ -0,0 +1,164 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::error::Error;
+use crate::analysis::{config, db, mytime, tst_record};
+use crate::analysis::tst_record::{or_empty, TstRecord};
+
+// the rows of tst_record the yield can not count, a sequencer or a db to be fixed. the report scans the records
+// of the last days, the aggregations of db.rs skip such rows and log the quantity.
+
+// the sample rows of each kind
+static SAMPLES: usize = 5;
+// the clock of the line may be a little ahead of the server
+static FUTURE_SLACK_SECS: i64 = 5 * 60;
+// FCH93250000, 3 letters of the factory, year, week and 4 digits or letters of the serial
+static SN: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[A-Z]{3}\d{4}[0-9A-Z]{4}$").unwrap());
+
+/// the kinds of the bad rows, in the order of the report
+pub static KINDS: [&str; 7] = ["unknown cell", "unknown result", "malformed sn", "empty pid/pn", "no time",
+    "time out of order", "time in future"];
+
+// (id, beijing_str, sn, cell, result, the value found bad)
+pub type Rsample = (i64, String, String, String, String, String);
+
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub kind: &'static str,
+    pub count: u32,
+    pub samples: Vec<Rsample>,
+}
+
+pub struct DbQuality {
+    pub station: &'static str,
+    pub db_path: String,
+    // the rows scanned
+    pub rows: u32,
+    // the known columns the db lacks, read as empty
+    pub missing: Vec<&'static str>,
+    pub issues: Vec<Issue>,
+    // the db can not be read, "" if ok
+    pub error: String,
+}
+
+impl DbQuality {
+    pub fn bad(&self) -> u32 {
+        self.issues.iter().map(|x| x.count).sum()
+    }
+}
+
+/// the kinds of the record with the bad value, prev_ts is the time of the record before it by id
+pub fn check(r: &TstRecord, station: &str, prev_ts: Option<i64>, now: i64) -> Vec<(&'static str, String)> {
+    let mut bad = Vec::new();
+    let cell = r.short_cell();
+    if !db::get_cells(station).contains(&cell.as_str()) {
+        bad.push(("unknown cell", or_empty(&r.cell)));
+    }
+    if !tst_record::RESULTS.contains(&r.result()) {
+        bad.push(("unknown result", or_empty(&r.result)));
+    }
+    let sn = or_empty(&r.sn);
+    if !SN.is_match(&sn) {
+        bad.push(("malformed sn", sn));
+    }
+    if r.pid.as_deref().unwrap_or("").trim().is_empty() || r.pn.as_deref().unwrap_or("").trim().is_empty() {
+        bad.push(("empty pid/pn", format!("{} / {}", or_empty(&r.pid), or_empty(&r.pn))));
+    }
+    match r.time_int {
+        None => bad.push(("no time", "".to_string())),
+        Some(ts) => {
+            if let Some(prev) = prev_ts.filter(|p| ts < *p) {
+                bad.push(("time out of order", format!("{} after {}", mytime::ts2vn_str(ts), mytime::ts2vn_str(prev))));
+            }
+            if ts > now + FUTURE_SLACK_SECS {
+                bad.push(("time in future", mytime::ts2vn_str(ts)));
+            }
+        }
+    }
+    bad
+}
+
+fn scan(line: &str, station: &'static str, start_ts: i64, quality: &mut DbQuality) -> Result<(), Box<dyn Error>> {
+    let config = config::get_config();
+    let db_path = config.get_db(line, station).ok_or("no such station")?;
+    quality.db_path = db_path.to_string_lossy().to_string();
+    if !db_path.exists() {
+        return Err("db file not found".into());
+    }
+    let (c, schema) = tst_record::open(db_path)?;
+    quality.missing = schema.missing();
+    // from the first record of the period by id, a record of a wrong time is still in
+    let mut stmt = c.prepare(&format!("select {} from tst_record
+                                       where id >= (select min(id) from tst_record where time_int >= ?1) order by id",
+                                      schema.select()))?;
+    let mut rows = stmt.query([start_ts])?;
+    let now = mytime::now_ts();
+    let mut prev_ts = None;
+    while let Some(row) = rows.next()? {
+        let r = TstRecord::from_row(row)?;
+        quality.rows += 1;
+        for (kind, value) in check(&r, station, prev_ts, now) {
+            if let Some(issue) = quality.issues.iter_mut().find(|x| x.kind == kind) {
+                issue.count += 1;
+                if issue.samples.len() < SAMPLES {
+                    issue.samples.push((r.id, or_empty(&r.beijing_str), or_empty(&r.sn), r.short_cell(),
+                                        r.result().to_string(), value));
+                }
+            }
+        }
+        prev_ts = r.time_int.or(prev_ts);
+    }
+    Ok(())
+}
+
+/// the db of the station since start_ts
+pub fn db_quality(line: &str, station: &'static str, start_ts: i64) -> DbQuality {
+    let mut quality = DbQuality {
+        station,
+        db_path: "".to_string(),
+        rows: 0,
+        missing: Vec::new(),
+        issues: KINDS.iter().map(|kind| Issue { kind, count: 0, samples: Vec::new() }).collect(),
+        error: "".to_string(),
+    };
+    if let Err(e) = scan(line, station, start_ts, &mut quality) {
+        quality.error = e.to_string();
+    }
+    quality
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let good = TstRecord {
+            id: 1,
+            sn: Some("FCH93250000".to_string()),
+            cell: Some("Bgibest Auto FST 1|PCBINT|KEYPAD-1|CELL_69".to_string()),
+            pn: Some("73-1234-05".to_string()),
+            pid: Some("C9200-24T".to_string()),
+            result: Some("P".to_string()),
+            time_int: Some(1000),
+            ..Default::default()
+        };
+        assert!(check(&good, "KEYPAD", Some(900), 1000).is_empty());
+        let kinds = |r: &TstRecord, prev: Option<i64>| check(r, "KEYPAD", prev, 1000).into_iter().map(|x| x.0)
+            .collect::<Vec<_>>();
+        let bad = TstRecord {
+            sn: Some("fch-1".to_string()),
+            cell: Some("Bgibest Auto FST 1|PCBINT|KEYPAD-1|CELL_99".to_string()),
+            pid: None,
+            result: Some("X".to_string()),
+            time_int: Some(2000),
+            ..good.clone()
+        };
+        assert_eq!(kinds(&bad, Some(900)), vec!["unknown cell", "unknown result", "malformed sn", "empty pid/pn",
+                                                 "time in future"]);
+        assert_eq!(kinds(&good, Some(1100)), vec!["time out of order"]);
+        assert_eq!(kinds(&TstRecord { time_int: None, ..good.clone() }, None), vec!["no time"]);
+        assert_eq!(kinds(&good, None).len(), 0);
+        // the cell of another station is unknown too
+        assert_eq!(check(&good, "LCDLED", None, 1000)[0].0, "unknown cell");
+    }
+}
@@ -1,9 +1,16 @@
-use rusqlite::{Connection, Rows};
+use rusqlite::{Connection, OpenFlags, Rows};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
 use std::error::Error;
 use std::io::Error as IoError;
 use std::io::ErrorKind::NotFound;
-use crate::analysis::config::CONFIG;
+use std::collections::HashMap;
+use crate::analysis::{config, fail_tag, query_cache, tst_record};
+use crate::analysis::tst_record::{or_empty, TstRecord};
+use crate::analysis::fail_class::{self, FailClass};
 use crate::analysis::mytime;
+use crate::analysis::yield_rate::{self, YieldView};
+use tracing::{debug, error, warn};
 
 static LINES: [&str; 4] = ["bst1", "bst2", "fst1", "fst2"];
 static LCDLED_CELL: [&str; 6] = ["CELL_81", "CELL_82", "CELL_83", "CELL_84", "CELL_85", "CELL_86"];
@@ -15,6 +22,18 @@ static BST_CELL: [&str; 8] = [
     "BST_01:DUT_01", "BST_01:DUT_02", "BST_01:DUT_03", "BST_01:DUT_04",
     "BST_01:DUT_05", "BST_01:DUT_06", "BST_01:DUT_07", "BST_01:DUT_08", ];
 
+/// the row count of the query and the bad rows skipped, fields of its span, see data_quality.rs for the bad rows
+fn rows_read(rows: usize, skipped: usize) {
+    let span = tracing::Span::current();
+    span.record("rows", rows);
+    span.record("skipped", skipped);
+    if skipped > 0 {
+        warn!(rows, skipped, "db query skipped the rows of unknown cell, result or time");
+    } else {
+        debug!(rows, "db query done");
+    }
+}
+
 pub fn cell2station(cell: &str) -> Option<&str> {
     if LCDLED_CELL.contains(&cell) {
         Some("LCDLED")
@@ -29,16 +48,17 @@ pub fn cell2station(cell: &str) -> Option<&str> {
     }
 }
 
-fn get_index(ts_per_hour: [(u32, u32); 12], ts: u32) -> usize {
-    for (i, x) in ts_per_hour.iter().enumerate() {
-        if x.0 <= ts && ts < x.1 {
-            return i;
-        }
-    }
-    unreachable!("ts index");
+/// the hour of the shift, None if out of the shift
+fn get_index(ts_per_hour: [(u32, u32); 12], ts: u32) -> Option<usize> {
+    ts_per_hour.iter().position(|x| x.0 <= ts && ts < x.1)
+}
+
+/// the stations of the line, one db file each
+pub fn stations_of(line: &str) -> Vec<&'static str> {
+    if line.contains("bst") { vec!["BST"] } else { vec!["LCDLED", "DIAG", "KEYPAD"] }
 }
 
-fn get_cells(station: &str) -> Vec<&str> {
+pub fn get_cells(station: &str) -> Vec<&str> {
     match station {
         "BST" => Vec::from(BST_CELL),
         "LCDLED" => Vec::from(LCDLED_CELL),
@@ -48,39 +68,58 @@ fn get_cells(station: &str) -> Vec<&str> {
     }
 }
 
-fn get_cell_index(cell: &str, station: &str) -> usize {
-    let cells = get_cells(station);
-    assert!(cells.contains(&cell));
-    for (i, x) in cells.iter().enumerate() {
-        if x == &cell {
-            return i;
-        }
-    }
-    unreachable!("cell index");
+/// None if the cell is not one of the station
+fn get_cell_index(cell: &str, station: &str) -> Option<usize> {
+    get_cells(station).iter().position(|x| x == &cell)
 }
 
-type Rstation<'a> = (&'a str, u16, u16, u16, u16, String);
+/// a cell of the station overview, by the last records of the cell
+#[derive(Debug, Clone, Default)]
+pub struct StationCell<'a> {
+    pub cell: &'a str,
+    pub start: u16,
+    pub pass: u16,
+    pub fail: u16,
+    pub unlock: u16,
+    // the fail yield F/S, "" if nothing failed
+    pub fail_yield: String,
+    // the yield of the define of the station
+    pub yield_view: YieldView,
+    // the fails tagged fixture by engineer
+    pub fixture_fails: u16,
+}
 
-/// return ("CELL_85", 399, 348, 51, 2, "12.8 %")
-fn rows_to_station_yield<'a>(cell_name: &'a str, mut rows: Rows) -> Rstation<'a> {
-    let mut res = (cell_name, 0, 0, 0, 0, "".to_string());
-    while let Some(row) = rows.next().unwrap() {
-        let (r, q) = (row.get::<_, String>(0).unwrap(), row.get::<_, u16>(1).unwrap());
+impl StationCell<'_> {
+    fn records(&self) -> usize {
+        self.start as usize + self.pass as usize + self.fail as usize + self.unlock as usize
+    }
+}
+
+/// return StationCell{"CELL_85", 399, 348, 51, 2, "12.8 %", YieldView{"87.2 %", "yield_bad", "P / (P+F)"}, 0}
+/// and the quantity of unknown result codes
+fn rows_to_station_yield<'a>(cell_name: &'a str, station: &str, mut rows: Rows)
+                             -> rusqlite::Result<(StationCell<'a>, usize)> {
+    let mut res = StationCell { cell: cell_name, ..Default::default() };
+    let mut skipped = 0;
+    while let Some(row) = rows.next()? {
+        let (r, q) = (tst_record::text(row, 0)?.unwrap_or_default(), row.get::<_, u16>(1)?);
         match r.as_str() {
-            "S" => { res.1 = q; }
-            "P" => { res.2 = q; }
-            "F" => { res.3 = q; }
-            "U" => { res.4 = q; }
-            _ => ()
+            "S" => { res.start = q; }
+            "P" => { res.pass = q; }
+            "F" => { res.fail = q; }
+            "U" => { res.unlock = q; }
+            _ => { skipped += q as usize; }
         }
     }
-    if res.1 != 0 && res.3 != 0 {
-        res.5 = format!("{:.1} %", 100.0 * res.3 as f32 / res.1 as f32);
+    if res.start != 0 && res.fail != 0 {
+        res.fail_yield = format!("{:.1} %", 100.0 * res.fail as f32 / res.start as f32);
     }
-    res
+    res.yield_view = yield_rate::station_yield(station, (res.start, res.pass, res.fail, res.unlock));
+    Ok((res, skipped))
 }
 
-pub fn query_station_yield<'a>(line: &str, station: &str, count: u16) -> Result<Vec<Rstation<'a>>, Box<dyn Error>> {
+#[tracing::instrument(level = "debug", skip_all, fields(line = %line, station = %station, rows, skipped))]
+pub fn query_station_yield<'a>(line: &str, station: &str, count: u16) -> Result<Vec<StationCell<'a>>, Box<dyn Error>> {
     assert!(LINES.contains(&line));
     let _line = match line {
         "fst1" => "1",
@@ -95,13 +134,15 @@ pub fn query_station_yield<'a>(line: &str, station: &str, count: u16) -> Result<
         "KEYPAD" => Vec::from(KEYPAD_CELL),
         _ => Vec::from(AP3_CELL)
     };
-    let db_path = CONFIG.get().unwrap().get_db(line, station).unwrap();
+    let config = config::get_config();
+    let db_path = config.get_db(line, station).unwrap();
     if !db_path.exists() {
-        eprintln!("Error: for {line} {station}, db_path {db_path:?} not exist!");
+        error!("for {line} {station}, db_path {db_path:?} not exist!");
         return Err(Box::new(IoError::new(NotFound, "db file not found")));
     }
-    let c = Connection::open(db_path)?;
+    let (c, _) = tst_record::open(db_path)?;
     let mut v = Vec::new();
+    let mut skipped = 0;
     for cell in cells {
         let cell_name = if line.contains("fst") {
             format!("Bgibest Auto FST {_line}|PCBINT|{station}-{_line}|{cell}")
@@ -112,49 +153,105 @@ pub fn query_station_yield<'a>(line: &str, station: &str, count: u16) -> Result<
                                        (select result from tst_record where cell=?1 order by id desc limit 0, ?2)
                                         group by result")?;
         let rows = stmt.query(rusqlite::params![cell_name, count * 2])?;
-        let res = rows_to_station_yield(cell, rows);
+        let (mut res, n) = rows_to_station_yield(cell, station, rows)?;
+        skipped += n;
+        // the fixture-caused fails in the same records of the yield
+        let min_id = c.query_row("select min(id) from (select id from tst_record where cell=?1 order by id desc limit 0, ?2)",
+                                 rusqlite::params![cell_name, count * 2], |row| row.get::<_, Option<i64>>(0))?;
+        if let Some(min_id) = min_id {
+            res.fixture_fails = fail_tag::count_fixture(line, station, db_path, cell, min_id)?;
+        }
         v.push(res);
     }
+    rows_read(v.iter().map(|x| x.records()).sum::<usize>() + skipped, skipped);
     Ok(v)
 }
 
-pub type Rcell = (u16, String, String, String, String, String, String, String, String);
-pub type RcellYield = ((u16, u16, u16, u16), Vec<Rcell>);
+/// a fail record of query_cell and fail_detail. the tag and the category are not cached with the record,
+/// they change with server.db and config.toml
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FailRecord {
+    // the order in the records of the cell, 0 on fail_detail
+    #[serde(skip)]
+    pub seq: u16,
+    pub time: String,
+    pub sn: String,
+    pub pid: String,
+    pub pn: String,
+    pub result: String,
+    // "BST_01:DUT_02"
+    pub cell: String,
+    pub msg: String,
+    pub msg_detail: String,
+    // id of tst_record
+    pub id: i64,
+    // the root cause tag and its comment, "" if not tagged
+    #[serde(skip)]
+    pub cause: String,
+    #[serde(skip)]
+    pub comment: String,
+    #[serde(skip)]
+    pub class: FailClass,
+}
+
+impl FailRecord {
+    fn from_record(r: &TstRecord) -> FailRecord {
+        FailRecord {
+            time: or_empty(&r.beijing_str),
+            sn: real_sn(&r.sn),
+            pid: or_empty(&r.pid),
+            pn: or_empty(&r.pn),
+            result: r.result().to_string(),
+            //"UCEBU Automatic BST New|PCBDG|BST-01|BST_01:DUT_02" -> BST_01:DUT_02
+            cell: r.short_cell(),
+            msg: or_empty(&r.msg),
+            msg_detail: or_empty(&r.msg_detail),
+            id: r.id,
+            ..Default::default()
+        }
+    }
+}
+
+// ((start, pass, fail, unlock), the fail and unlock records)
+pub type RcellYield = ((u16, u16, u16, u16), Vec<FailRecord>);
 
-fn row_to_cell(mut rows: Rows) -> RcellYield {
+/// the sn of a golden sample is not shown
+fn real_sn(sn: &Option<String>) -> String {
+    match sn.as_deref() {
+        Some("FCH11111111") | Some("FCH12345678") | None => "".to_string(),
+        Some(x) => x.to_string(),
+    }
+}
+
+/// and the quantity of unknown result codes
+fn row_to_cell(mut rows: Rows, station: &str) -> rusqlite::Result<(RcellYield, usize)> {
+    let config = config::get_config();
     let (mut s, mut p, mut f, mut u) = (0, 0, 0, 0);
+    let mut skipped = 0;
     let mut seq = 0u16;
     let mut fail_data = Vec::new();
-    while let Some(row) = rows.next().unwrap() {
+    while let Some(row) = rows.next()? {
         seq += 1;
-        // let id = row.get::<_, u32>(0).unwrap();
-        let beijing_str = row.get::<_, String>(1).unwrap();
-        let _sn = row.get::<_, String>(2).unwrap();
-        let sn = if _sn == "FCH11111111" || _sn == "FCH12345678" { "".to_string() } else { _sn };
-        let pid = row.get::<_, String>(3).unwrap();
-        let pn = row.get::<_, String>(4).unwrap();
-        let result = row.get::<_, String>(5).unwrap();
-        match result.as_str() {
+        let r = TstRecord::from_row(row)?;
+        match r.result() {
             "S" => { s += 1; }
             "P" => { p += 1; }
             "F" => { f += 1; }
             "U" => { u += 1; }
-            _ => ()
+            _ => { skipped += 1; }
         }
-        //"UCEBU Automatic BST New|PCBDG|BST-01|BST_01:DUT_02"
-        let _cell = row.get::<_, String>(6).unwrap();
-        let sp: Vec<&str> = _cell.split('|').collect();
-        let cell = sp[sp.len() - 1].to_string();
-        let msg = row.get::<_, String>(7).unwrap();
-        let msg_detail = row.get::<_, String>(8).unwrap();
-        if result == "F" || result == "U" {
-            fail_data.push((seq, beijing_str, sn, pid, pn, result, cell, msg, msg_detail));
+        if r.result() == "F" || r.result() == "U" {
+            let mut x = FailRecord::from_record(&r);
+            x.seq = seq;
+            x.class = fail_class::classify(&config.fail_rule, station, &x.msg, &x.msg_detail);
+            fail_data.push(x);
         }
     }
-    ((s, p, f, u), fail_data)
+    Ok((((s, p, f, u), fail_data), skipped))
 }
 
 
+#[tracing::instrument(level = "debug", skip_all, fields(line = %line, cell = %cell, rows, skipped))]
 pub fn query_cell(line: &str, cell: &str, count: u16) -> Result<RcellYield, Box<dyn Error>> {
     assert!(LINES.contains(&line));
     let _line = match line {
@@ -163,105 +260,307 @@ pub fn query_cell(line: &str, cell: &str, count: u16) -> Result<RcellYield, Box<
         _ => "1"
     };
     let station = cell2station(cell).unwrap();
-    let db_path = CONFIG.get().unwrap().get_db(line, station).unwrap();
+    let config = config::get_config();
+    let db_path = config.get_db(line, station).unwrap();
     if !db_path.exists() {
-        eprintln!("Error: for {line} {station}, db_path {db_path:?} not exist!");
+        error!("for {line} {station}, db_path {db_path:?} not exist!");
         return Err(Box::new(IoError::new(NotFound, "db file not found")));
     }
-    let c = Connection::open(db_path)?;
+    let (c, schema) = tst_record::open(db_path)?;
     let cell_name = if line.contains("fst") {
         format!("Bgibest Auto FST {_line}|PCBINT|{station}-{_line}|{cell}")
     } else {
         format!("UCEBU Automatic BST New|PCBDG|{station}-01|{cell}")
     };
-    let mut stmt = c.prepare("select id,beijing_str,sn,pid,pn,result,cell,msg,msg_detail from tst_record '
-    'where cell=?1 order by id desc limit 0, ?2")?;
+    let mut stmt = c.prepare(&format!("select {} from tst_record where cell=?1 order by id desc limit 0, ?2",
+                                      schema.select()))?;
     let rows = stmt.query(rusqlite::params![cell_name, count * 2])?;
-    let (res_yield, fail_data) = row_to_cell(rows);
+    let ((res_yield, mut fail_data), skipped) = row_to_cell(rows, station)?;
+    rows_read(res_yield.0 as usize + res_yield.1 as usize + res_yield.2 as usize + res_yield.3 as usize + skipped,
+              skipped);
+    let tags = tags_of(line, station, db_path, fail_data.iter().map(|x| x.id))?;
+    for x in fail_data.iter_mut() {
+        if let Some((cause, comment)) = tags.get(&x.id) {
+            (x.cause, x.comment) = (cause.to_owned(), comment.to_owned());
+        }
+    }
     Ok((res_yield, fail_data))
 }
 
-pub type Rdetail = (String, String, String, String, String, String, String, String);
+/// the root cause tags of the records, by the range of the record ids
+fn tags_of(line: &str, station: &str, db_path: &Path, ids: impl Iterator<Item=i64> + Clone)
+           -> Result<HashMap<i64, (String, String)>, Box<dyn Error>> {
+    match (ids.clone().min(), ids.max()) {
+        (Some(min_id), Some(max_id)) => fail_tag::of_records(line, station, db_path, min_id, max_id),
+        _ => Ok(HashMap::new()),
+    }
+}
 
-fn row_to_detail(mut rows: Rows) -> Vec<Rdetail> {
+fn row_to_detail(mut rows: Rows) -> rusqlite::Result<Vec<FailRecord>> {
     let mut fail_data = Vec::new();
-    while let Some(row) = rows.next().unwrap() {
-        let beijing_str = row.get::<_, String>(1).unwrap();
-        let _sn = row.get::<_, String>(2).unwrap();
-        let sn = if _sn == "FCH11111111" || _sn == "FCH12345678" { "".to_string() } else { _sn };
-        let pid = row.get::<_, String>(3).unwrap();
-        let pn = row.get::<_, String>(4).unwrap();
-        let result = row.get::<_, String>(5).unwrap();
-        //UCEBU Automatic BST New|PCBDG|BST-01|BST_01:DUT_02 -> BST_01:DUT_02
-        let _cell = row.get::<_, String>(6).unwrap();
-        let sp: Vec<&str> = _cell.split('|').collect();
-        let cell = sp[sp.len() - 1].to_string();
-        let msg = row.get::<_, String>(7).unwrap();
-        let msg_detail = row.get::<_, String>(8).unwrap();
-        fail_data.push((beijing_str, sn, pid, pn, result, cell, msg, msg_detail));
+    while let Some(row) = rows.next()? {
+        fail_data.push(FailRecord::from_record(&TstRecord::from_row(row)?));
     }
-    fail_data
+    Ok(fail_data)
 }
 
-pub fn fail_detail(line: &str, station: &str, dateshift: &mytime::DateShift) -> Result<Vec<Rdetail>, Box<dyn Error>> {
-    let (start_ts, end_ts) = mytime::start_end_of_shift(dateshift);
-    let db_path = CONFIG.get().unwrap().get_db(line, station).unwrap();
+#[tracing::instrument(level = "debug", skip_all, fields(line = %line, station = %station, rows, skipped))]
+fn raw_detail(line: &str, station: &str, start_ts: i64, end_ts: i64) -> Result<Vec<FailRecord>, Box<dyn Error>> {
+    let config = config::get_config();
+    let db_path = config.get_db(line, station).unwrap();
     if !db_path.exists() {
-        eprintln!("Error: for {line} {station}, db_path {db_path:?} not exist!");
+        error!("for {line} {station}, db_path {db_path:?} not exist!");
         return Err(Box::new(IoError::new(NotFound, "db file not found")));
     }
-    let c = Connection::open(db_path)?;
-    let mut stmt = c.prepare(r#"select id,beijing_str,sn,pid,pn,result,cell,msg,msg_detail from tst_record
-                             where (result="F" or result="U") and ?1<=time_int and time_int<?2"#)?;
+    let (c, schema) = tst_record::open(db_path)?;
+    let mut stmt = c.prepare(&format!(r#"select {} from tst_record
+                                      where (result="F" or result="U") and ?1<=time_int and time_int<?2"#,
+                                      schema.select()))?;
     let rows = stmt.query([start_ts, end_ts])?;
-    Ok(row_to_detail(rows))
+    let detail = row_to_detail(rows)?;
+    rows_read(detail.len(), 0);
+    Ok(detail)
 }
 
-fn row_to_yield<'a>(mut rows: Rows, ts_per_hour: [(u32, u32); 12], hours_str: &'a [String]) -> [(&'a str, u16, u16, u16, u16); 13] {
-    let mut z = [("", 0, 0, 0, 0); 13];
-    for i in 0..13 {
-        z[i].0 = hours_str[i].as_str();
+/// the fail records of the shift, the records of a closed shift are cached. the tags and categories are not,
+/// they change with server.db and config.toml
+pub fn fail_detail(line: &str, station: &str, dateshift: &mytime::DateShift) -> Result<Vec<FailRecord>, Box<dyn Error>> {
+    let (start_ts, end_ts) = mytime::start_end_of_shift(dateshift);
+    let config = config::get_config();
+    let raw = query_cache::cached("detail", line, station, start_ts, end_ts,
+                                  || raw_detail(line, station, start_ts, end_ts))?;
+    let db_path = config.get_db(line, station).ok_or("no such station")?;
+    let tags = tags_of(line, station, db_path, raw.iter().map(|x| x.id))?;
+    let fail_data = raw.into_iter().map(|mut x| {
+        x.class = fail_class::classify(&config.fail_rule, station, &x.msg, &x.msg_detail);
+        (x.cause, x.comment) = tags.get(&x.id).cloned().unwrap_or_default();
+        x
+    }).collect();
+    Ok(fail_data)
+}
+
+// (cell, (start, pass, fail, unlock))
+pub type RcellCount = (String, (u32, u32, u32, u32));
+
+/// the result quantity of every cell of the station tested in the time window, sorted by cell
+#[tracing::instrument(level = "debug", skip_all, fields(line = %line, station = %station, rows, skipped))]
+pub fn cell_counts(line: &str, station: &str, start_ts: i64, end_ts: i64) -> Result<Vec<RcellCount>, Box<dyn Error>> {
+    let config = config::get_config();
+    let db_path = config.get_db(line, station).unwrap();
+    if !db_path.exists() {
+        error!("for {line} {station}, db_path {db_path:?} not exist!");
+        return Err(Box::new(IoError::new(NotFound, "db file not found")));
+    }
+    let (c, _) = tst_record::open(db_path)?;
+    let mut stmt = c.prepare("select cell,result,count(result) from tst_record where ?1<=time_int and time_int<?2 \
+                              group by cell,result")?;
+    let mut rows = stmt.query([start_ts, end_ts])?;
+    let mut counts: Vec<RcellCount> = Vec::new();
+    let mut skipped = 0;
+    while let Some(row) = rows.next()? {
+        let _cell = tst_record::text(row, 0)?.unwrap_or_default();
+        let cell = _cell.rsplit('|').next().unwrap_or("").to_string();
+        let result = tst_record::text(row, 1)?.unwrap_or_default();
+        let n = row.get::<_, u32>(2)?;
+        if get_cell_index(&cell, station).is_none() || !tst_record::RESULTS.contains(&result.as_str()) {
+            skipped += n as usize;
+            continue;
+        }
+        let i = match counts.iter().position(|x| x.0 == cell) {
+            Some(i) => i,
+            None => {
+                counts.push((cell, (0, 0, 0, 0)));
+                counts.len() - 1
+            }
+        };
+        let x = &mut counts[i].1;
+        match result.as_str() {
+            "S" => { x.0 += n; }
+            "P" => { x.1 += n; }
+            "F" => { x.2 += n; }
+            "U" => { x.3 += n; }
+            _ => ()
+        }
+    }
+    counts.sort();
+    rows_read(counts.iter().map(|x| x.1.0 as usize + x.1.1 as usize + x.1.2 as usize + x.1.3 as usize).sum::<usize>()
+              + skipped, skipped);
+    Ok(counts)
+}
+
+/// (fail msg, quantity) of the F and U records of the station in the time window, the most one first
+#[tracing::instrument(level = "debug", skip_all, fields(line = %line, station = %station, rows, skipped))]
+pub fn fail_msg_counts(line: &str, station: &str, start_ts: i64, end_ts: i64) -> Result<Vec<(String, u32)>, Box<dyn Error>> {
+    let config = config::get_config();
+    let db_path = config.get_db(line, station).unwrap();
+    if !db_path.exists() {
+        error!("for {line} {station}, db_path {db_path:?} not exist!");
+        return Err(Box::new(IoError::new(NotFound, "db file not found")));
+    }
+    let (c, schema) = tst_record::open(db_path)?;
+    let msg = schema.text("msg");
+    let mut stmt = c.prepare(&format!(r#"select {msg},count(*) as n from tst_record
+                                      where (result="F" or result="U") and ?1<=time_int and time_int<?2
+                                      group by {msg} order by n desc, {msg}"#))?;
+    let counts = stmt.query_map([start_ts, end_ts], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    rows_read(counts.iter().map(|x: &(String, u32)| x.1 as usize).sum(), 0);
+    Ok(counts)
+}
+
+// (fail msg, cell, quantity)
+pub type RmsgCell = (String, String, u32);
+
+/// the F and U records of the station in the time window grouped by fail msg and cell, the most one first
+#[tracing::instrument(level = "debug", skip_all, fields(line = %line, station = %station, rows, skipped))]
+pub fn fail_msg_cell_counts(line: &str, station: &str, start_ts: i64, end_ts: i64) -> Result<Vec<RmsgCell>, Box<dyn Error>> {
+    let config = config::get_config();
+    let db_path = config.get_db(line, station).unwrap();
+    if !db_path.exists() {
+        error!("for {line} {station}, db_path {db_path:?} not exist!");
+        return Err(Box::new(IoError::new(NotFound, "db file not found")));
+    }
+    let (c, schema) = tst_record::open(db_path)?;
+    let msg = schema.text("msg");
+    let mut stmt = c.prepare(&format!(r#"select {msg},cell,count(*) as n from tst_record
+                                      where (result="F" or result="U") and ?1<=time_int and time_int<?2
+                                      group by {msg},cell order by n desc, {msg}"#))?;
+    let mut rows = stmt.query([start_ts, end_ts])?;
+    let mut counts = Vec::new();
+    while let Some(row) = rows.next()? {
+        let _cell = tst_record::text(row, 1)?.unwrap_or_default();
+        let cell = _cell.rsplit('|').next().unwrap_or("").to_string();
+        counts.push((row.get(0)?, cell, row.get(2)?));
     }
-    while let Some(row) = rows.next().unwrap() {
-        let (res, ts) = (row.get::<_, String>(0).unwrap(), row.get::<_, f32>(1).unwrap() as u32);
-        let i = get_index(ts_per_hour, ts);
+    rows_read(counts.iter().map(|x: &RmsgCell| x.2 as usize).sum(), 0);
+    Ok(counts)
+}
+
+/// time_int of the latest record of the station, None if the db is empty
+pub fn last_test(line: &str, station: &str) -> Result<Option<i64>, Box<dyn Error>> {
+    let config = config::get_config();
+    let db_path = config.get_db(line, station).unwrap();
+    if !db_path.exists() {
+        error!("for {line} {station}, db_path {db_path:?} not exist!");
+        return Err(Box::new(IoError::new(NotFound, "db file not found")));
+    }
+    let (c, _) = tst_record::open(db_path)?;
+    let ts = c.query_row("select max(time_int) from tst_record", [], |row| row.get::<_, Option<f64>>(0))?;
+    Ok(ts.map(|x| x as i64))
+}
+
+/// the id of the latest record of the station, None if the db is empty
+pub fn last_id(line: &str, station: &str) -> Result<Option<i64>, Box<dyn Error>> {
+    let config = config::get_config();
+    let db_path = config.get_db(line, station).unwrap();
+    if !db_path.exists() {
+        return Err(Box::new(IoError::new(NotFound, "db file not found")));
+    }
+    let (c, _) = tst_record::open(db_path)?;
+    Ok(c.query_row("select max(id) from tst_record", [], |row| row.get::<_, Option<i64>>(0))?)
+}
+
+// (cell, msg, msg_detail)
+pub type RkeypadFail = (String, String, String);
+
+/// the KEYPAD fails in the time window, cell "" is all the cells of the line
+#[tracing::instrument(level = "debug", skip_all, fields(line = %line, station = "KEYPAD", cell = %cell, rows, skipped))]
+pub fn keypad_fails(line: &str, cell: &str, start_ts: i64, end_ts: i64) -> Result<Vec<RkeypadFail>, Box<dyn Error>> {
+    let config = config::get_config();
+    let db_path = match config.get_db(line, "KEYPAD") {
+        Some(p) => p,
+        // bst line has no keypad station
+        None => return Ok(Vec::new()),
+    };
+    if !db_path.exists() {
+        error!("for {line} KEYPAD, db_path {db_path:?} not exist!");
+        return Err(Box::new(IoError::new(NotFound, "db file not found")));
+    }
+    let (c, schema) = tst_record::open(db_path)?;
+    let mut stmt = c.prepare(&format!(r#"select {} from tst_record
+                                      where (result="F" or result="U") and ?1<=time_int and time_int<?2"#,
+                                      schema.select()))?;
+    let mut rows = stmt.query([start_ts, end_ts])?;
+    let mut fails = Vec::new();
+    while let Some(row) = rows.next()? {
+        //Bgibest Auto FST 1|PCBINT|KEYPAD-1|CELL_69 -> CELL_69
+        let r = TstRecord::from_row(row)?;
+        let _cell = r.short_cell();
+        if cell.is_empty() || _cell == cell {
+            fails.push((_cell, or_empty(&r.msg), or_empty(&r.msg_detail)));
+        }
+    }
+    rows_read(fails.len(), 0);
+    Ok(fails)
+}
+
+// (start, pass, fail, unlock) of every hour, the last is the sum
+type RhourCounts = Vec<(u16, u16, u16, u16)>;
+
+/// and the quantity of rows of unknown result or out of the shift
+fn row_to_yield(mut rows: Rows, ts_per_hour: [(u32, u32); 12]) -> rusqlite::Result<(RhourCounts, usize)> {
+    let mut z = vec![(0, 0, 0, 0); 13];
+    let mut skipped = 0;
+    while let Some(row) = rows.next()? {
+        let res = tst_record::text(row, 0)?.unwrap_or_default();
+        let i = match tst_record::int(row, 1)?.and_then(|ts| get_index(ts_per_hour, ts as u32)) {
+            Some(i) => i,
+            None => {
+                skipped += 1;
+                continue;
+            }
+        };
         match res.as_str() {
             "S" => {
+                z[i].0 += 1;
+                z[12].0 += 1
+            }
+            "P" => {
                 z[i].1 += 1;
                 z[12].1 += 1
             }
-            "P" => {
+            "F" => {
                 z[i].2 += 1;
                 z[12].2 += 1
             }
-            "F" => {
+            "U" => {
                 z[i].3 += 1;
                 z[12].3 += 1
             }
-            "U" => {
-                z[i].4 += 1;
-                z[12].4 += 1
-            }
-            _ => ()
+            _ => { skipped += 1; }
         }
     }
-    z
+    Ok((z, skipped))
 }
 
-type RdayYield<'a> = [(&'a str, u16, u16, u16, u16); 13];
+pub type RdayYield<'a> = Vec<(&'a str, u16, u16, u16, u16, YieldView)>;
 
-pub fn day_yield<'a>(line: &str, station: &str, ts_per_hour: [(u32, u32); 12], hours_str: &'a [String])
-                     -> Result<RdayYield<'a>, Box<dyn Error>> {
+#[tracing::instrument(level = "debug", skip_all, fields(line = %line, station = %station, rows, skipped))]
+fn yield_counts(line: &str, station: &str, ts_per_hour: [(u32, u32); 12]) -> Result<RhourCounts, Box<dyn Error>> {
     let (start_ts, end_ts) = (ts_per_hour[0].0, ts_per_hour[11].1);
-    let db_path = CONFIG.get().unwrap().get_db(line, station).unwrap();
+    let config = config::get_config();
+    let db_path = config.get_db(line, station).unwrap();
     if !db_path.exists() {
-        eprintln!("Error: for {line} {station}, db_path {db_path:?} not exist!");
+        error!("for {line} {station}, db_path {db_path:?} not exist!");
         return Err(Box::new(IoError::new(NotFound, "db file not found")));
     }
-    let c = Connection::open(db_path)?;
+    let (c, _) = tst_record::open(db_path)?;
     let mut stmt = c.prepare("select result,time_int from tst_record where ?1<=time_int and time_int<?2")?;
     let rows = stmt.query([start_ts, end_ts])?;
-    Ok(row_to_yield(rows, ts_per_hour, hours_str))
+    let (z, skipped) = row_to_yield(rows, ts_per_hour)?;
+    rows_read(z[12].0 as usize + z[12].1 as usize + z[12].2 as usize + z[12].3 as usize + skipped, skipped);
+    Ok(z)
+}
+
+/// the counts of a closed shift are cached, the yield is not, the threshold change with config.toml
+pub fn day_yield<'a>(line: &str, station: &str, ts_per_hour: [(u32, u32); 12], hours_str: &'a [String])
+                     -> Result<RdayYield<'a>, Box<dyn Error>> {
+    let (start_ts, end_ts) = (ts_per_hour[0].0 as i64, ts_per_hour[11].1 as i64);
+    let z = query_cache::cached("yield", line, station, start_ts, end_ts,
+                                || yield_counts(line, station, ts_per_hour))?;
+    Ok(z.into_iter().enumerate()
+        .map(|(i, x)| (hours_str[i].as_str(), x.0, x.1, x.2, x.3, yield_rate::station_yield(station, x)))
+        .collect())
 }
 
 pub type Rpf<'a> = (&'a str, String, String, String, String, String, String, String, String, String);
@@ -300,7 +599,7 @@ fn array2str(array: Vec<[(u16, u16); 9]>) -> Vec<[String; 9]> {
     v
 }
 
-fn array2rfp(array_str: Vec<[String; 9]>, hours_str: &[String]) -> Vec<Rpf> {
+fn array2rfp(array_str: Vec<[String; 9]>, hours_str: &[String]) -> Vec<Rpf<'_>> {
     let mut r = Vec::new();
     for i in 0..13 {
         let x: Rpf = (hours_str[i].as_str(), array_str[i][0].to_owned(), array_str[i][1].to_owned(), array_str[i][2].to_owned(),
@@ -312,17 +611,27 @@ fn array2rfp(array_str: Vec<[String; 9]>, hours_str: &[String]) -> Vec<Rpf> {
 }
 
 
-pub fn row_to_pf<'a>(mut rows: Rows, station: &str, ts_per_hour: [(u32, u32); 12], hours_str: &'a [String])
-                     -> Vec<Rpf<'a>> {
+// (pass, fail) of the station and every cell, every hour and the sum
+type RpfCounts = Vec<[(u16, u16); 9]>;
+
+/// and the quantity of rows of unknown cell, result or out of the shift
+fn row_to_pf(mut rows: Rows, station: &str, ts_per_hour: [(u32, u32); 12]) -> rusqlite::Result<(RpfCounts, usize)> {
     let mut v = rpft_default();
-    while let Some(row) = rows.next().unwrap() {
-        let res = row.get::<_, String>(0).unwrap();
-        let ts = row.get::<_, f32>(1).unwrap() as u32;
-        let _cell = row.get::<_, String>(2).unwrap();
-        let sp: Vec<&str> = _cell.split('|').collect();
-        let cell = sp[sp.len() - 1].to_string();
-        let i = get_index(ts_per_hour, ts);
+    let mut skipped = 0;
+    while let Some(row) = rows.next()? {
+        let res = tst_record::text(row, 0)?.unwrap_or_default();
+        let ts = tst_record::int(row, 1)?;
+        let _cell = tst_record::text(row, 2)?.unwrap_or_default();
+        let cell = _cell.rsplit('|').next().unwrap_or("").to_string();
+        let i = ts.and_then(|ts| get_index(ts_per_hour, ts as u32));
         let j = get_cell_index(&cell, station);
+        let (i, j) = match (i, j) {
+            (Some(i), Some(j)) if tst_record::RESULTS.contains(&res.as_str()) => (i, j),
+            _ => {
+                skipped += 1;
+                continue;
+            }
+        };
         match res.as_str() {
             "P" => {
                 v[i][j + 1].0 += 1;
@@ -336,62 +645,99 @@ pub fn row_to_pf<'a>(mut rows: Rows, station: &str, ts_per_hour: [(u32, u32); 12
                 v[12][j + 1].1 += 1;
                 v[12][0].1 += 1;
             }
+            // start and unlock are not in the table
             _ => ()
         }
     }
-    let array_str = array2str(v);
-    array2rfp(array_str, hours_str)
+    Ok((v, skipped))
 }
 
-pub fn pf_data<'a>(line: &str, station: &str, ts_per_hour: [(u32, u32); 12], hours_str: &'a [String])
-                   -> Result<Vec<Rpf<'a>>, Box<dyn Error>> {
+#[tracing::instrument(level = "debug", skip_all, fields(line = %line, station = %station, rows, skipped))]
+fn pf_counts(line: &str, station: &str, ts_per_hour: [(u32, u32); 12]) -> Result<RpfCounts, Box<dyn Error>> {
     let (start_ts, end_ts) = (ts_per_hour[0].0, ts_per_hour[11].1);
-    let db_path = CONFIG.get().unwrap().get_db(line, station).unwrap();
+    let config = config::get_config();
+    let db_path = config.get_db(line, station).unwrap();
     if !db_path.exists() {
-        eprintln!("Error: for {line} {station}, db_path {db_path:?} not exist!");
+        error!("for {line} {station}, db_path {db_path:?} not exist!");
         return Err(Box::new(IoError::new(NotFound, "db file not found")));
     }
-    let c = Connection::open(db_path)?;
+    let (c, _) = tst_record::open(db_path)?;
     let mut stmt = c.prepare("select result,time_int,cell from tst_record where ?1<=time_int and time_int<?2")?;
     let rows = stmt.query([start_ts, end_ts])?;
-    Ok(row_to_pf(rows, station, ts_per_hour, hours_str))
+    let (v, skipped) = row_to_pf(rows, station, ts_per_hour)?;
+    rows_read(v[12][0].0 as usize + v[12][0].1 as usize + skipped, skipped);
+    Ok(v)
 }
 
-pub type Rrecord<'a> = (String, String, String, String, &'a str, String, &'a str, String, String, String);
+/// the counts of a closed shift are cached
+pub fn pf_data<'a>(line: &str, station: &str, ts_per_hour: [(u32, u32); 12], hours_str: &'a [String])
+                   -> Result<Vec<Rpf<'a>>, Box<dyn Error>> {
+    let (start_ts, end_ts) = (ts_per_hour[0].0 as i64, ts_per_hour[11].1 as i64);
+    let v = query_cache::cached("pf", line, station, start_ts, end_ts, || pf_counts(line, station, ts_per_hour))?;
+    Ok(array2rfp(array2str(v), hours_str))
+}
 
-fn row_to_rec<'a>(mut rows: Rows, area: &'a str, hostname: &'a str) -> Vec<Rrecord<'a>> {
+pub type Rrecord<'a> = (String, String, String, String, &'a str, String, String, String, String, String);
+
+fn row_to_rec<'a>(mut rows: Rows, area: &'a str, hostname: &str) -> rusqlite::Result<Vec<Rrecord<'a>>> {
     let mut rec_data = Vec::new();
-    while let Some(row) = rows.next().unwrap() {
-        let beijing_str = row.get::<_, String>(0).unwrap();
-        let sn = row.get::<_, String>(1).unwrap();
-        let pid = row.get::<_, String>(2).unwrap();
-        let pn = row.get::<_, String>(3).unwrap();
-        let result = row.get::<_, String>(4).unwrap();
+    while let Some(row) = rows.next()? {
+        let r = TstRecord::from_row(row)?;
         //UCEBU Automatic BST New|PCBDG|BST-01|BST_01:DUT_02 -> BST_01:DUT_02
-        let _cell = row.get::<_, String>(5).unwrap();
-        let sp: Vec<&str> = _cell.split('|').collect();
-        let cell = sp[sp.len() - 1].to_string();
-        let msg = row.get::<_, String>(6).unwrap();
-        let msg_detail = row.get::<_, String>(7).unwrap();
-        rec_data.push((beijing_str, sn, pid, pn, area, result, hostname, cell, msg, msg_detail));
+        rec_data.push((or_empty(&r.beijing_str), or_empty(&r.sn), or_empty(&r.pid), or_empty(&r.pn), area,
+                       r.result().to_string(), hostname.to_owned(), r.short_cell(), or_empty(&r.msg),
+                       or_empty(&r.msg_detail)));
     }
-    rec_data
+    Ok(rec_data)
 }
 
+#[tracing::instrument(level = "debug", skip_all, fields(sn = %sn, rows, skipped))]
 pub fn sn_record<'a>(sn: String) -> Result<Vec<Rrecord<'a>>, Box<dyn Error>> {
     let mut v = Vec::new();
-    let all_db_detail = CONFIG.get().unwrap().get_all_db();
+    let config = config::get_config();
+    let all_db_detail = config.get_all_db();
     for db_detail in all_db_detail.iter() {
         let (area, hostname, db_path) = db_detail;
         if !db_path.exists() {
-            eprintln!("Error: query sn_record for {area} {hostname} db_path {db_path:?} not exist!");
+            error!("query sn_record for {area} {hostname} db_path {db_path:?} not exist!");
         } else {
-            let c = Connection::open(db_path)?;
-            let mut stmt = c.prepare("select beijing_str,sn,pid,pn,result,cell,msg,msg_detail from tst_record where sn=?1")?;
+            // a broken db is skipped, the sn is searched in the other dbs
+            let (c, schema) = match tst_record::open(db_path) {
+                Ok(x) => x,
+                Err(e) => {
+                    warn!("query sn_record for {area} {hostname} open {db_path:?} fail, {e}");
+                    continue;
+                }
+            };
+            if !schema.has("sn") {
+                continue;
+            }
+            let mut stmt = c.prepare(&format!("select {} from tst_record where sn=?1", schema.select()))?;
             let rows = stmt.query([&sn])?;
-            let mut r = row_to_rec(rows, area, hostname);
+            let mut r = row_to_rec(rows, area, hostname)?;
             v.append(&mut r);
         }
     }
+    rows_read(v.len(), 0);
     Ok(v)
 }
+
+/// used by `server validate`, open the db read only and return "N records, latest at ..."
+pub fn check_db(db_path: &Path) -> Result<String, Box<dyn Error>> {
+    if !db_path.exists() {
+        return Err(Box::new(IoError::new(NotFound, "db file not found")));
+    }
+    let c = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let schema = tst_record::detect(&c)?;
+    let latest = if schema.has("beijing_str") { "max(beijing_str)" } else { "NULL" };
+    let (count, latest) = c.query_row(&format!("select count(id), {latest} from tst_record"), [], |row| {
+        Ok((row.get::<_, u32>(0)?, row.get::<_, Option<String>>(1)?))
+    })?;
+    let latest = latest.unwrap_or_default();
+    let missing = schema.missing();
+    if missing.is_empty() {
+        Ok(format!("{count} records, latest at {latest}"))
+    } else {
+        Ok(format!("{count} records, latest at {latest}, no column {} (read as empty)", missing.join(", ")))
+    }
+}
@@ -0,0 +1,129 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+pub static SEVERITIES: [&str; 4] = ["info", "minor", "major", "critical"];
+static STATIONS: [&str; 4] = ["BST", "LCDLED", "DIAG", "KEYPAD"];
+static UNCLASSIFIED: &str = "unclassified";
+
+/// [[fail_rule]] of config.toml, a fail record match the rule when every given field match
+#[derive(Deserialize, Debug, Clone)]
+pub struct FailRule {
+    pub category: String,
+    pub severity: String,
+    // all stations if not given
+    pub station: Option<String>,
+    // regex on msg and msg_detail of tst_record, at least one of them
+    pub msg: Option<String>,
+    pub msg_detail: Option<String>,
+    #[serde(skip)]
+    msg_re: Option<Regex>,
+    #[serde(skip)]
+    msg_detail_re: Option<Regex>,
+}
+
+impl FailRule {
+    /// check the fields and compile the regex, called when load config.toml
+    pub fn compile(&mut self) -> Result<(), String> {
+        if !SEVERITIES.contains(&self.severity.as_str()) {
+            return Err(format!("severity {} should be one of {SEVERITIES:?}", self.severity));
+        }
+        if let Some(station) = &self.station {
+            if !STATIONS.contains(&station.as_str()) {
+                return Err(format!("station {station} should be one of {STATIONS:?}"));
+            }
+        }
+        if self.msg.is_none() && self.msg_detail.is_none() {
+            return Err("msg or msg_detail is required".to_string());
+        }
+        let compile = |x: &Option<String>| x.as_deref().map(Regex::new).transpose().map_err(|e| e.to_string());
+        self.msg_re = compile(&self.msg)?;
+        self.msg_detail_re = compile(&self.msg_detail)?;
+        Ok(())
+    }
+
+    fn is_match(&self, station: &str, msg: &str, msg_detail: &str) -> bool {
+        self.station.as_deref().map(|x| x == station).unwrap_or(true)
+            && self.msg_re.as_ref().map(|re| re.is_match(msg)).unwrap_or(true)
+            && self.msg_detail_re.as_ref().map(|re| re.is_match(msg_detail)).unwrap_or(true)
+    }
+}
+
+/// the category of a fail record, empty when no rule match
+#[derive(Debug, Clone, Default)]
+pub struct FailClass {
+    pub category: String,
+    pub severity: String,
+}
+
+impl FailClass {
+    /// css class defined in general.css
+    pub fn class(&self) -> String {
+        if self.severity.is_empty() { "".to_string() } else { format!("severity_{}", self.severity) }
+    }
+}
+
+/// the first matched rule in the order of config.toml
+pub fn classify(rules: &[FailRule], station: &str, msg: &str, msg_detail: &str) -> FailClass {
+    match rules.iter().find(|r| r.is_match(station, msg, msg_detail)) {
+        Some(r) => FailClass { category: r.category.to_owned(), severity: r.severity.to_owned() },
+        None => FailClass::default(),
+    }
+}
+
+/// (station, category, severity, quantity)
+pub type Rcategory = (String, String, String, u16);
+
+/// quantity of the fails by station and category, the most one first
+pub fn group<'a>(classes: impl Iterator<Item=(&'a str, &'a FailClass)>) -> Vec<Rcategory> {
+    let mut map: HashMap<(&str, &str), (&str, u16)> = HashMap::new();
+    for (station, class) in classes {
+        let category = if class.category.is_empty() { UNCLASSIFIED } else { class.category.as_str() };
+        map.entry((station, category)).or_insert((class.severity.as_str(), 0)).1 += 1;
+    }
+    let mut v: Vec<Rcategory> = map.into_iter()
+        .map(|((station, category), (severity, n))| (station.to_string(), category.to_string(), severity.to_string(), n))
+        .collect();
+    v.sort_by(|a, b| b.3.cmp(&a.3).then(a.0.cmp(&b.0)).then(a.1.cmp(&b.1)));
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let mut rules: Vec<FailRule> = toml::from_str::<HashMap<String, Vec<FailRule>>>(r#"
+            [[fail_rule]]
+            category = "console timeout"
+            severity = "major"
+            station = "DIAG"
+            msg = "(?i)console"
+            msg_detail = "timeout"
+            [[fail_rule]]
+            category = "console other"
+            severity = "minor"
+            msg = "(?i)console"
+        "#).unwrap().remove("fail_rule").unwrap();
+        for r in rules.iter_mut() {
+            r.compile().unwrap();
+        }
+        assert_eq!(classify(&rules, "DIAG", "Console check", "read timeout 30s").category, "console timeout");
+        assert_eq!(classify(&rules, "LCDLED", "Console check", "read timeout 30s").category, "console other");
+        assert_eq!(classify(&rules, "DIAG", "Console check", "").severity, "minor");
+        assert_eq!(classify(&rules, "DIAG", "loop back", "").category, "");
+
+        let a = classify(&rules, "DIAG", "console", "timeout");
+        let b = classify(&rules, "DIAG", "other", "");
+        let v = group(vec![("DIAG", &a), ("DIAG", &a), ("DIAG", &b)].into_iter());
+        assert_eq!(v[0], ("DIAG".to_string(), "console timeout".to_string(), "major".to_string(), 2));
+        assert_eq!(v[1].1, "unclassified");
+
+        rules[0].msg = Some("(".to_string());
+        assert!(rules[0].compile().is_err());
+        rules[0].msg = Some("x".to_string());
+        rules[0].severity = "fatal".to_string();
+        assert!(rules[0].compile().is_err());
+    }
+}
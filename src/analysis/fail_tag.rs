@@ -0,0 +1,86 @@
+use rusqlite::params;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use crate::analysis::{mytime, server_db};
+
+// root cause of a fail record, the tag is keyed by the source db (line, station, db file) and the id of tst_record.
+// a new or rotated db file of the station starts the ids again, the tags of the old file are not shown for it
+pub static CAUSES: [&str; 5] = ["fixture", "dut", "operator", "software", "unknown"];
+pub static COMMENT_MAX_LEN: usize = 200;
+
+/// tag the record, the old tag of the record is replaced. cause "" remove the tag
+#[allow(clippy::too_many_arguments)]
+pub fn set(line: &str, station: &str, db_path: &Path, record_id: i64, cell: &str, cause: &str, comment: &str,
+           user_name: &str) -> Result<(), Box<dyn Error>> {
+    let c = server_db::open()?;
+    let db_path = db_path.to_string_lossy();
+    if cause.is_empty() {
+        c.execute("delete from fail_tag where line=?1 and station=?2 and db_path=?3 and record_id=?4",
+                  params![line, station, db_path, record_id])?;
+        return Ok(());
+    }
+    c.execute("insert or replace into fail_tag (line, station, db_path, record_id, cell, cause, comment, user_name, \
+               time_int) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+              params![line, station, db_path, record_id, cell, cause, comment, user_name, mytime::now_ts()])?;
+    Ok(())
+}
+
+/// record id -> (cause, comment), for the records with min_id <= id <= max_id
+pub fn of_records(line: &str, station: &str, db_path: &Path, min_id: i64, max_id: i64)
+                  -> Result<HashMap<i64, (String, String)>, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let mut stmt = c.prepare("select record_id, cause, comment from fail_tag \
+                              where line=?1 and station=?2 and db_path=?3 and record_id>=?4 and record_id<=?5")?;
+    let mut rows = stmt.query(params![line, station, db_path.to_string_lossy(), min_id, max_id])?;
+    let mut map = HashMap::new();
+    while let Some(row) = rows.next()? {
+        map.insert(row.get::<_, i64>(0)?, (row.get::<_, String>(1)?, row.get::<_, String>(2)?));
+    }
+    Ok(map)
+}
+
+/// quantity of the fails caused by the fixture of the cell, for the records from min_id
+pub fn count_fixture(line: &str, station: &str, db_path: &Path, cell: &str, min_id: i64)
+                     -> Result<u16, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let n = c.query_row("select count(id) from fail_tag \
+                         where line=?1 and station=?2 and db_path=?3 and cell=?4 and cause='fixture' and record_id>=?5",
+                        params![line, station, db_path.to_string_lossy(), cell, min_id], |row| row.get::<_, u16>(0))?;
+    Ok(n)
+}
+
+/// (the latest id, quantity) of the tags of the line, changed by every set of a tag
+pub fn version(line: &str) -> Result<(i64, i64), Box<dyn Error>> {
+    let c = server_db::open()?;
+    let v = c.query_row("select coalesce(max(id), 0), count(id) from fail_tag where line=?1", [line],
+                        |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?;
+    Ok(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag() {
+        server_db::init_test_db();
+        let (db, new_db) = (Path::new("db/fst2/KEYPAD-2.db"), Path::new("db/fst2/KEYPAD-3.db"));
+        set("fst2", "KEYPAD", db, 10, "CELL_69", "fixture", "pogo pin", "tom").unwrap();
+        set("fst2", "KEYPAD", db, 12, "CELL_69", "dut", "", "tom").unwrap();
+        set("fst2", "KEYPAD", db, 14, "CELL_71", "fixture", "", "tom").unwrap();
+        let tags = of_records("fst2", "KEYPAD", db, 10, 12).unwrap();
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags.get(&10), Some(&("fixture".to_string(), "pogo pin".to_string())));
+        assert_eq!(count_fixture("fst2", "KEYPAD", db, "CELL_69", 0).unwrap(), 1);
+        assert_eq!(count_fixture("fst2", "KEYPAD", db, "CELL_69", 11).unwrap(), 0);
+        // the same id of a new db file is another record
+        assert!(of_records("fst2", "KEYPAD", new_db, 0, 100).unwrap().is_empty());
+        assert_eq!(count_fixture("fst2", "KEYPAD", new_db, "CELL_71", 0).unwrap(), 0);
+        // replace, then remove
+        set("fst2", "KEYPAD", db, 12, "CELL_69", "fixture", "", "ann").unwrap();
+        assert_eq!(count_fixture("fst2", "KEYPAD", db, "CELL_69", 0).unwrap(), 2);
+        set("fst2", "KEYPAD", db, 10, "CELL_69", "", "", "ann").unwrap();
+        assert_eq!(of_records("fst2", "KEYPAD", db, 0, 100).unwrap().len(), 2);
+    }
+}
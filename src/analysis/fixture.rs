@@ -0,0 +1,187 @@
+use once_cell::sync::Lazy;
+use rusqlite::params;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::error::Error;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use crate::analysis::{db, mytime, port_config, server_db};
+
+// send the fixture command of the port config page to the fixture port of the terminal server,
+// then wait for the expected reply. every command sent is logged in the server db.
+
+// the reply read at most, a fixture keep talking should not fill the memory
+const REPLY_MAX_LEN: usize = 4096;
+
+/// where to send the command, resolved from the port config
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub host: String,
+    pub port: u16,
+    // "" is any reply, the first data received
+    pub reply: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandResult {
+    // "ok", "timeout" or "error"
+    pub result: String,
+    pub received: String,
+    pub elapsed_ms: u32,
+}
+
+/// (time, name, host:port, command, result, received, user)
+pub type RcommandLog = (String, String, String, String, String, String, String);
+
+// the host:port in use, one command or console at a time for a serial port
+static BUSY: Lazy<Mutex<HashSet<(String, u16)>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// the port is released when dropped
+pub struct PortLock(String, u16);
+
+impl Drop for PortLock {
+    fn drop(&mut self) {
+        BUSY.lock().unwrap().remove(&(self.0.to_owned(), self.1));
+    }
+}
+
+/// None if the port is used by others
+pub fn lock_port(host: &str, port: u16) -> Option<PortLock> {
+    let mut busy = BUSY.lock().unwrap();
+    if busy.insert((host.to_string(), port)) {
+        Some(PortLock(host.to_string(), port))
+    } else {
+        None
+    }
+}
+
+/// the host and port of the name (cell) in the port config of the line. port_index choose one of "2002/2010",
+/// the first one by default. the command should be one of the station of the cell when it is given.
+pub fn resolve(line: &str, name: &str, port_index: usize, command: Option<&str>) -> Result<Target, String> {
+    let host = port_config::server_of(line).map_err(|e| e.to_string())?
+        .ok_or(format!("no terminal server of line {line}"))?.0;
+    let ports = port_config::ports_of(line).map_err(|e| e.to_string())?;
+    let ports = ports.iter().find(|x| x.0 == name).ok_or(format!("{name} is not in the port config of line {line}"))?;
+    let port = port_config::parse_ports(&ports.1).and_then(|x| x.get(port_index).copied())
+        .ok_or(format!("{name} has no port {} in {}", port_index + 1, ports.1))?;
+    let reply = match command {
+        Some(command) => {
+            let station = db::cell2station(name).unwrap_or("");
+            let commands = port_config::commands().map_err(|e| e.to_string())?;
+            commands.into_iter().find(|x| x.0 == station && x.1 == command)
+                .ok_or(format!("{command} is not a command of {name}"))?.2
+        }
+        None => "".to_string(),
+    };
+    Ok(Target { host, port, reply })
+}
+
+/// send the command and read until the reply is received, the reply "" is any data
+pub async fn send(target: &Target, command: &str, line_end: &str, timeout: Duration) -> CommandResult {
+    let start = Instant::now();
+    let deadline = tokio::time::Instant::now() + timeout;
+    let done = |result: &str, received: &[u8]| CommandResult {
+        result: result.to_string(),
+        received: String::from_utf8_lossy(received).trim().to_string(),
+        elapsed_ms: start.elapsed().as_millis() as u32,
+    };
+    let mut stream = match tokio::time::timeout_at(deadline, TcpStream::connect((target.host.as_str(), target.port))).await {
+        Ok(Ok(s)) => s,
+        Ok(Err(e)) => return done("error", format!("connect fail, {e}").as_bytes()),
+        Err(_) => return done("timeout", b"connect timeout"),
+    };
+    if let Err(e) = stream.write_all(format!("{command}{line_end}").as_bytes()).await {
+        return done("error", format!("send fail, {e}").as_bytes());
+    }
+    let mut received = Vec::new();
+    let mut buf = [0u8; 512];
+    loop {
+        let n = match tokio::time::timeout_at(deadline, stream.read(&mut buf)).await {
+            Ok(Ok(0)) => {
+                received.extend_from_slice(b"\n(closed by the terminal server)");
+                return done("error", &received);
+            }
+            Ok(Ok(n)) => n,
+            Ok(Err(e)) => {
+                received.extend_from_slice(format!("\n(read fail, {e})").as_bytes());
+                return done("error", &received);
+            }
+            Err(_) => return done("timeout", &received),
+        };
+        received.extend_from_slice(&buf[..n]);
+        if String::from_utf8_lossy(&received).contains(target.reply.as_str()) {
+            return done("ok", &received);
+        }
+        if received.len() >= REPLY_MAX_LEN {
+            return done("error", &received);
+        }
+    }
+}
+
+pub fn log(line: &str, name: &str, target: &Target, command: &str, result: &CommandResult, user_name: &str)
+           -> Result<(), Box<dyn Error>> {
+    let c = server_db::open()?;
+    c.execute("insert into fixture_command_log (line, name, host, port, command, result, received, user_name, time_int) \
+               values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+              params![line, name, target.host, target.port, command, result.result, result.received, user_name,
+                      mytime::now_ts()])?;
+    Ok(())
+}
+
+/// the latest commands of the line, name "" is all
+pub fn logs(line: &str, name: &str, count: u16) -> Result<Vec<RcommandLog>, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let mut stmt = c.prepare("select time_int, name, host, port, command, result, received, user_name \
+                              from fixture_command_log where line=?1 and (?2='' or name=?2) order by id desc limit ?3")?;
+    let mut rows = stmt.query(params![line, name, count])?;
+    let mut v = Vec::new();
+    while let Some(row) = rows.next()? {
+        let host: String = row.get(2)?;
+        let port: u16 = row.get(3)?;
+        v.push((mytime::ts2vn_str(row.get(0)?), row.get(1)?, format!("{host}:{port}"), row.get(4)?, row.get(5)?,
+                row.get(6)?, row.get(7)?));
+    }
+    Ok(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_send() {
+        // a fixture stand-in, reply "DONE1#" to "MODE1#" after some noise
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            loop {
+                let (mut s, _) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 64];
+                let n = s.read(&mut buf).await.unwrap();
+                if &buf[..n] == b"MODE1#\r\n" {
+                    s.write_all(b"busy\r\n").await.unwrap();
+                    s.write_all(b"DONE1#\r\n").await.unwrap();
+                } else {
+                    // no reply, keep the connection until the client timeout
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        });
+        let mut target = Target { host: "127.0.0.1".to_string(), port, reply: "DONE1#".to_string() };
+        let timeout = Duration::from_millis(500);
+        let r = send(&target, "MODE1#", "\r\n", timeout).await;
+        assert_eq!(r.result, "ok");
+        assert_eq!(r.received, "busy\r\nDONE1#");
+        target.reply = "DONE2#".to_string();
+        assert_eq!(send(&target, "MODE2#", "\r\n", timeout).await.result, "timeout");
+
+        let lock = lock_port("127.0.0.1", port);
+        assert!(lock.is_some());
+        assert!(lock_port("127.0.0.1", port).is_none());
+        drop(lock);
+        assert!(lock_port("127.0.0.1", port).is_some());
+    }
+}
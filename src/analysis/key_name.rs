@@ -0,0 +1,92 @@
+use rusqlite::params;
+use std::collections::HashMap;
+use std::error::Error;
+use crate::analysis::server_db;
+
+// the keypad key names of a line, edited by admin on the keyname page.
+// the key codes are also the mapping to find the failed key in the KEYPAD fail msg for the heatmap.
+
+pub static KEY_MAX_LEN: usize = 40;
+
+/// (key code in the KEYPAD fail msg, name printed on the key, x, y), x y is the percent position on the
+/// key-name image, None is not marked on the image
+pub type Rkey = (String, String, Option<f64>, Option<f64>);
+
+/// sorted by key code
+pub fn keys_of(line: &str) -> Result<Vec<Rkey>, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let mut stmt = c.prepare("select key_code, name, x, y from key_name where line=?1 order by key_code")?;
+    let keys = stmt.query_map([line], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(keys)
+}
+
+/// add the key, or change the name and position of the same key code
+pub fn set_key(line: &str, key_code: &str, name: &str, x: Option<f64>, y: Option<f64>) -> Result<(), Box<dyn Error>> {
+    let c = server_db::open()?;
+    c.execute("insert into key_name (line, key_code, name, x, y) values (?1, ?2, ?3, ?4, ?5) \
+               on conflict (line, key_code) do update set name=excluded.name, x=excluded.x, y=excluded.y",
+              params![line, key_code, name, x, y])?;
+    Ok(())
+}
+
+pub fn del_key(line: &str, key_code: &str) -> Result<bool, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let n = c.execute("delete from key_name where line=?1 and key_code=?2", params![line, key_code])?;
+    Ok(n > 0)
+}
+
+/// (key code, name, x, y, fail quantity, opacity of the heat mark 0.0~1.0)
+pub type Rheat = (String, String, Option<f64>, Option<f64>, u32, String);
+
+/// key code -> quantity of the fail records whose msg or msg_detail has the key code as a whole word,
+/// "KEY_1 press fail" is a fail of KEY_1 not KEY_10. a record naming two keys is counted for both.
+pub fn count_fails<'a>(keys: &[Rkey], fails: impl Iterator<Item=(&'a str, &'a str)>) -> HashMap<String, u32> {
+    let mut counts: HashMap<String, u32> = keys.iter().map(|x| (x.0.to_owned(), 0)).collect();
+    for (msg, msg_detail) in fails {
+        let mut words: Vec<&str> = msg.split(|c: char| !c.is_alphanumeric() && c != '_')
+            .chain(msg_detail.split(|c: char| !c.is_alphanumeric() && c != '_'))
+            .filter(|x| !x.is_empty())
+            .collect();
+        words.sort_unstable();
+        words.dedup();
+        for word in words {
+            if let Some(n) = counts.get_mut(word) {
+                *n += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// the keys with the fail quantity, the most failed one first. opacity is relative to the most one
+pub fn heat_of(keys: Vec<Rkey>, counts: &HashMap<String, u32>) -> Vec<Rheat> {
+    let max = counts.values().copied().max().unwrap_or(0);
+    let mut heat: Vec<Rheat> = keys.into_iter().map(|(code, name, x, y)| {
+        let n = counts.get(&code).copied().unwrap_or(0);
+        let opacity = if n == 0 { 0.0 } else { 0.2 + 0.8 * n as f64 / max as f64 };
+        (code, name, x, y, n, format!("{opacity:.2}"))
+    }).collect();
+    heat.sort_by_key(|x| std::cmp::Reverse(x.4));
+    heat
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_fails() {
+        let keys: Vec<Rkey> = ["KEY_1", "KEY_10", "KEY_STAR"].iter()
+            .map(|x| (x.to_string(), "".to_string(), None, None)).collect();
+        let fails = [("KEY_1 press fail", "detail"), ("KEY_STAR stuck", "KEY_STAR,KEY_10"),
+                     ("KEY_10 press fail", ""), ("diag timeout 30s", "KEY_100")];
+        let counts = count_fails(&keys, fails.iter().map(|x| (x.0, x.1)));
+        assert_eq!(counts["KEY_1"], 1);
+        assert_eq!(counts["KEY_10"], 2);
+        assert_eq!(counts["KEY_STAR"], 1);
+        let heat = heat_of(keys, &counts);
+        assert_eq!((heat[0].0.as_str(), heat[0].4, heat[0].5.as_str()), ("KEY_10", 2, "1.00"));
+        assert_eq!(heat[1].5, "0.60");
+    }
+}
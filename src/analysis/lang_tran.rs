@@ -1,98 +1,227 @@
-// use std::cell::OnceCell;
-use maplit::hashmap;
-use once_cell::sync::OnceCell;
+use once_cell::sync::Lazy;
 use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use crate::analysis::config;
+use tracing::warn;
 
 //tem_* means the identifier used in template, avoid confused with variable in function.
 //tem_* should decide by language ONLY, should NOT be dynamic.
-pub static LANG_MAP: OnceCell<H> = OnceCell::new();
-
-type H = HashMap<&'static str, HashMap<&'static str, &'static str>>;
-
-pub fn init_lang_map() {
-    let e = hashmap! {
-        "tem_language" => "en-US",
-        "tem_day" => "DAY",
-        "tem_night" => "NIGHT",
-        "tem_previous_day" => "previous day",
-        "tem_previous_shift" => "previous shift",
-        "tem_today" => "today",
-        "tem_viewing_data" => "Viewing data",
-        "tem_colon" => ": ",
-        "tem_home" => "Home",
-        "tem_quantity_of_pass_fail" => "quantity of Pass | Fail",
-        "tem_yield_of_shift" => "yield of shift",
-        "tem_fail_record_details" => "Fail record details",
-        "tem_query_400_records_of_cell" => "query 400 records of CELL",
-        "tem_query_sn_history" => "query SN history (auto)",
-        "tem_query_sn_history_all" => "query SN history (all )",
-        "tem_key_name_of_bgibest" => "key name of Bgibest",
-        "tem_port_config_of_terminal_server" => "port config of Router",
-        "tem_line_0" => "line 0",
-        "tem_line_1" => "line 1",
-        "tem_line_2" => "line 2",
-        "tem_line_3" => "line 3",
-        "tem_submit" => "Submit",
-        "tem_local_time" => "Local time",
-        "tem_sort_able" => "you can click the table header to sort",
-    };
-    let c = hashmap! {
-        "tem_language" => "zh-CN",
-        "tem_day" => "白班",
-        "tem_night" => "晚班",
-        "tem_previous_day" => "前一天",
-        "tem_previous_shift" => "前一班",
-        "tem_today" => "今天",
-        "tem_viewing_data" => "当前页面数据",
-        "tem_colon" => "：",
-        "tem_home" => "首页",
-        "tem_quantity_of_pass_fail" => "每班测试 Pass | Fail 数量",
-        "tem_yield_of_shift" => "每班良率",
-        "tem_fail_record_details" => "每班 Fail 记录详细信息",
-        "tem_query_400_records_of_cell" => "查询 CELL 最近400次记录",
-        "tem_query_sn_history" => "查询 SN 的记录（仅自动化线）",
-        "tem_query_sn_history_all" => "查询 SN 的记录（所有）",
-        "tem_key_name_of_bgibest" => "Bgibest 各按键的名字",
-        "tem_port_config_of_terminal_server" => "路由 Port 的使用情况",
-        "tem_line_0" => "0线",
-        "tem_line_1" => "1线",
-        "tem_line_2" => "2线",
-        "tem_line_3" => "3线",
-        "tem_submit" => "查询",
-        "tem_local_time" => "本地时间",
-        "tem_sort_able" => "点击表头可以排序",
-    };
-    let v = hashmap! {
-        "tem_language" => "vi-VN",
-        "tem_day" => "DAY",
-        "tem_night" => "NIGHT",
-        "tem_previous_day" => "previous day",
-        "tem_previous_shift" => "previous shift",
-        "tem_today" => "today",
-        "tem_viewing_data" => "Viewing data",
-        "tem_colon" => ": ",
-        "tem_home" => "Home",
-        "tem_quantity_of_pass_fail" => "quantity of Pass | Fail",
-        "tem_yield_of_shift" => "yield of shift",
-        "tem_fail_record_details" => "Fail record details",
-        "tem_query_400_records_of_cell" => "query 400 records of CELL",
-        "tem_query_sn_history" => "query SN history (auto)",
-        "tem_query_sn_history_all" => "query SN history (all )",
-        "tem_key_name_of_bgibest" => "key name of Bgibest",
-        "tem_port_config_of_terminal_server" => "port config of Router",
-        "tem_line_0" => "line 0",
-        "tem_line_1" => "line 1",
-        "tem_line_2" => "line 2",
-        "tem_line_3" => "line 3",
-        "tem_submit" => "Submit",
-        "tem_local_time" => "Local time",
-        "tem_sort_able" => "you can click the table header to sort",
+//the catalogs are locales/<lang code>.toml, en-US is the fallback of other languages.
+pub static LANG_MAP: Lazy<RwLock<Arc<H>>> = Lazy::new(|| RwLock::new(Arc::new(HashMap::new())));
+
+type H = HashMap<String, HashMap<String, String>>;
+
+// increased by every reload of the catalogs, a part of the ETag of the pages
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+static FALLBACK_LANG: &str = "en-US";
+
+// every key used by templates and view.rs, en-US.toml must define all of them.
+static TEM_KEYS: [&str; 43] = [
+    "tem_language_name", "tem_day", "tem_night", "tem_previous_day", "tem_previous_shift", "tem_today",
+    "tem_viewing_data", "tem_colon", "tem_home", "tem_quantity_of_pass_fail", "tem_yield_of_shift",
+    "tem_fail_record_details", "tem_query_400_records_of_cell", "tem_query_sn_history",
+    "tem_query_sn_history_all", "tem_key_name_of_bgibest", "tem_port_config_of_terminal_server",
+    "tem_line_0", "tem_line_1", "tem_line_2", "tem_line_3", "tem_submit", "tem_local_time", "tem_sort_able",
+    "tem_maintenance_log", "tem_username", "tem_password", "tem_login", "tem_login_fail",
+    "tem_title_pf_data", "tem_title_day_yield", "tem_title_fail_detail", "tem_title_query_cell",
+    "tem_title_query_sn", "tem_title_port_config", "tem_title_key_name", "tem_title_maintenance",
+    "tem_title_login", "tem_title_console", "tem_title_compare", "tem_title_cross_line",
+    "tem_title_kiosk", "tem_title_data_quality",
+];
+
+fn load_catalog(path: &Path) -> Result<HashMap<String, String>, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("read {path:?} fail, {e}"))?;
+    toml::from_str::<HashMap<String, String>>(&contents).map_err(|e| format!("deserialize {path:?} fail, {e}"))
+}
+
+/// load all the catalogs under locales_dir, fill the missing key of other languages by en-US
+pub fn load_lang_map(locales_dir: &Path) -> Result<H, String> {
+    let entries = fs::read_dir(locales_dir).map_err(|e| format!("read dir {locales_dir:?} fail, {e}"))?;
+    let mut map = H::new();
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().and_then(|x| x.to_str()) != Some("toml") {
+            continue;
+        }
+        let lang = match path.file_stem().and_then(|x| x.to_str()) {
+            Some(x) => x.to_string(),
+            None => continue,
+        };
+        let mut catalog = load_catalog(&path)?;
+        catalog.insert("tem_language".to_string(), lang.clone());
+        map.insert(lang, catalog);
+    }
+    let fallback = match map.get(FALLBACK_LANG) {
+        Some(x) => x.clone(),
+        None => { return Err(format!("{FALLBACK_LANG}.toml not found in {locales_dir:?}")); }
     };
-    debug_assert_eq!(e.len(), c.len());
-    debug_assert_eq!(e.len(), v.len());
-    let mut map: HashMap<&'static str, HashMap<&'static str, &'static str>> = HashMap::new();
-    map.insert("en-US", e);
-    map.insert("zh-CN", c);
-    map.insert("vi-VN", v);
-    LANG_MAP.get_or_init(|| map);
+    let missing: Vec<&str> = TEM_KEYS.iter().filter(|k| !fallback.contains_key(**k)).copied().collect();
+    if !missing.is_empty() {
+        return Err(format!("{FALLBACK_LANG}.toml missing keys: {missing:?}"));
+    }
+    for (lang, catalog) in map.iter_mut() {
+        let mut missing = Vec::new();
+        for (k, v) in fallback.iter() {
+            if !catalog.contains_key(k) {
+                catalog.insert(k.to_owned(), v.to_owned());
+                missing.push(k.as_str());
+            }
+        }
+        if !missing.is_empty() {
+            warn!("{lang}.toml missing keys, use {FALLBACK_LANG} instead: {missing:?}");
+        }
+    }
+    Ok(map)
+}
+
+/// the snapshot of all catalogs, a reload will not change the snapshot already taken
+pub fn get_lang_map() -> Arc<H> {
+    LANG_MAP.read().unwrap().clone()
+}
+
+/// how many times the catalogs are reloaded
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::Relaxed)
+}
+
+pub fn has_lang(lang: &str) -> bool {
+    LANG_MAP.read().unwrap().contains_key(lang)
+}
+
+/// [("en-US", "English (en-US)"), ...] sorted by language code
+pub fn languages() -> Vec<(String, String)> {
+    let lang_map = get_lang_map();
+    let mut v: Vec<(String, String)> = lang_map.iter()
+        .map(|(lang, catalog)| (lang.to_owned(), catalog.get("tem_language_name").unwrap().to_owned()))
+        .collect();
+    v.sort();
+    v
+}
+
+/// find the catalog of the code, "vi-vn" -> "vi-VN", "vi" -> "vi-VN"
+fn match_lang_from(langs: &[String], code: &str) -> Option<String> {
+    let code = code.trim();
+    if code.is_empty() {
+        return None;
+    }
+    if let Some(x) = langs.iter().find(|x| x.eq_ignore_ascii_case(code)) {
+        return Some(x.to_owned());
+    }
+    let primary = code.split('-').next().unwrap();
+    langs.iter()
+        .find(|x| x.split('-').next().unwrap().eq_ignore_ascii_case(primary))
+        .map(|x| x.to_owned())
+}
+
+/// the remembered cookie first, then the Accept-Language header by quality, at last en-US
+fn negotiate_from(langs: &[String], cookie_lang: Option<&str>, accept_language: Option<&str>) -> String {
+    if let Some(lang) = cookie_lang.and_then(|x| langs.iter().find(|l| l.as_str() == x)) {
+        return lang.to_owned();
+    }
+    // "vi-VN,vi;q=0.9,en-US;q=0.8,en;q=0.7"
+    let mut accept: Vec<(&str, f32)> = accept_language.unwrap_or("").split(',')
+        .filter_map(|x| {
+            let mut sp = x.split(';');
+            let code = sp.next()?.trim();
+            let q = sp.find_map(|p| p.trim().strip_prefix("q=")).map(|q| q.parse::<f32>().unwrap_or(0.0)).unwrap_or(1.0);
+            // q is 0 to 1, "nan", "inf" and q>1 are ignored like q=0
+            let valid = q.is_finite() && (0.0..=1.0).contains(&q) && q > 0.0;
+            if code.is_empty() || code == "*" || !valid { None } else { Some((code, q)) }
+        })
+        .collect();
+    // stable sort, the earlier one win when the quality is same
+    accept.sort_by(|a, b| b.1.total_cmp(&a.1));
+    for (code, _) in accept {
+        if let Some(lang) = match_lang_from(langs, code) {
+            return lang;
+        }
+    }
+    FALLBACK_LANG.to_string()
+}
+
+fn lang_codes() -> Vec<String> {
+    let mut langs: Vec<String> = LANG_MAP.read().unwrap().keys().cloned().collect();
+    langs.sort();
+    langs
+}
+
+pub fn match_lang(code: &str) -> Option<String> {
+    match_lang_from(&lang_codes(), code)
+}
+
+pub fn negotiate(cookie_lang: Option<&str>, accept_language: Option<&str>) -> String {
+    negotiate_from(&lang_codes(), cookie_lang, accept_language)
+}
+
+/// reload the catalogs from disk, the running catalogs are kept if any file is broken
+pub fn reload_lang_map() -> Result<Vec<String>, String> {
+    let locales_dir = &config::get_config().current_locales_path;
+    let map = load_lang_map(locales_dir)?;
+    let mut langs: Vec<String> = map.keys().cloned().collect();
+    langs.sort();
+    *LANG_MAP.write().unwrap() = Arc::new(map);
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+    Ok(langs)
+}
+
+pub fn init_lang_map() -> Result<(), String> {
+    let locales_dir = &config::get_config().current_locales_path;
+    print!("expected path of locales dir: {locales_dir:?}");
+    match reload_lang_map() {
+        Ok(langs) => {
+            println!("      --exist, {langs:?}");
+            Ok(())
+        }
+        Err(e) => {
+            println!("  --Error: {e}");
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_lang_map() {
+        let dir = std::env::temp_dir().join(format!("lang_tran_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let en: String = TEM_KEYS.iter().map(|k| format!("{k} = \"{k} en\"\n")).collect();
+        fs::write(dir.join("en-US.toml"), en).unwrap();
+        fs::write(dir.join("vi-VN.toml"), "tem_day = \"CA NGÀY\"\n").unwrap();
+        let map = load_lang_map(&dir).unwrap();
+        let vi = map.get("vi-VN").unwrap();
+        assert_eq!(vi.get("tem_day").unwrap(), "CA NGÀY");
+        assert_eq!(vi.get("tem_night").unwrap(), "tem_night en");
+        assert_eq!(vi.get("tem_language").unwrap(), "vi-VN");
+
+        fs::write(dir.join("en-US.toml"), "tem_day = \"DAY\"\n").unwrap();
+        assert!(load_lang_map(&dir).is_err());
+        fs::write(dir.join("en-US.toml"), "tem_day = ").unwrap();
+        assert!(load_lang_map(&dir).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let langs = vec!["en-US".to_string(), "vi-VN".to_string(), "zh-CN".to_string()];
+        assert_eq!(negotiate_from(&langs, None, None), "en-US");
+        assert_eq!(negotiate_from(&langs, Some("zh-CN"), Some("vi-VN")), "zh-CN");
+        assert_eq!(negotiate_from(&langs, Some("fr-FR"), Some("vi-VN,vi;q=0.9")), "vi-VN");
+        assert_eq!(negotiate_from(&langs, None, Some("vi")), "vi-VN");
+        assert_eq!(negotiate_from(&langs, None, Some("fr-FR,zh;q=0.5,en;q=0.8")), "en-US");
+        assert_eq!(negotiate_from(&langs, None, Some("fr-FR,zh-TW;q=0.5")), "zh-CN");
+        assert_eq!(negotiate_from(&langs, None, Some("fr-FR,de;q=0.5,*;q=0.1")), "en-US");
+        assert_eq!(negotiate_from(&langs, None, Some("en;q=nan, vi")), "vi-VN");
+        assert_eq!(negotiate_from(&langs, None, Some("en;q=inf,zh;q=-inf, vi;q=0.5")), "vi-VN");
+        assert_eq!(negotiate_from(&langs, None, Some("zh;q=2,vi;q=0.1")), "vi-VN");
+        assert_eq!(negotiate_from(&langs, None, Some("zh;q=NaN,en;q=infinity")), "en-US");
+        assert_eq!(match_lang_from(&langs, "VI-vn"), Some("vi-VN".to_string()));
+        assert_eq!(match_lang_from(&langs, "favicon.ico"), None);
+    }
 }
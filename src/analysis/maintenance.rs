@@ -0,0 +1,70 @@
+use rusqlite::{params, Rows};
+use std::error::Error;
+use crate::analysis::{mytime, server_db};
+
+/// the repair done on a fixture, the first one is the default of the form
+pub static ACTIONS: [&str; 4] = ["pogo pin change", "cable reseat", "calibration", "other"];
+pub static NOTES_MAX_LEN: usize = 1000;
+
+/// (time, station, cell, action, notes, user)
+pub type Rmaintenance = (String, String, String, String, String, String);
+
+fn row_to_maintenance(mut rows: Rows) -> Result<Vec<Rmaintenance>, Box<dyn Error>> {
+    let mut v = Vec::new();
+    while let Some(row) = rows.next()? {
+        v.push((mytime::ts2vn_str(row.get(0)?), row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?));
+    }
+    Ok(v)
+}
+
+pub fn add(line: &str, station: &str, cell: &str, time_int: i64, action: &str, notes: &str, user_name: &str)
+           -> Result<(), Box<dyn Error>> {
+    let c = server_db::open()?;
+    c.execute("insert into maintenance (line, station, cell, time_int, action, notes, user_name, create_int) \
+               values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+              params![line, station, cell, time_int, action, notes, user_name, mytime::now_ts()])?;
+    Ok(())
+}
+
+/// the latest records of the cell, shown on the query_cell page
+pub fn of_cell(line: &str, cell: &str, count: u16) -> Result<Vec<Rmaintenance>, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let mut stmt = c.prepare("select time_int, station, cell, action, notes, user_name from maintenance \
+                              where line=?1 and cell=?2 order by time_int desc limit ?3")?;
+    let rows = stmt.query(params![line, cell, count])?;
+    row_to_maintenance(rows)
+}
+
+/// station "" is all stations, time range is [start, end)
+pub fn search(line: &str, station: &str, start: i64, end: i64, count: u16) -> Result<Vec<Rmaintenance>, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let mut stmt = c.prepare("select time_int, station, cell, action, notes, user_name from maintenance \
+                              where line=?1 and (?2='' or station=?2) and time_int>=?3 and time_int<?4 \
+                              order by time_int desc limit ?5")?;
+    let rows = stmt.query(params![line, station, start, end, count])?;
+    row_to_maintenance(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_search() {
+        server_db::init_test_db();
+        // 2024-01-05 07:30:00
+        let ts = 1704414600;
+        add("fst2", "KEYPAD", "CELL_69", ts, "cable reseat", "loose", "tom").unwrap();
+        add("fst2", "KEYPAD", "CELL_69", ts + 2000, "calibration", "", "ann").unwrap();
+        add("fst2", "DIAG", "CELL_75", ts + 1000, "other", "", "tom").unwrap();
+        let r = of_cell("fst2", "CELL_69", 50).unwrap();
+        assert_eq!(r.iter().map(|x| x.3.as_str()).collect::<Vec<_>>(), vec!["calibration", "cable reseat"]);
+        assert_eq!((r[1].0.as_str(), r[1].4.as_str(), r[1].5.as_str()), ("2024-01-05 07:30:00", "loose", "tom"));
+        assert_eq!(of_cell("fst2", "CELL_69", 1).unwrap().len(), 1);
+        assert_eq!(search("fst2", "", ts, ts + 3000, 50).unwrap().len(), 3);
+        assert_eq!(search("fst2", "DIAG", ts, ts + 3000, 50).unwrap()[0].2, "CELL_75");
+        // [start, end)
+        assert_eq!(search("fst2", "", ts, ts + 2000, 50).unwrap().len(), 2);
+        assert!(search("bst2", "", ts, ts + 3000, 50).unwrap().is_empty());
+    }
+}
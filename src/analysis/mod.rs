@@ -1,4 +1,21 @@
+pub mod compare;
 pub mod config;
+pub mod cross_line;
+pub mod data_quality;
 pub mod db;
+pub mod fail_class;
+pub mod fail_tag;
+pub mod fixture;
+pub mod key_name;
 pub mod lang_tran;
+pub mod maintenance;
 pub mod mytime;
+pub mod overview;
+pub mod port_check;
+pub mod port_config;
+pub mod query_cache;
+pub mod server_db;
+pub mod shift_report;
+pub mod tst_record;
+pub mod user;
+pub mod yield_rate;
@@ -47,6 +47,7 @@ impl fmt::Display for Shift {
     }
 }
 
+#[derive(Clone, Copy)]
 pub struct DateShift(pub i32, pub u32, pub u32, pub Shift);
 
 impl DateShift {
@@ -83,6 +84,50 @@ pub fn now_vn() -> String {
     chrono::Utc::now().with_timezone(&Ho_Chi_Minh).format("%Y-%m-%d %H:%M:%S").to_string()
 }
 
+/// seconds like python time.time(), the same as time_int of tst_record
+pub fn now_ts() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+/// "2024-01-04 20:48:22", "2024-01-04T20:48" (html datetime-local) or "2024-01-04" of local time to seconds
+pub fn vn_str2ts(s: &str) -> Option<i64> {
+    let s = s.trim();
+    for fmt in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"] {
+        if let Ok(t) = chrono::NaiveDateTime::parse_from_str(s, fmt) {
+            return Ho_Chi_Minh.from_local_datetime(&t).earliest().map(|x| x.timestamp());
+        }
+    }
+    let date = chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()?;
+    Ho_Chi_Minh.from_local_datetime(&date.and_hms_opt(0, 0, 0)?).earliest().map(|x| x.timestamp())
+}
+
+/// seconds to local time "2024-01-04 20:48:22"
+pub fn ts2vn_str(ts: i64) -> String {
+    ts2vn_format(ts, "%Y-%m-%d %H:%M:%S")
+}
+
+/// seconds to local time of the chrono format, "" if out of range
+pub fn ts2vn_format(ts: i64, fmt: &str) -> String {
+    match chrono::DateTime::from_timestamp(ts, 0) {
+        Some(t) => t.with_timezone(&Ho_Chi_Minh).format(fmt).to_string(),
+        None => "".to_string(),
+    }
+}
+
+/// "2024-01-04" of local time to the seconds of 00:00, None if not a date or the year is not in years
+pub fn vn_date2ts(s: &str, years: &std::ops::RangeInclusive<i32>) -> Option<i64> {
+    let date = chrono::NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").ok()?;
+    if !years.contains(&date.year()) {
+        return None;
+    }
+    Ho_Chi_Minh.from_local_datetime(&date.and_hms_opt(0, 0, 0)?).earliest().map(|x| x.timestamp())
+}
+
+/// seconds to local date "2024-01-04"
+pub fn ts2vn_date(ts: i64) -> String {
+    ts2vn_format(ts, "%Y-%m-%d")
+}
+
 pub fn pre_day_from_int(year: i32, month: u32, day: u32) -> (i32, u32, u32) {
     let day = chrono::Utc.with_ymd_and_hms(year, month, day, 8, 0, 0).unwrap();
     let dur = chrono::Duration::days(1);
@@ -150,8 +195,8 @@ pub fn ts_per_hour_shift(shift: &DateShift) -> [(u32, u32); 12] {
     let start_time = Ho_Chi_Minh.with_ymd_and_hms(shift.0, shift.1, shift.2, h, m, 0).unwrap();
     let start_time_stamp = start_time.timestamp() as u32;
     let mut r = [(0, 0); 12];
-    for i in 0..12 {
-        r[i] = (start_time_stamp + i as u32 * 3600, start_time_stamp + i as u32 * 3600 + 3600)
+    for (i, x) in r.iter_mut().enumerate() {
+        *x = (start_time_stamp + i as u32 * 3600, start_time_stamp + i as u32 * 3600 + 3600)
     }
     r
 }
@@ -188,6 +233,18 @@ mod tests {
         println!("horus ts: {:?}", ts_per_hour_shift(&DateShift(2024, 1, 5, Shift::Night)));
         assert_eq!(x, (1704457800, 1704501000));
 
+        assert_eq!(vn_str2ts("2024-01-05 07:30:00"), Some(1704414600));
+        assert_eq!(vn_str2ts("2024-01-05T07:30"), Some(1704414600));
+        assert_eq!(vn_str2ts("2024-01-05"), Some(1704414600 - 7 * 3600 - 1800));
+        assert_eq!(vn_str2ts("2024-13-05"), None);
+        assert_eq!(ts2vn_str(1704414600), "2024-01-05 07:30:00");
+        assert_eq!(vn_date2ts("2024-01-05", &(2000..=2100)), Some(1704414600 - 7 * 3600 - 1800));
+        assert_eq!(vn_date2ts("+10010-01-01", &(2000..=2100)), None);
+        assert_eq!(vn_date2ts("1999-12-31", &(2000..=2100)), None);
+        assert_eq!(ts2vn_date(1704414600), "2024-01-05");
+        assert_eq!(ts2vn_format(1704414600, "%m-%d %H:%M"), "01-05 07:30");
+        assert_eq!(ts2vn_format(i64::MAX, "%m-%d %H:%M"), "");
+
         let re_date: Regex = Regex::new(r"^(202\d)-(0[1-9]|1[012])-(0[1-9]|[12]\d|3[01])$").unwrap();
         let r = re_date.captures("2024-01-06").unwrap();
         println!("{:?}", r);
@@ -0,0 +1,137 @@
+use crate::analysis::{config, db, mytime, port_check, yield_rate};
+use crate::analysis::yield_rate::{YieldStatus, YieldView};
+
+// one tile of every line on the homepage, the current shift at a glance, click into the line for the details
+
+// no test for so long in a shift is an alert, the line may be stopped
+static IDLE_ALERT_SECS: i64 = 30 * 60;
+
+pub struct StationTile {
+    pub station: &'static str,
+    // (start, pass, fail, unlock)
+    pub counts: (u32, u32, u32, u32),
+    pub yield_view: YieldView,
+    // "target 95.0 %", "" if no threshold of the station
+    pub target: String,
+}
+
+/// (cell, station, fail + unlock, yield)
+pub type RworstCell = (String, &'static str, u32, String);
+
+pub struct LineTile {
+    pub line: &'static str,
+    pub hostname: String,
+    pub stations: Vec<StationTile>,
+    // the most fail + unlock of all stations, None if no fail
+    pub worst_cell: Option<RworstCell>,
+    // "5m ago", "never"
+    pub last_test: String,
+    pub alerts: Vec<String>,
+}
+
+impl LineTile {
+    /// css class of the tile, defined in general.css
+    pub fn class(&self) -> &'static str {
+        if self.alerts.is_empty() { "tile tile_ok" } else { "tile tile_alert" }
+    }
+}
+
+/// the sum of the cells, and the cell of the most fail + unlock, None if no fail
+fn tally(cells: &[db::RcellCount]) -> ((u32, u32, u32, u32), Option<&db::RcellCount>) {
+    let mut total = (0u32, 0u32, 0u32, 0u32);
+    let mut worst: Option<&db::RcellCount> = None;
+    for x in cells.iter() {
+        let c = x.1;
+        total = (total.0.saturating_add(c.0), total.1.saturating_add(c.1),
+                 total.2.saturating_add(c.2), total.3.saturating_add(c.3));
+        let bad = c.2.saturating_add(c.3);
+        if bad > 0 && worst.map(|w| bad > w.1.2.saturating_add(w.1.3)).unwrap_or(true) {
+            worst = Some(x);
+        }
+    }
+    (total, worst)
+}
+
+/// "no test for 45m" when the last test is too long ago
+fn idle_alert(now: i64, last: Option<i64>) -> Option<String> {
+    last.filter(|ts| now - ts > IDLE_ALERT_SECS).map(|ts| format!("no test for {}", port_check::duration_str(now - ts)))
+}
+
+/// the current shift of the line
+pub fn line_tile(line: &'static str) -> LineTile {
+    let config = config::get_config();
+    let (start, end) = mytime::start_end_of_shift(&mytime::current_shift());
+    let now = mytime::now_ts();
+    let mut tile = LineTile {
+        line,
+        hostname: config.get_hostname(line).cloned().unwrap_or_default(),
+        stations: Vec::new(),
+        worst_cell: None,
+        last_test: "never".to_string(),
+        alerts: Vec::new(),
+    };
+    let mut last: Option<i64> = None;
+    for station in db::stations_of(line) {
+        let threshold = config.yield_station.get(station);
+        let cells = match db::cell_counts(line, station, start, end) {
+            Ok(c) => c,
+            Err(e) => {
+                tile.alerts.push(format!("{station}: {e}"));
+                continue;
+            }
+        };
+        match db::last_test(line, station) {
+            Ok(ts) => last = last.max(ts),
+            Err(e) => tile.alerts.push(format!("{station}: {e}")),
+        }
+        let (total, worst) = tally(&cells);
+        if let Some((cell, x)) = worst {
+            let bad = x.2.saturating_add(x.3);
+            if tile.worst_cell.as_ref().map(|w| bad > w.2).unwrap_or(true) {
+                let text = yield_rate::calc(&config.yield_define, threshold, *x).text;
+                tile.worst_cell = Some((cell.to_owned(), station, bad, text));
+            }
+        }
+        let yield_view = yield_rate::calc(&config.yield_define, threshold, total);
+        if yield_view.class == YieldStatus::Bad.class() {
+            let warning = threshold.map(|t| format!(", warning {:.1} %", t.warning)).unwrap_or_default();
+            tile.alerts.push(format!("{station} yield {}{warning}", yield_view.text));
+        }
+        tile.stations.push(StationTile {
+            station,
+            counts: total,
+            yield_view,
+            target: threshold.map(|t| format!("target {:.1} %", t.target)).unwrap_or_default(),
+        });
+    }
+    if let Some(ts) = last {
+        tile.last_test = format!("{} ago", port_check::duration_str(now - ts));
+    }
+    tile.alerts.extend(idle_alert(now, last));
+    let mut down: Vec<String> = port_check::badges_of(line).into_iter()
+        .filter(|x| x.1.0 == "port_down").map(|x| x.0).collect();
+    if !down.is_empty() {
+        down.sort();
+        tile.alerts.push(format!("fixture port down ({}): {}", down.len(), down.join(", ")));
+    }
+    tile
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tally() {
+        let cells = vec![("CELL_69".to_string(), (10, 9, 1, 0)), ("CELL_71".to_string(), (12, 8, 2, 2)),
+                         ("CELL_73".to_string(), (8, 4, 4, 0))];
+        let (total, worst) = tally(&cells);
+        assert_eq!(total, (30, 21, 7, 2));
+        assert_eq!(worst.unwrap().0, "CELL_71");
+        assert!(tally(&[("CELL_69".to_string(), (5, 5, 0, 0))]).1.is_none());
+
+        assert_eq!(idle_alert(10000, Some(10000 - 45 * 60)), Some("no test for 45m".to_string()));
+        assert_eq!(idle_alert(10000, Some(10000 - 60)), None);
+        assert_eq!(idle_alert(10000, None), None);
+    }
+}
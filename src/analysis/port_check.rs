@@ -0,0 +1,243 @@
+use once_cell::sync::Lazy;
+use rusqlite::params;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
+use crate::analysis::{config, fixture, mytime, port_config, server_db};
+use tracing::{error, warn};
+
+// tcp connect to every fixture port of the port config page, a port refused or timeout is a dead serial port.
+// the latest status is kept in memory, the outages are saved in the server db.
+
+static LINES: [&str; 4] = ["bst1", "bst2", "fst1", "fst2"];
+
+#[derive(Debug, Clone)]
+pub struct PortStatus {
+    pub host: String,
+    pub ports: String,
+    // every port of the name is connected
+    pub up: bool,
+    // the slowest port, 0 if down
+    pub latency_ms: u32,
+    // the first port fail, "" if up
+    pub error: String,
+    // time of the last change between up and down
+    pub since: i64,
+    pub checked: i64,
+}
+
+/// (css class, text, title) of the badge
+pub type Rbadge = (&'static str, String, String);
+
+/// (name, ports, badge) of the port config page
+pub type RportBadge = (String, String, Rbadge);
+
+/// (name, host, ports, error, start, end, duration), end is "" if not recovered yet
+pub type Routage = (String, String, String, String, String, String, String);
+
+// (line, name) -> the latest status
+static STATUS: Lazy<RwLock<HashMap<(String, String), PortStatus>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+impl PortStatus {
+    pub fn badge(&self) -> Rbadge {
+        let title = format!("{}:{}, checked at {}", self.host, self.ports, mytime::ts2vn_str(self.checked));
+        if self.up {
+            ("port_up", format!("up {} ms", self.latency_ms), title)
+        } else {
+            let since = mytime::ts2vn_format(self.since, "%m-%d %H:%M");
+            ("port_down", format!("down since {since}"), format!("{title}, {}", self.error))
+        }
+    }
+}
+
+/// the badge of a port not checked yet
+pub fn unknown_badge() -> Rbadge {
+    ("port_unknown", "unknown".to_string(), "not checked yet".to_string())
+}
+
+pub fn badge_of(line: &str, name: &str) -> Rbadge {
+    let status = STATUS.read().unwrap();
+    status.get(&(line.to_string(), name.to_string())).map(|x| x.badge()).unwrap_or_else(unknown_badge)
+}
+
+/// name -> badge of every checked port of the line
+pub fn badges_of(line: &str) -> HashMap<String, Rbadge> {
+    let status = STATUS.read().unwrap();
+    status.iter().filter(|((l, _), _)| l == line).map(|((_, name), x)| (name.to_owned(), x.badge())).collect()
+}
+
+/// "3d 2h", "2h 5m", "5m", "30s"
+pub fn duration_str(secs: i64) -> String {
+    let (d, h, m) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+    if d > 0 {
+        format!("{d}d {h}h")
+    } else if h > 0 {
+        format!("{h}h {m}m")
+    } else if m > 0 {
+        format!("{m}m")
+    } else {
+        format!("{}s", secs.max(0))
+    }
+}
+
+async fn connect(host: &str, port: u16, timeout: Duration) -> Result<u32, String> {
+    let start = Instant::now();
+    match tokio::time::timeout(timeout, TcpStream::connect((host, port))).await {
+        Ok(Ok(_)) => Ok(start.elapsed().as_millis() as u32),
+        Ok(Err(e)) => Err(format!("port {port} {e}")),
+        Err(_) => Err(format!("port {port} timeout {}ms", timeout.as_millis())),
+    }
+}
+
+/// (latency, error) of all the ports of a name
+async fn check_ports(host: String, ports: Vec<u16>, timeout: Duration) -> (u32, String) {
+    let mut latency = 0;
+    for port in ports {
+        // in use by a fixture command or console, it is reachable. held over the connect, a terminal server
+        // port takes one session, a command or console can not start while the checker is connected
+        let _lock = match fixture::lock_port(&host, port) {
+            Some(l) => l,
+            None => continue,
+        };
+        match connect(&host, port, timeout).await {
+            Ok(ms) => latency = latency.max(ms),
+            Err(e) => return (0, e),
+        }
+    }
+    (latency, "".to_string())
+}
+
+fn outage_start(line: &str, name: &str, status: &PortStatus) -> Result<(), Box<dyn Error>> {
+    let c = server_db::open()?;
+    // an outage not recovered before restart is continued
+    c.execute("insert into port_outage (line, name, host, ports, error, start_int) select ?1, ?2, ?3, ?4, ?5, ?6 \
+               where not exists (select 1 from port_outage where line=?1 and name=?2 and end_int is null)",
+              params![line, name, status.host, status.ports, status.error, status.checked])?;
+    Ok(())
+}
+
+fn outage_end(line: &str, name: &str, time_int: i64) -> Result<(), Box<dyn Error>> {
+    let c = server_db::open()?;
+    c.execute("update port_outage set end_int=?3 where line=?1 and name=?2 and end_int is null",
+              params![line, name, time_int])?;
+    Ok(())
+}
+
+/// check every port of the port config once, save the change of up and down
+async fn check_all(timeout: Duration) {
+    let mut set = JoinSet::new();
+    for line in LINES {
+        let server = port_config::server_of(line);
+        let ports = port_config::ports_of(line);
+        let (host, ports) = match (server, ports) {
+            (Ok(Some(s)), Ok(p)) => (s.0, p),
+            (Ok(None), _) => continue,
+            (Err(e), _) | (_, Err(e)) => {
+                error!("read port config of {line} fail, {e}");
+                continue;
+            }
+        };
+        for (name, port_str) in ports {
+            let port_list = match port_config::parse_ports(&port_str) {
+                Some(x) => x,
+                None => continue,
+            };
+            let host = host.clone();
+            set.spawn(async move {
+                let (latency_ms, error) = check_ports(host.clone(), port_list, timeout).await;
+                (line, name, host, port_str, latency_ms, error)
+            });
+        }
+    }
+    let now = mytime::now_ts();
+    let mut new = HashMap::new();
+    while let Some(result) = set.join_next().await {
+        let (line, name, host, ports, latency_ms, error) = match result {
+            Ok(x) => x,
+            Err(e) => {
+                error!("port check task fail, {e}");
+                continue;
+            }
+        };
+        let status = PortStatus { host, ports, up: error.is_empty(), latency_ms, error, since: now, checked: now };
+        new.insert((line.to_string(), name), status);
+    }
+    let old = STATUS.read().unwrap().clone();
+    for ((line, name), status) in new.iter_mut() {
+        let last = old.get(&(line.to_owned(), name.to_owned()));
+        if let Some(last) = last.filter(|x| x.up == status.up) {
+            status.since = last.since;
+            continue;
+        }
+        let result = if status.up {
+            outage_end(line, name, now)
+        } else {
+            warn!("fixture port {line} {name} {}:{} down, {}", status.host, status.ports, status.error);
+            outage_start(line, name, status)
+        };
+        if let Err(e) = result {
+            error!("save port outage of {line} {name} fail, {e}");
+        }
+    }
+    // the port deleted from the port config is not down any more
+    for (line, name) in old.keys().filter(|k| !new.contains_key(*k)) {
+        if let Err(e) = outage_end(line, name, now) {
+            error!("save port outage of {line} {name} fail, {e}");
+        }
+    }
+    *STATUS.write().unwrap() = new;
+}
+
+/// check the ports every port_check.interval_secs of config.toml, run forever in a spawned task
+pub async fn watch_ports() {
+    loop {
+        let port_check = config::get_config().port_check.clone();
+        if port_check.interval_secs == 0 {
+            // disabled, wait for the config reload
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            continue;
+        }
+        check_all(Duration::from_millis(port_check.timeout_ms)).await;
+        tokio::time::sleep(Duration::from_secs(port_check.interval_secs)).await;
+    }
+}
+
+/// the latest outages of the line, name "" is all the ports
+pub fn outages(line: &str, name: &str, count: u16) -> Result<Vec<Routage>, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let mut stmt = c.prepare("select name, host, ports, error, start_int, end_int from port_outage \
+                              where line=?1 and (?2='' or name=?2) order by start_int desc limit ?3")?;
+    let mut rows = stmt.query(params![line, name, count])?;
+    let now = mytime::now_ts();
+    let mut v = Vec::new();
+    while let Some(row) = rows.next()? {
+        let start: i64 = row.get(4)?;
+        let end: Option<i64> = row.get(5)?;
+        v.push((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, mytime::ts2vn_str(start),
+                end.map(mytime::ts2vn_str).unwrap_or_default(), duration_str(end.unwrap_or(now) - start)));
+    }
+    Ok(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_badge() {
+        assert_eq!(duration_str(30), "30s");
+        assert_eq!(duration_str(300), "5m");
+        assert_eq!(duration_str(7500), "2h 5m");
+        assert_eq!(duration_str(3 * 86400 + 7500), "3d 2h");
+        let mut status = PortStatus { host: "10.1.1.6".to_string(), ports: "2030".to_string(), up: true,
+                                      latency_ms: 3, error: "".to_string(), since: 1704375000, checked: 1704375000 };
+        assert_eq!(status.badge().1, "up 3 ms");
+        status.up = false;
+        status.error = "port 2030 timeout 2000ms".to_string();
+        assert_eq!(status.badge().0, "port_down");
+        assert!(status.badge().2.ends_with("timeout 2000ms"));
+    }
+}
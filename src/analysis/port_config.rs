@@ -0,0 +1,162 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use std::error::Error;
+use crate::analysis::server_db;
+
+// the terminal server of a line, the fixture ports on it and the fixture commands,
+// edited by admin on the portconfig page. filled from the old hardcoded page at the first start.
+
+pub static HOST_MAX_LEN: usize = 64;
+pub static NAME_MAX_LEN: usize = 90;
+pub static TEXT_MAX_LEN: usize = 200;
+// command and reply
+pub static COMMAND_MAX_LEN: usize = 40;
+
+/// (name, ports), the name is the cell of tst_record or a free text, ("CELL_81", "2030"), ("Lcd robot", "2038/2045")
+pub type Rport = (String, String);
+/// (station, command, expected reply, note)
+pub type Rcommand = (String, String, String, String);
+
+static SEED_HOST: (&str, &str) = ("10.1.1.6", "10.1.1.6/10.1.1.1");
+
+static SEED_FST_PORT: [(&str, &str); 23] = [
+    ("CELL_81", "2030"), ("CELL_82", "2031"), ("CELL_83", "2032"),
+    ("CELL_84", "2033"), ("CELL_85", "2034"), ("CELL_86", "2035"),
+    ("CELL_53", "2002"), ("CELL_55", "2004"), ("CELL_57", "2006"), ("CELL_59", "2008"),
+    ("CELL_61", "2010"), ("CELL_63", "2012"), ("CELL_65", "2014"), ("CELL_67", "2016"),
+    ("CELL_69", "2018"), ("CELL_71", "2020"), ("CELL_73", "2022"),
+    ("CELL_75", "2024"), ("CELL_77", "2026"), ("CELL_79", "2028"),
+    ("Lcd robot", "2038/2045"), ("Diag robot", "2036/2043"), ("Keypad robot", "2037/2044"),
+];
+
+static SEED_BST_PORT: [(&str, &str); 9] = [
+    ("BST_01:DUT_01", "2002/2010"), ("BST_01:DUT_02", "2003/2011"), ("BST_01:DUT_03", "2004/2012"),
+    ("BST_01:DUT_04", "2005/2013"), ("BST_01:DUT_05", "2006/2014"), ("BST_01:DUT_06", "2007/2015"),
+    ("BST_01:DUT_07", "2008/2016"), ("BST_01:DUT_08", "2009/2017"), ("Bst robot", "2018"),
+];
+
+static SEED_COMMAND: [(&str, &str, &str, &str); 17] = [
+    ("LCDLED", "open", "", ""), ("LCDLED", "close", "", ""), ("LCDLED", "state", "OK", "or Ready"),
+    ("BST", "open", "", ""), ("BST", "close", "", ""), ("BST", "state", "OK", "or Ready"),
+    ("DIAG", "open", "", ""), ("DIAG", "close", "", ""), ("DIAG", "state", "", ""),
+    ("DIAG", "power on", "", ""), ("DIAG", "power off", "", ""),
+    ("KEYPAD", "OPEN#", "", ""), ("KEYPAD", "CLOSE#", "", ""), ("KEYPAD", "STATE", "", ""),
+    ("KEYPAD", "MODE1#", "DONE1#", "press all key"),
+    ("KEYPAD", "MODE2#", "DONE2#", "press hookswitch 5 times"),
+    ("KEYPAD", "MODE3#", "DONE3#", "press NAV ENTER"),
+];
+
+fn is_empty(c: &Connection, table: &str) -> rusqlite::Result<bool> {
+    let n: i64 = c.query_row(&format!("select count(*) from {table}"), [], |row| row.get(0))?;
+    Ok(n == 0)
+}
+
+/// fill the empty tables by the content of the old hardcoded page, a row deleted by admin is not filled again
+pub fn seed(c: &Connection) -> rusqlite::Result<()> {
+    if is_empty(c, "terminal_server")? {
+        for line in ["bst1", "bst2", "fst1", "fst2"] {
+            c.execute("insert into terminal_server (line, host, note) values (?1, ?2, ?3)",
+                      params![line, SEED_HOST.0, SEED_HOST.1])?;
+        }
+    }
+    if is_empty(c, "fixture_port")? {
+        for line in ["bst1", "bst2", "fst1", "fst2"] {
+            let seed: &[(&str, &str)] = if line.contains("bst") { &SEED_BST_PORT } else { &SEED_FST_PORT };
+            for (name, ports) in seed {
+                c.execute("insert into fixture_port (line, name, ports) values (?1, ?2, ?3)", params![line, name, ports])?;
+            }
+        }
+    }
+    if is_empty(c, "fixture_command")? {
+        for (station, command, reply, note) in SEED_COMMAND {
+            c.execute("insert into fixture_command (station, command, reply, note) values (?1, ?2, ?3, ?4)",
+                      params![station, command, reply, note])?;
+        }
+    }
+    Ok(())
+}
+
+/// "2002/2010" -> [2002, 2010], None if any port is invalid
+pub fn parse_ports(ports: &str) -> Option<Vec<u16>> {
+    let v: Vec<u16> = ports.split('/').map(|x| x.trim().parse::<u16>().ok().filter(|p| *p > 0))
+        .collect::<Option<Vec<u16>>>()?;
+    if v.is_empty() { None } else { Some(v) }
+}
+
+/// (host, note)
+pub fn server_of(line: &str) -> Result<Option<(String, String)>, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let server = c.query_row("select host, note from terminal_server where line=?1", [line],
+                             |row| Ok((row.get(0)?, row.get(1)?))).optional()?;
+    Ok(server)
+}
+
+pub fn set_server(line: &str, host: &str, note: &str) -> Result<(), Box<dyn Error>> {
+    let c = server_db::open()?;
+    c.execute("insert into terminal_server (line, host, note) values (?1, ?2, ?3) \
+               on conflict (line) do update set host=excluded.host, note=excluded.note",
+              params![line, host, note])?;
+    Ok(())
+}
+
+/// in the order added
+pub fn ports_of(line: &str) -> Result<Vec<Rport>, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let mut stmt = c.prepare("select name, ports from fixture_port where line=?1 order by id")?;
+    let ports = stmt.query_map([line], |row| Ok((row.get(0)?, row.get(1)?)))?.collect::<Result<Vec<_>, _>>()?;
+    Ok(ports)
+}
+
+/// add the port, or change the ports of the same name
+pub fn set_port(line: &str, name: &str, ports: &str) -> Result<(), Box<dyn Error>> {
+    let c = server_db::open()?;
+    c.execute("insert into fixture_port (line, name, ports) values (?1, ?2, ?3) \
+               on conflict (line, name) do update set ports=excluded.ports",
+              params![line, name, ports])?;
+    Ok(())
+}
+
+pub fn del_port(line: &str, name: &str) -> Result<bool, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let n = c.execute("delete from fixture_port where line=?1 and name=?2", params![line, name])?;
+    Ok(n > 0)
+}
+
+/// the commands of all stations in the order added, they are same for every line
+pub fn commands() -> Result<Vec<Rcommand>, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let mut stmt = c.prepare("select station, command, reply, note from fixture_command order by id")?;
+    let commands = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(commands)
+}
+
+pub fn set_command(station: &str, command: &str, reply: &str, note: &str) -> Result<(), Box<dyn Error>> {
+    let c = server_db::open()?;
+    c.execute("insert into fixture_command (station, command, reply, note) values (?1, ?2, ?3, ?4) \
+               on conflict (station, command) do update set reply=excluded.reply, note=excluded.note",
+              params![station, command, reply, note])?;
+    Ok(())
+}
+
+pub fn del_command(station: &str, command: &str) -> Result<bool, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let n = c.execute("delete from fixture_command where station=?1 and command=?2", params![station, command])?;
+    Ok(n > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ports() {
+        assert_eq!(parse_ports("2030"), Some(vec![2030]));
+        assert_eq!(parse_ports("2002/2010"), Some(vec![2002, 2010]));
+        assert_eq!(parse_ports(" 2002 / 2010 "), Some(vec![2002, 2010]));
+        assert_eq!(parse_ports(""), None);
+        assert_eq!(parse_ports("2002/"), None);
+        assert_eq!(parse_ports("0"), None);
+        assert_eq!(parse_ports("70000"), None);
+        assert!(SEED_FST_PORT.iter().chain(SEED_BST_PORT.iter()).all(|x| parse_ports(x.1).is_some()));
+    }
+}
@@ -0,0 +1,154 @@
+use once_cell::sync::Lazy;
+use rusqlite::{params, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::any::Any;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use crate::analysis::{config, mytime, server_db};
+use tracing::error;
+
+// the query results of a closed shift never change, they are kept in memory, and in the query_cache table of
+// server.db when cache.persist is set. the key is (kind, line, station, start, end) of the query,
+// a shift still open is always read from the db.
+
+struct Entry {
+    value: Arc<dyn Any + Send + Sync>,
+    // the db file of the result, the result is stale if config.toml point to another file
+    db_path: String,
+    // the tick of the last hit, the least one is dropped first
+    used: u64,
+}
+
+#[derive(Default)]
+struct Cache {
+    entries: HashMap<String, Entry>,
+    tick: u64,
+}
+
+static CACHE: Lazy<Mutex<Cache>> = Lazy::new(|| Mutex::new(Cache::default()));
+
+/// the shift ended long enough, see cache.closed_after_secs of config.toml
+pub fn is_closed(end_ts: i64) -> bool {
+    end_ts + config::get_config().cache.closed_after_secs as i64 <= mytime::now_ts()
+}
+
+fn key_of(kind: &str, line: &str, station: &str, start_ts: i64, end_ts: i64) -> String {
+    format!("{kind}|{line}|{station}|{start_ts}|{end_ts}")
+}
+
+fn db_path_of(line: &str, station: &str) -> String {
+    config::get_config().get_db(line, station).map(|x| x.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+fn get_memory<T: Clone + 'static>(key: &str, db_path: &str) -> Option<T> {
+    let mut cache = CACHE.lock().unwrap();
+    cache.tick += 1;
+    let tick = cache.tick;
+    let entry = cache.entries.get_mut(key).filter(|x| x.db_path == db_path)?;
+    entry.used = tick;
+    entry.value.downcast_ref::<T>().cloned()
+}
+
+fn put_memory<T: Send + Sync + 'static>(key: String, db_path: String, value: T, max_entries: usize) {
+    let mut cache = CACHE.lock().unwrap();
+    cache.tick += 1;
+    let used = cache.tick;
+    cache.entries.insert(key, Entry { value: Arc::new(value), db_path, used });
+    while cache.entries.len() > max_entries {
+        let oldest = cache.entries.iter().min_by_key(|x| x.1.used).map(|x| x.0.to_owned());
+        match oldest {
+            Some(k) => { cache.entries.remove(&k); }
+            None => break,
+        }
+    }
+}
+
+fn get_persisted<T: DeserializeOwned>(key: &str, db_path: &str) -> Result<Option<T>, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let value: Option<String> = c.query_row("select value from query_cache where key=?1", [format!("{key}|{db_path}")],
+                                            |row| row.get(0)).optional()?;
+    Ok(value.and_then(|x| serde_json::from_str(&x).ok()))
+}
+
+/// the old results are deleted with every new one, the table does not grow forever
+fn put_persisted<T: Serialize>(key: &str, db_path: &str, value: &T, persist_days: u32) -> Result<(), Box<dyn Error>> {
+    let c = server_db::open()?;
+    let now = mytime::now_ts();
+    c.execute("insert or replace into query_cache (key, value, time_int) values (?1, ?2, ?3)",
+              params![format!("{key}|{db_path}"), serde_json::to_string(value)?, now])?;
+    c.execute("delete from query_cache where time_int < ?1", [now - persist_days as i64 * 24 * 3600])?;
+    Ok(())
+}
+
+/// the result of query() for [start_ts, end_ts) of the station, from the cache if the shift is closed
+pub fn cached<T, F>(kind: &str, line: &str, station: &str, start_ts: i64, end_ts: i64, query: F)
+                    -> Result<T, Box<dyn Error>>
+    where T: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+          F: FnOnce() -> Result<T, Box<dyn Error>>,
+{
+    if !is_closed(end_ts) {
+        return query();
+    }
+    let key = key_of(kind, line, station, start_ts, end_ts);
+    let db_path = db_path_of(line, station);
+    if let Some(value) = get_memory::<T>(&key, &db_path) {
+        return Ok(value);
+    }
+    let config = config::get_config();
+    let (persist, max_entries, persist_days) = (config.cache.persist, config.cache.max_entries, config.cache.persist_days);
+    if persist {
+        match get_persisted::<T>(&key, &db_path) {
+            Ok(Some(value)) => {
+                put_memory(key, db_path, value.clone(), max_entries);
+                return Ok(value);
+            }
+            Ok(None) => (),
+            Err(e) => error!("read query cache {key} fail, {e}"),
+        }
+    }
+    let value = query()?;
+    if persist {
+        if let Err(e) = put_persisted(&key, &db_path, &value, persist_days) {
+            error!("save query cache {key} fail, {e}");
+        }
+    }
+    put_memory(key, db_path, value.clone(), max_entries);
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory() {
+        put_memory("pf|fst1|KEYPAD|0|1".to_string(), "a.db".to_string(), vec![(1u16, 2u16)], 2);
+        assert_eq!(get_memory::<Vec<(u16, u16)>>("pf|fst1|KEYPAD|0|1", "a.db"), Some(vec![(1, 2)]));
+        // another db file or type is a miss
+        assert_eq!(get_memory::<Vec<(u16, u16)>>("pf|fst1|KEYPAD|0|1", "b.db"), None);
+        assert_eq!(get_memory::<Vec<u32>>("pf|fst1|KEYPAD|0|1", "a.db"), None);
+        // the least used is dropped
+        put_memory("pf|fst1|KEYPAD|1|2".to_string(), "a.db".to_string(), 1u32, 2);
+        let _ = get_memory::<Vec<(u16, u16)>>("pf|fst1|KEYPAD|0|1", "a.db");
+        put_memory("pf|fst1|KEYPAD|2|3".to_string(), "a.db".to_string(), 2u32, 2);
+        assert_eq!(get_memory::<u32>("pf|fst1|KEYPAD|1|2", "a.db"), None);
+        assert!(get_memory::<Vec<(u16, u16)>>("pf|fst1|KEYPAD|0|1", "a.db").is_some());
+        assert_eq!(key_of("yield", "bst1", "BST", 10, 20), "yield|bst1|BST|10|20");
+    }
+
+    #[test]
+    fn test_persisted() {
+        server_db::init_test_db();
+        let c = server_db::open().unwrap();
+        let old = mytime::now_ts() - 91 * 24 * 3600;
+        c.execute("insert into query_cache (key, value, time_int) values ('pf|fst1|KEYPAD|0|1|a.db', '[[1,2]]', ?1)",
+                  [old]).unwrap();
+        assert_eq!(get_persisted::<Vec<(u32, u32)>>("pf|fst1|KEYPAD|0|1", "a.db").unwrap(), Some(vec![(1, 2)]));
+        // the result older than persist_days is deleted by the next put
+        put_persisted("pf|fst1|KEYPAD|1|2", "a.db", &vec![(3u32, 4u32)], 90).unwrap();
+        assert_eq!(get_persisted::<Vec<(u32, u32)>>("pf|fst1|KEYPAD|0|1", "a.db").unwrap(), None);
+        assert_eq!(get_persisted::<Vec<(u32, u32)>>("pf|fst1|KEYPAD|1|2", "a.db").unwrap(), Some(vec![(3, 4)]));
+    }
+}
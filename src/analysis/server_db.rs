@@ -0,0 +1,145 @@
+use rusqlite::Connection;
+use crate::analysis::{config, port_config};
+#[cfg(test)]
+use std::sync::{Arc, Once, RwLock};
+
+// the db owned by this server, not the tst_record db written by the test lines.
+// every feature which need to write something create its table here.
+static CREATE_TABLES: &str = "
+create table if not exists user
+    (id INTEGER PRIMARY KEY AUTOINCREMENT,
+     name VARCHAR(32) UNIQUE NOT NULL,
+     password_hash VARCHAR(128) NOT NULL,
+     role VARCHAR(10) NOT NULL,
+     lines VARCHAR(40) NOT NULL DEFAULT '',
+     time_int INTEGER NOT NULL);
+create table if not exists api_token
+    (id INTEGER PRIMARY KEY AUTOINCREMENT,
+     user_id INTEGER NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+     name VARCHAR(32) NOT NULL,
+     token_hash VARCHAR(64) UNIQUE NOT NULL,
+     time_int INTEGER NOT NULL,
+     last_used_int INTEGER);
+create table if not exists session
+    (token_hash VARCHAR(64) PRIMARY KEY,
+     user_id INTEGER NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+     expire_int INTEGER NOT NULL);
+create table if not exists maintenance
+    (id INTEGER PRIMARY KEY AUTOINCREMENT,
+     line VARCHAR(8) NOT NULL,
+     station VARCHAR(10) NOT NULL,
+     cell VARCHAR(90) NOT NULL,
+     time_int INTEGER NOT NULL,
+     action VARCHAR(20) NOT NULL,
+     notes VARCHAR(1000) NOT NULL DEFAULT '',
+     user_name VARCHAR(32) NOT NULL,
+     create_int INTEGER NOT NULL);
+create index if not exists maintenance_cell on maintenance (line, cell, time_int);
+create table if not exists fail_tag
+    (id INTEGER PRIMARY KEY AUTOINCREMENT,
+     line VARCHAR(8) NOT NULL,
+     station VARCHAR(10) NOT NULL,
+     db_path VARCHAR(260) NOT NULL DEFAULT '',
+     record_id INTEGER NOT NULL,
+     cell VARCHAR(90) NOT NULL,
+     cause VARCHAR(10) NOT NULL,
+     comment VARCHAR(200) NOT NULL DEFAULT '',
+     user_name VARCHAR(32) NOT NULL,
+     time_int INTEGER NOT NULL,
+     UNIQUE (line, station, db_path, record_id));
+create table if not exists terminal_server
+    (line VARCHAR(8) PRIMARY KEY,
+     host VARCHAR(64) NOT NULL,
+     note VARCHAR(200) NOT NULL DEFAULT '');
+create table if not exists fixture_port
+    (id INTEGER PRIMARY KEY AUTOINCREMENT,
+     line VARCHAR(8) NOT NULL,
+     name VARCHAR(90) NOT NULL,
+     ports VARCHAR(40) NOT NULL,
+     UNIQUE (line, name));
+create table if not exists fixture_command
+    (id INTEGER PRIMARY KEY AUTOINCREMENT,
+     station VARCHAR(10) NOT NULL,
+     command VARCHAR(40) NOT NULL,
+     reply VARCHAR(40) NOT NULL DEFAULT '',
+     note VARCHAR(200) NOT NULL DEFAULT '',
+     UNIQUE (station, command));
+create table if not exists key_name
+    (id INTEGER PRIMARY KEY AUTOINCREMENT,
+     line VARCHAR(8) NOT NULL,
+     key_code VARCHAR(40) NOT NULL,
+     name VARCHAR(40) NOT NULL,
+     x REAL,
+     y REAL,
+     UNIQUE (line, key_code));
+create table if not exists port_outage
+    (id INTEGER PRIMARY KEY AUTOINCREMENT,
+     line VARCHAR(8) NOT NULL,
+     name VARCHAR(90) NOT NULL,
+     host VARCHAR(64) NOT NULL,
+     ports VARCHAR(40) NOT NULL,
+     error VARCHAR(200) NOT NULL DEFAULT '',
+     start_int INTEGER NOT NULL,
+     end_int INTEGER);
+create index if not exists port_outage_name on port_outage (line, name, start_int);
+create table if not exists fixture_command_log
+    (id INTEGER PRIMARY KEY AUTOINCREMENT,
+     line VARCHAR(8) NOT NULL,
+     name VARCHAR(90) NOT NULL,
+     host VARCHAR(64) NOT NULL,
+     port INTEGER NOT NULL,
+     command VARCHAR(40) NOT NULL,
+     result VARCHAR(10) NOT NULL,
+     received VARCHAR(4096) NOT NULL DEFAULT '',
+     user_name VARCHAR(32) NOT NULL,
+     time_int INTEGER NOT NULL);
+create table if not exists query_cache
+    (key VARCHAR(120) PRIMARY KEY,
+     value TEXT NOT NULL,
+     time_int INTEGER NOT NULL);
+create index if not exists query_cache_time on query_cache (time_int);
+";
+
+pub fn open() -> rusqlite::Result<Connection> {
+    let c = Connection::open(&config::get_config().auth.server_db)?;
+    c.execute_batch("PRAGMA foreign_keys = ON;")?;
+    Ok(c)
+}
+
+/// create the db file and the tables if not exist, fill the port config of the first start
+pub fn init_server_db() -> Result<(), String> {
+    let db_path = config::get_config().auth.server_db.clone();
+    print!("expected path of server db: {db_path:?}");
+    match open().and_then(|c| c.execute_batch(CREATE_TABLES).and_then(|_| port_config::seed(&c))) {
+        Ok(_) => {
+            println!("       --ready");
+            Ok(())
+        }
+        Err(e) => {
+            println!("  --Error: {e}");
+            Err(format!("init server db {db_path:?} fail, {e}"))
+        }
+    }
+}
+
+/// the config.toml of the repo with a new server db in the temp dir, once for all the tests of the process
+#[cfg(test)]
+pub fn init_test_db() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        let dir = std::env::temp_dir().join(format!("server_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let repo = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let paths = crate::cli::Paths {
+            exe_path: repo.join("server"),
+            config_path: repo.join("config.toml"),
+            db_dir: dir.join("db"),
+            locales_dir: repo.join("locales"),
+        };
+        let mut c = config::load_config(&paths).unwrap();
+        c.auth.server_db = dir.join("server.db");
+        assert!(config::CONFIG.set(RwLock::new(Arc::new(c))).is_ok());
+        init_server_db().unwrap();
+    });
+}
@@ -0,0 +1,386 @@
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::Serialize;
+use std::fmt::Write;
+use std::time::Duration;
+use crate::analysis::config::{self, ShiftReport, Smtp};
+use crate::analysis::{db, mytime, yield_rate};
+use tracing::{error, info, warn};
+
+// the summary of a line at the end of every shift, instead of the screenshots of day_yield and fail_detail.
+// mailed by [shift_report.smtp] and POSTed as json to [shift_report].webhooks of config.toml.
+
+static WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// (cell, fail + unlock, yield)
+pub type RworstCell = (String, u32, String);
+/// (fail msg, quantity)
+pub type RtopMessage = (String, u32);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StationSummary {
+    pub station: String,
+    pub start: u32,
+    pub pass: u32,
+    pub fail: u32,
+    pub unlock: u32,
+    // "97.5 %", "" if nothing tested
+    pub yield_text: String,
+    // "P / (P+F)"
+    pub yield_formula: String,
+    // "yield_good", "yield_warning", "yield_bad" or "yield_unrated"
+    pub yield_status: String,
+    // the most fail + unlock first
+    pub worst_cells: Vec<RworstCell>,
+    pub top_messages: Vec<RtopMessage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub line: String,
+    pub hostname: String,
+    // "2024-01-04 DAY"
+    pub shift: String,
+    pub start: String,
+    pub end: String,
+    pub stations: Vec<StationSummary>,
+    // the station failed to read, the rest of the report is still sent
+    pub errors: Vec<String>,
+}
+
+fn station_summary(line: &str, station: &str, dateshift: &mytime::DateShift, worst_cells: usize,
+                   top_messages: usize) -> Result<StationSummary, String> {
+    let config = config::get_config();
+    let threshold = config.yield_station.get(station);
+    let (start_ts, end_ts) = mytime::start_end_of_shift(dateshift);
+    let cells = db::cell_counts(line, station, start_ts, end_ts).map_err(|e| format!("{station}: {e}"))?;
+    let mut messages = db::fail_msg_counts(line, station, start_ts, end_ts).map_err(|e| format!("{station}: {e}"))?;
+    let mut total = (0u32, 0u32, 0u32, 0u32);
+    for (_, x) in cells.iter() {
+        total = (total.0.saturating_add(x.0), total.1.saturating_add(x.1),
+                 total.2.saturating_add(x.2), total.3.saturating_add(x.3));
+    }
+    let view = yield_rate::calc(&config.yield_define, threshold, total);
+    let mut worst: Vec<RworstCell> = cells.iter().filter(|x| x.1.2.saturating_add(x.1.3) > 0)
+        .map(|(cell, x)| {
+            (cell.to_owned(), x.2.saturating_add(x.3), yield_rate::calc(&config.yield_define, threshold, *x).text)
+        })
+        .collect();
+    worst.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    worst.truncate(worst_cells);
+    messages.truncate(top_messages);
+    Ok(StationSummary {
+        station: station.to_string(),
+        start: total.0,
+        pass: total.1,
+        fail: total.2,
+        unlock: total.3,
+        yield_text: view.text,
+        yield_formula: view.formula,
+        yield_status: view.class.to_string(),
+        worst_cells: worst,
+        top_messages: messages,
+    })
+}
+
+/// the summary of every station of the line in the shift
+pub fn build(line: &str, dateshift: &mytime::DateShift) -> Report {
+    let config = config::get_config();
+    let (start, end) = mytime::start_end_of_shift(dateshift);
+    let stations = db::stations_of(line);
+    let mut report = Report {
+        line: line.to_string(),
+        hostname: config.get_hostname(line).cloned().unwrap_or_default(),
+        shift: format!("{} {}", dateshift.date(), dateshift.shift()),
+        start: mytime::ts2vn_str(start),
+        end: mytime::ts2vn_str(end),
+        stations: Vec::new(),
+        errors: Vec::new(),
+    };
+    for station in stations {
+        match station_summary(line, station, dateshift, config.shift_report.worst_cells,
+                              config.shift_report.top_messages) {
+            Ok(s) => report.stations.push(s),
+            Err(e) => report.errors.push(e),
+        }
+    }
+    report
+}
+
+/// build() on a blocking thread, it reads every db of the line, not to hold a worker of the runtime
+pub async fn build_blocking(line: &str, dateshift: &mytime::DateShift) -> Result<Report, String> {
+    let (line, dateshift) = (line.to_string(), *dateshift);
+    tokio::task::spawn_blocking(move || build(&line, &dateshift)).await.map_err(|e| e.to_string())
+}
+
+impl Report {
+    /// "[fst1] shift report 2024-01-04 DAY, fail 12, unlock 3"
+    pub fn subject(&self) -> String {
+        let fail: u32 = self.stations.iter().map(|x| x.fail).sum();
+        let unlock: u32 = self.stations.iter().map(|x| x.unlock).sum();
+        format!("[{}] shift report {}, fail {fail}, unlock {unlock}", self.line, self.shift)
+    }
+
+    /// the plain text mail body
+    pub fn text(&self) -> String {
+        let mut s = String::new();
+        let _ = writeln!(s, "{} {}, shift {}, {} ~ {}", self.line, self.hostname, self.shift, self.start, self.end);
+        for x in self.stations.iter() {
+            let _ = writeln!(s, "\n{}: yield {} ({}), start {}, pass {}, fail {}, unlock {}",
+                             x.station, if x.yield_text.is_empty() { "-" } else { &x.yield_text },
+                             x.yield_formula, x.start, x.pass, x.fail, x.unlock);
+            if !x.worst_cells.is_empty() {
+                let _ = writeln!(s, "  worst cells:");
+                for (cell, n, text) in x.worst_cells.iter() {
+                    let _ = writeln!(s, "    {cell:<16} fail+unlock {n:<5} yield {text}");
+                }
+            }
+            if !x.top_messages.is_empty() {
+                let _ = writeln!(s, "  top fail messages:");
+                for (msg, n) in x.top_messages.iter() {
+                    let _ = writeln!(s, "    {n:<5} {msg}");
+                }
+            }
+        }
+        for e in self.errors.iter() {
+            let _ = writeln!(s, "\nError: {e}");
+        }
+        s
+    }
+}
+
+async fn send_mail(smtp: &Smtp, report: &Report) -> Result<(), String> {
+    let from: Mailbox = smtp.from.parse().map_err(|e| format!("from {}, {e}", smtp.from))?;
+    let mut builder = Message::builder().from(from).subject(report.subject()).header(ContentType::TEXT_PLAIN);
+    for to in smtp.to.iter() {
+        builder = builder.to(to.parse().map_err(|e| format!("to {to}, {e}"))?);
+    }
+    let message = builder.body(report.text()).map_err(|e| e.to_string())?;
+    let transport = match smtp.tls.as_str() {
+        "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&smtp.host),
+        "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&smtp.host),
+        _ => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&smtp.host)),
+    }.map_err(|e| e.to_string())?;
+    let mut transport = transport.port(smtp.port);
+    if !smtp.username.is_empty() {
+        transport = transport.credentials(Credentials::new(smtp.username.to_owned(), smtp.password.to_owned()));
+    }
+    transport.build().send(message).await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+async fn post_webhook(url: &str, report: &Report) -> Result<(), String> {
+    let client = reqwest::Client::builder().timeout(WEBHOOK_TIMEOUT).build().map_err(|e| e.to_string())?;
+    let response = client.post(url).json(report).send().await.map_err(|e| e.to_string())?;
+    response.error_for_status().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// send to the smtp and every webhook, a failed one is tried again shift_report.retry times
+pub async fn deliver(report: Report) {
+    let config = config::get_config().shift_report.clone();
+    deliver_to(&config, &report).await;
+}
+
+/// the targets failed after all the retries
+async fn deliver_to(config: &ShiftReport, report: &Report) -> Vec<String> {
+    let mut targets: Vec<String> = config.webhooks.clone();
+    if config.smtp.is_some() {
+        targets.insert(0, "smtp".to_string());
+    }
+    let mut failed = Vec::new();
+    for target in targets {
+        for attempt in 0..=config.retry {
+            let result = match (target.as_str(), config.smtp.as_ref()) {
+                ("smtp", Some(smtp)) => send_mail(smtp, report).await,
+                _ => post_webhook(&target, report).await,
+            };
+            match result {
+                Ok(_) => {
+                    info!("shift report {} {} sent to {target}", report.line, report.shift);
+                    break;
+                }
+                Err(e) if attempt < config.retry => {
+                    warn!("send shift report {} {} to {target} fail, retry {}/{}, {e}",
+                             report.line, report.shift, attempt + 1, config.retry);
+                    tokio::time::sleep(Duration::from_secs(config.retry_interval_secs)).await;
+                }
+                Err(e) => {
+                    error!("send shift report {} {} to {target} fail, give up, {e}",
+                              report.line, report.shift);
+                    failed.push(target.to_owned());
+                }
+            }
+        }
+    }
+    failed
+}
+
+/// send the report of every shift_report.lines after the end of every shift, run forever in a spawned task
+pub async fn watch_shifts() {
+    loop {
+        let dateshift = mytime::current_shift();
+        let (_, end) = mytime::start_end_of_shift(&dateshift);
+        let delay = config::get_config().shift_report.delay_secs as i64;
+        let wait = (end + delay - mytime::now_ts()).max(1);
+        tokio::time::sleep(Duration::from_secs(wait as u64)).await;
+        let config = config::get_config();
+        if config.shift_report.smtp.is_none() && config.shift_report.webhooks.is_empty() {
+            continue;
+        }
+        for line in config.shift_report.lines.iter() {
+            match build_blocking(line, &dateshift).await {
+                Ok(report) => { tokio::spawn(deliver(report)); }
+                Err(e) => error!("build shift report of {line} fail, {e}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    fn report() -> Report {
+        Report {
+            line: "fst1".to_string(),
+            hostname: "host".to_string(),
+            shift: "2024-01-04 DAY".to_string(),
+            start: "2024-01-04 07:30:00".to_string(),
+            end: "2024-01-04 19:30:00".to_string(),
+            stations: vec![StationSummary {
+                station: "KEYPAD".to_string(), start: 100, pass: 90, fail: 8, unlock: 2,
+                yield_text: "91.8 %".to_string(), yield_formula: "P / (P+F)".to_string(),
+                yield_status: "yield_bad".to_string(),
+                worst_cells: vec![("CELL_69".to_string(), 6, "80.0 %".to_string())],
+                top_messages: vec![("KEY_STAR stuck".to_string(), 5)],
+            }],
+            errors: vec!["DIAG: db file not found".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_text() {
+        let report = report();
+        assert_eq!(report.subject(), "[fst1] shift report 2024-01-04 DAY, fail 8, unlock 2");
+        let text = report.text();
+        assert!(text.contains("KEYPAD: yield 91.8 % (P / (P+F)), start 100, pass 90, fail 8, unlock 2"));
+        assert!(text.contains("CELL_69"));
+        assert!(text.contains("5     KEY_STAR stuck"));
+        assert!(text.ends_with("Error: DIAG: db file not found\n"));
+    }
+
+    /// a smtp server stand-in, the DATA of every mail received is pushed to mails
+    async fn smtp_sink(mails: Arc<Mutex<Vec<String>>>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            loop {
+                let (s, _) = listener.accept().await.unwrap();
+                let mails = mails.clone();
+                tokio::spawn(async move {
+                    let (r, mut w) = s.into_split();
+                    let mut r = BufReader::new(r);
+                    w.write_all(b"220 sink\r\n").await.unwrap();
+                    let mut line = String::new();
+                    while r.read_line(&mut line).await.unwrap_or(0) > 0 {
+                        let command = line.trim().to_uppercase();
+                        line.clear();
+                        if command == "DATA" {
+                            w.write_all(b"354 go on\r\n").await.unwrap();
+                            let mut data = String::new();
+                            while r.read_line(&mut line).await.unwrap() > 0 && line != ".\r\n" {
+                                data.push_str(&line);
+                                line.clear();
+                            }
+                            line.clear();
+                            mails.lock().unwrap().push(data);
+                            w.write_all(b"250 queued\r\n").await.unwrap();
+                        } else if command == "QUIT" {
+                            w.write_all(b"221 bye\r\n").await.unwrap();
+                            break;
+                        } else {
+                            w.write_all(b"250 ok\r\n").await.unwrap();
+                        }
+                    }
+                });
+            }
+        });
+        port
+    }
+
+    /// a webhook stand-in, answer 500 to the first fails requests, then 200. the bodies are pushed to posts
+    async fn webhook_sink(posts: Arc<Mutex<Vec<String>>>, fails: usize) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut n = 0;
+            loop {
+                let (mut s, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                // the headers, then the body of content-length
+                let body = loop {
+                    let k = s.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..k]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let len = head.lines().find_map(|x| x.to_lowercase().strip_prefix("content-length:")
+                            .map(|v| v.trim().parse::<usize>().unwrap())).unwrap_or(0);
+                        if body.len() >= len || k == 0 {
+                            break body.to_string();
+                        }
+                    }
+                };
+                n += 1;
+                let status = if n <= fails { "500 Internal Server Error" } else { "200 OK" };
+                posts.lock().unwrap().push(body);
+                s.write_all(format!("HTTP/1.1 {status}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n").as_bytes())
+                    .await.unwrap();
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_deliver() {
+        let (mails, posts) = (Arc::new(Mutex::new(Vec::new())), Arc::new(Mutex::new(Vec::new())));
+        let smtp_port = smtp_sink(mails.clone()).await;
+        let webhook_port = webhook_sink(posts.clone(), 2).await;
+        let mut config = ShiftReport {
+            retry: 2,
+            retry_interval_secs: 0,
+            webhooks: vec![format!("http://127.0.0.1:{webhook_port}/hook")],
+            smtp: Some(Smtp {
+                host: "127.0.0.1".to_string(),
+                port: smtp_port,
+                tls: "none".to_string(),
+                username: "".to_string(),
+                password: "".to_string(),
+                from: "Yield Report <yield@example.com>".to_string(),
+                to: vec!["qa@example.com".to_string()],
+            }),
+            ..Default::default()
+        };
+        // the webhook fails twice, ok on the last retry
+        assert!(deliver_to(&config, &report()).await.is_empty());
+        let mail = mails.lock().unwrap()[0].to_owned();
+        assert!(mail.contains("Subject: [fst1] shift report 2024-01-04 DAY, fail 8, unlock 2"));
+        assert!(mail.contains("To: qa@example.com"));
+        assert!(mail.contains("KEY_STAR stuck"));
+        let posts_now = posts.lock().unwrap().clone();
+        assert_eq!(posts_now.len(), 3);
+        assert!(posts_now[2].contains("\"line\":\"fst1\""));
+
+        // give up after the retries
+        config.retry = 1;
+        config.smtp = None;
+        config.webhooks = vec![format!("http://127.0.0.1:{}/hook", webhook_sink(posts.clone(), 5).await)];
+        assert_eq!(deliver_to(&config, &report()).await, config.webhooks);
+        assert_eq!(posts.lock().unwrap().len(), 5);
+    }
+}
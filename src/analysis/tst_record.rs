@@ -0,0 +1,175 @@
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, Row, RowIndex};
+use std::error::Error;
+use std::path::Path;
+use tracing::{error, info, warn};
+use crate::analysis::config;
+
+// the tst_record table written by the sequencer of the test lines. an older sequencer writes NULL to some columns
+// or has no such column at all, a missing or NULL column is read as None, a row is never a reason to panic.
+
+// the columns read by this server
+pub static COLUMNS: [&str; 10] = ["id", "sn", "cell", "pn", "pid", "result", "msg", "msg_detail", "time_int", "beijing_str"];
+// a db without any of them is not usable, the yield needs them all
+static REQUIRED: [&str; 4] = ["id", "cell", "result", "time_int"];
+// 'S' start, 'P' pass, 'F' fail, 'U' unlock, any other is skipped by the yield
+pub static RESULTS: [&str; 4] = ["S", "P", "F", "U"];
+
+/// the known columns the db has
+#[derive(Debug, Clone)]
+pub struct Schema {
+    pub columns: Vec<&'static str>,
+}
+
+impl Schema {
+    pub fn has(&self, column: &str) -> bool {
+        self.columns.contains(&column)
+    }
+
+    /// the known columns not in the db, read as None
+    pub fn missing(&self) -> Vec<&'static str> {
+        COLUMNS.iter().filter(|x| !self.has(x)).copied().collect()
+    }
+
+    /// "id,sn,cell,NULL as pn,...", every known column, NULL for the missing one
+    pub fn select(&self) -> String {
+        COLUMNS.iter().map(|x| if self.has(x) { x.to_string() } else { format!("NULL as {x}") })
+            .collect::<Vec<_>>().join(",")
+    }
+
+    /// the text column for group by, "" for NULL or a missing column
+    pub fn text(&self, column: &str) -> String {
+        if self.has(column) { format!("coalesce({column},'')") } else { "''".to_string() }
+    }
+}
+
+/// the known columns of the tst_record table of the db, error if any of the required one is missing
+pub fn detect(c: &Connection) -> Result<Schema, Box<dyn Error>> {
+    let mut stmt = c.prepare("select name from pragma_table_info('tst_record')")?;
+    let names = stmt.query_map([], |row| row.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?;
+    if names.is_empty() {
+        return Err("no table tst_record".into());
+    }
+    let columns: Vec<&'static str> = COLUMNS.iter().filter(|x| names.iter().any(|n| n.eq_ignore_ascii_case(x)))
+        .copied().collect();
+    let lack: Vec<&str> = REQUIRED.iter().filter(|x| !columns.contains(x)).copied().collect();
+    if !lack.is_empty() {
+        return Err(format!("tst_record has no column {}", lack.join(", ")).into());
+    }
+    Ok(Schema { columns })
+}
+
+/// open the db and detect its schema, the file may be replaced by the one of another sequencer at any time
+pub fn open(db_path: &Path) -> Result<(Connection, Schema), Box<dyn Error>> {
+    let c = Connection::open(db_path)?;
+    let schema = detect(&c)?;
+    Ok((c, schema))
+}
+
+/// at startup, log the db of config.toml lacking some columns
+pub fn check_schemas() {
+    for (_, hostname, db_path) in config::get_config().get_all_db() {
+        if !db_path.exists() {
+            continue;
+        }
+        match open(db_path) {
+            Ok((_, schema)) if schema.missing().is_empty() => info!("schema of {hostname} {db_path:?} ok"),
+            Ok((_, schema)) => warn!("{hostname} {db_path:?} has no column {}, read as empty",
+                                     schema.missing().join(", ")),
+            Err(e) => error!("{hostname} {db_path:?} not usable, {e}"),
+        }
+    }
+}
+
+/// the text of any type, None if NULL
+pub fn text<I: RowIndex>(row: &Row, column: I) -> rusqlite::Result<Option<String>> {
+    Ok(match row.get_ref(column)? {
+        ValueRef::Null => None,
+        ValueRef::Integer(i) => Some(i.to_string()),
+        ValueRef::Real(f) => Some(f.to_string()),
+        ValueRef::Text(t) | ValueRef::Blob(t) => Some(String::from_utf8_lossy(t).to_string()),
+    })
+}
+
+/// the integer of an integer, real or numeric text, None if NULL or not a number
+pub fn int<I: RowIndex>(row: &Row, column: I) -> rusqlite::Result<Option<i64>> {
+    Ok(match row.get_ref(column)? {
+        ValueRef::Integer(i) => Some(i),
+        ValueRef::Real(f) => Some(f as i64),
+        ValueRef::Text(t) => std::str::from_utf8(t).ok().and_then(|x| x.trim().parse::<f64>().ok()).map(|x| x as i64),
+        _ => None,
+    })
+}
+
+/// a row of tst_record selected by Schema::select()
+#[derive(Debug, Clone, Default)]
+pub struct TstRecord {
+    pub id: i64,
+    pub sn: Option<String>,
+    // "UCEBU Automatic BST New|PCBDG|BST-01|BST_01:DUT_02"
+    pub cell: Option<String>,
+    pub pn: Option<String>,
+    pub pid: Option<String>,
+    // 'S' start, 'P' pass, 'F' fail, 'U' unlock
+    pub result: Option<String>,
+    pub msg: Option<String>,
+    pub msg_detail: Option<String>,
+    pub time_int: Option<i64>,
+    pub beijing_str: Option<String>,
+}
+
+impl TstRecord {
+    pub fn from_row(row: &Row) -> rusqlite::Result<TstRecord> {
+        Ok(TstRecord {
+            id: int(row, "id")?.unwrap_or(0),
+            sn: text(row, "sn")?,
+            cell: text(row, "cell")?,
+            pn: text(row, "pn")?,
+            pid: text(row, "pid")?,
+            result: text(row, "result")?,
+            msg: text(row, "msg")?,
+            msg_detail: text(row, "msg_detail")?,
+            time_int: int(row, "time_int")?,
+            beijing_str: text(row, "beijing_str")?,
+        })
+    }
+
+    /// "BST_01:DUT_02" of the cell, "" if NULL
+    pub fn short_cell(&self) -> String {
+        self.cell.as_deref().and_then(|x| x.rsplit('|').next()).unwrap_or("").to_string()
+    }
+
+    pub fn result(&self) -> &str {
+        self.result.as_deref().unwrap_or("")
+    }
+}
+
+/// "" for None
+pub fn or_empty(x: &Option<String>) -> String {
+    x.clone().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_old_schema() {
+        let c = Connection::open_in_memory().unwrap();
+        // an older sequencer, no pid and beijing_str, the time_int is real
+        c.execute_batch("create table tst_record (id INTEGER PRIMARY KEY, sn, cell, pn, result, msg, msg_detail,
+                                                  time_int REAL);
+                         insert into tst_record values (1, NULL, 'Bgibest Auto FST 1|PCBINT|KEYPAD-1|CELL_69',
+                                                        '73-1234-05', 'F', 'KEY_1', NULL, 1792137035.5);").unwrap();
+        let schema = detect(&c).unwrap();
+        assert_eq!(schema.missing(), vec!["pid", "beijing_str"]);
+        assert_eq!(schema.text("pid"), "''");
+        let mut stmt = c.prepare(&format!("select {} from tst_record", schema.select())).unwrap();
+        let r = stmt.query_row([], TstRecord::from_row).unwrap();
+        assert_eq!((r.id, &r.sn, &r.pid, &r.msg_detail), (1, &None, &None, &None));
+        assert_eq!((r.short_cell().as_str(), r.result(), r.time_int), ("CELL_69", "F", Some(1792137035)));
+
+        c.execute_batch("drop table tst_record; create table tst_record (id INTEGER PRIMARY KEY, cell, result);").unwrap();
+        assert!(detect(&c).is_err());
+    }
+}
@@ -0,0 +1,238 @@
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use argon2::password_hash::SaltString;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use rusqlite::{params, OptionalExtension};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use crate::analysis::{config, mytime, server_db};
+
+/// the higher role can do everything of the lower one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Viewer,
+    Engineer,
+    Admin,
+}
+
+impl Role {
+    pub fn from_str(s: &str) -> Option<Role> {
+        match s {
+            "viewer" => Some(Role::Viewer),
+            "engineer" => Some(Role::Engineer),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Engineer => "engineer",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+/// the visitor of a request, put into the request extensions by auth::require_role
+#[derive(Debug, Clone)]
+pub struct User {
+    // 0 is the anonymous visitor
+    pub id: i64,
+    pub name: String,
+    pub role: Role,
+    // the lines allowed, empty is all lines
+    pub lines: Vec<String>,
+}
+
+impl User {
+    pub fn anonymous(role: Role) -> User {
+        User { id: 0, name: "anonymous".to_string(), role, lines: Vec::new() }
+    }
+
+    pub fn can_view_line(&self, line: &str) -> bool {
+        self.lines.is_empty() || self.lines.iter().any(|x| x == line)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// random token for session cookie and api token, only the sha256 of it is saved
+fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex(&bytes)
+}
+
+fn token_hash(token: &str) -> String {
+    hex(&Sha256::digest(token.as_bytes()))
+}
+
+fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|e| format!("hash password fail, {e}"))
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(h) => Argon2::default().verify_password(password.as_bytes(), &h).is_ok(),
+        Err(_) => false,
+    }
+}
+
+fn row_to_user(row: &rusqlite::Row) -> rusqlite::Result<User> {
+    let role: String = row.get(2)?;
+    let lines: String = row.get(3)?;
+    Ok(User {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        // a role edited to unknown value by hand is the lowest one
+        role: Role::from_str(&role).unwrap_or(Role::Viewer),
+        lines: lines.split(',').filter(|x| !x.is_empty()).map(|x| x.to_string()).collect(),
+    })
+}
+
+pub fn add_user(name: &str, password: &str, role: Role, lines: &[String]) -> Result<(), Box<dyn Error>> {
+    let password_hash = hash_password(password)?;
+    let c = server_db::open()?;
+    c.execute("insert into user (name, password_hash, role, lines, time_int) values (?1, ?2, ?3, ?4, ?5)",
+              params![name, password_hash, role.as_str(), lines.join(","), mytime::now_ts()])?;
+    Ok(())
+}
+
+pub fn set_password(name: &str, password: &str) -> Result<bool, Box<dyn Error>> {
+    let password_hash = hash_password(password)?;
+    let c = server_db::open()?;
+    let n = c.execute("update user set password_hash=?1 where name=?2", params![password_hash, name])?;
+    // the old sessions should not survive a password change
+    c.execute("delete from session where user_id in (select id from user where name=?1)", [name])?;
+    Ok(n > 0)
+}
+
+pub fn set_role(name: &str, role: Role, lines: &[String]) -> Result<bool, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let n = c.execute("update user set role=?1, lines=?2 where name=?3", params![role.as_str(), lines.join(","), name])?;
+    Ok(n > 0)
+}
+
+/// the sessions and api tokens of the user are deleted too
+pub fn del_user(name: &str) -> Result<bool, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let n = c.execute("delete from user where name=?1", [name])?;
+    Ok(n > 0)
+}
+
+pub fn list_users() -> Result<Vec<User>, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let mut stmt = c.prepare("select id, name, role, lines from user order by name")?;
+    let users = stmt.query_map([], row_to_user)?.collect::<Result<Vec<User>, _>>()?;
+    Ok(users)
+}
+
+/// Some(session token) when the name and password match
+pub fn login(name: &str, password: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let found = c.query_row("select id, password_hash from user where name=?1", [name], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+    }).optional()?;
+    let (user_id, password_hash) = match found {
+        Some(x) => x,
+        None => { return Ok(None); }
+    };
+    if !verify_password(password, &password_hash) {
+        return Ok(None);
+    }
+    let now = mytime::now_ts();
+    let expire = now + config::get_config().auth.session_hours as i64 * 3600;
+    c.execute("delete from session where expire_int < ?1", [now])?;
+    let token = new_token();
+    c.execute("insert into session (token_hash, user_id, expire_int) values (?1, ?2, ?3)",
+              params![token_hash(&token), user_id, expire])?;
+    Ok(Some(token))
+}
+
+pub fn logout(session: &str) -> Result<(), Box<dyn Error>> {
+    let c = server_db::open()?;
+    c.execute("delete from session where token_hash=?1", [token_hash(session)])?;
+    Ok(())
+}
+
+pub fn user_of_session(session: &str) -> Result<Option<User>, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let user = c.query_row(
+        "select user.id, user.name, user.role, user.lines from session join user on session.user_id=user.id \
+         where session.token_hash=?1 and session.expire_int>=?2",
+        params![token_hash(session), mytime::now_ts()], row_to_user).optional()?;
+    Ok(user)
+}
+
+/// the token is shown only once, save it in the script
+pub fn add_token(user_name: &str, token_name: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let user_id = c.query_row("select id from user where name=?1", [user_name], |row| row.get::<_, i64>(0))
+        .optional()?;
+    let user_id = match user_id {
+        Some(x) => x,
+        None => { return Ok(None); }
+    };
+    let token = new_token();
+    c.execute("insert into api_token (user_id, name, token_hash, time_int) values (?1, ?2, ?3, ?4)",
+              params![user_id, token_name, token_hash(&token), mytime::now_ts()])?;
+    Ok(Some(token))
+}
+
+pub fn del_token(id: i64) -> Result<bool, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let n = c.execute("delete from api_token where id=?1", [id])?;
+    Ok(n > 0)
+}
+
+/// (id, user name, token name, last used time)
+pub type Rtoken = (i64, String, String, Option<i64>);
+
+pub fn list_tokens() -> Result<Vec<Rtoken>, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let mut stmt = c.prepare("select api_token.id, user.name, api_token.name, api_token.last_used_int \
+                              from api_token join user on api_token.user_id=user.id order by api_token.id")?;
+    let tokens = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(tokens)
+}
+
+pub fn user_of_token(token: &str) -> Result<Option<User>, Box<dyn Error>> {
+    let c = server_db::open()?;
+    let hash = token_hash(token);
+    let user = c.query_row(
+        "select user.id, user.name, user.role, user.lines from api_token join user on api_token.user_id=user.id \
+         where api_token.token_hash=?1", [&hash], row_to_user).optional()?;
+    if user.is_some() {
+        c.execute("update api_token set last_used_int=?1 where token_hash=?2", params![mytime::now_ts(), hash])?;
+    }
+    Ok(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_and_role() {
+        let h = hash_password("secret").unwrap();
+        assert!(verify_password("secret", &h));
+        assert!(!verify_password("Secret", &h));
+        assert!(!verify_password("secret", "not a hash"));
+        assert_ne!(new_token(), new_token());
+        assert_eq!(token_hash("abc").len(), 64);
+        assert!(Role::Admin > Role::Engineer && Role::Engineer > Role::Viewer);
+        assert_eq!(Role::from_str("engineer"), Some(Role::Engineer));
+        let user = User { id: 1, name: "a".to_string(), role: Role::Viewer, lines: vec!["fst1".to_string()] };
+        assert!(user.can_view_line("fst1"));
+        assert!(!user.can_view_line("bst1"));
+        assert!(User::anonymous(Role::Viewer).can_view_line("bst1"));
+    }
+}
@@ -0,0 +1,195 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use crate::analysis::config;
+
+// result code of tst_record: 'S' start, 'P' pass, 'F' fail, 'U' unlock
+static RESULT_CODE: [&str; 4] = ["S", "P", "F", "U"];
+static DEFAULT_DEFINE: &str = "pass_rate";
+
+/// a named yield formula, numerator and denominator are the sum of the result code quantity.
+#[derive(Deserialize, Debug, Clone)]
+pub struct YieldDefine {
+    pub numerator: Vec<String>,
+    pub denominator: Vec<String>,
+    // for fail rate, the smaller the better
+    #[serde(default)]
+    pub lower_is_better: bool,
+}
+
+/// target and warning threshold in percent, and which yield define is used by the station.
+#[derive(Deserialize, Debug, Clone)]
+pub struct StationThreshold {
+    pub define: String,
+    pub target: f32,
+    pub warning: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum YieldStatus {
+    Good,
+    Warning,
+    Bad,
+    // no threshold configured, or nothing tested
+    Unrated,
+}
+
+impl YieldStatus {
+    /// css class defined in general.css
+    pub fn class(&self) -> &'static str {
+        match self {
+            YieldStatus::Good => "yield_good",
+            YieldStatus::Warning => "yield_warning",
+            YieldStatus::Bad => "yield_bad",
+            YieldStatus::Unrated => "yield_unrated",
+        }
+    }
+}
+
+/// what the template need to show one yield: "97.5 %", "yield_good", "P / (P+F)"
+#[derive(Debug, Clone, Default)]
+pub struct YieldView {
+    pub text: String,
+    pub class: &'static str,
+    pub formula: String,
+}
+
+fn code_sum(codes: &[String], counts: (u32, u32, u32, u32)) -> u32 {
+    let mut sum = 0u32;
+    for code in codes {
+        sum += match code.as_str() {
+            "S" => counts.0,
+            "P" => counts.1,
+            "F" => counts.2,
+            "U" => counts.3,
+            _ => 0
+        };
+    }
+    sum
+}
+
+impl YieldDefine {
+    /// counts is (start, pass, fail, unlock), return None when the denominator is 0
+    pub fn rate(&self, counts: (u32, u32, u32, u32)) -> Option<f32> {
+        let (numerator, denominator) = self.parts(counts);
+        if denominator == 0 {
+            return None;
+        }
+        Some(100.0 * numerator as f32 / denominator as f32)
+    }
+
+    /// (numerator, denominator) of the counts
+    pub fn parts(&self, counts: (u32, u32, u32, u32)) -> (u32, u32) {
+        (code_sum(&self.numerator, counts), code_sum(&self.denominator, counts))
+    }
+
+    /// "P / (P+F)"
+    pub fn formula(&self) -> String {
+        let numerator = self.numerator.join("+");
+        let denominator = self.denominator.join("+");
+        let numerator = if self.numerator.len() > 1 { format!("({numerator})") } else { numerator };
+        let denominator = if self.denominator.len() > 1 { format!("({denominator})") } else { denominator };
+        format!("{numerator} / {denominator}")
+    }
+
+    pub fn valid(&self) -> bool {
+        !self.denominator.is_empty()
+            && self.numerator.iter().chain(self.denominator.iter()).all(|x| RESULT_CODE.contains(&x.as_str()))
+    }
+}
+
+impl StationThreshold {
+    pub fn status(&self, define: &YieldDefine, rate: Option<f32>) -> YieldStatus {
+        let rate = match rate {
+            Some(r) => r,
+            None => { return YieldStatus::Unrated; }
+        };
+        let (good, warning) = if define.lower_is_better {
+            (rate <= self.target, rate <= self.warning)
+        } else {
+            (rate >= self.target, rate >= self.warning)
+        };
+        if good {
+            YieldStatus::Good
+        } else if warning {
+            YieldStatus::Warning
+        } else {
+            YieldStatus::Bad
+        }
+    }
+}
+
+/// used when config.toml has no [yield_define.*]
+pub fn default_defines() -> HashMap<String, YieldDefine> {
+    let codes = |x: &[&str]| x.iter().map(|c| c.to_string()).collect::<Vec<String>>();
+    let mut map = HashMap::new();
+    map.insert("pass_rate".to_string(),
+               YieldDefine { numerator: codes(&["P"]), denominator: codes(&["P", "F"]), lower_is_better: false });
+    map.insert("pass_rate_with_unlock".to_string(),
+               YieldDefine { numerator: codes(&["P"]), denominator: codes(&["P", "F", "U"]), lower_is_better: false });
+    map.insert("fail_rate".to_string(),
+               YieldDefine { numerator: codes(&["F"]), denominator: codes(&["S"]), lower_is_better: true });
+    map
+}
+
+pub fn calc(defines: &HashMap<String, YieldDefine>, threshold: Option<&StationThreshold>,
+            counts: (u32, u32, u32, u32)) -> YieldView {
+    let define_name = threshold.map(|t| t.define.as_str()).unwrap_or(DEFAULT_DEFINE);
+    let define = match defines.get(define_name) {
+        Some(d) => d,
+        None => { return YieldView { text: "".to_string(), class: YieldStatus::Unrated.class(), formula: "".to_string() }; }
+    };
+    let rate = define.rate(counts);
+    let status = match threshold {
+        Some(t) => t.status(define, rate),
+        None => YieldStatus::Unrated,
+    };
+    let text = match rate {
+        Some(r) => format!("{r:.1} %"),
+        None => "".to_string(),
+    };
+    YieldView { text, class: status.class(), formula: define.formula() }
+}
+
+/// the yield define used by the station in config.toml, None if the define is missing
+pub fn station_define(station: &str) -> Option<YieldDefine> {
+    let config = config::get_config();
+    let define_name = config.yield_station.get(station).map(|t| t.define.as_str()).unwrap_or(DEFAULT_DEFINE);
+    config.yield_define.get(define_name).cloned()
+}
+
+/// yield of the station with the define and threshold from config.toml
+pub fn station_yield<T: Into<u32>>(station: &str, counts: (T, T, T, T)) -> YieldView {
+    let config = config::get_config();
+    let counts = (counts.0.into(), counts.1.into(), counts.2.into(), counts.3.into());
+    calc(&config.yield_define, config.yield_station.get(station), counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_and_status() {
+        let defines = default_defines();
+        let counts = (100, 90, 10, 5);
+        let pass_rate = defines.get("pass_rate").unwrap();
+        assert_eq!(pass_rate.rate(counts), Some(90.0));
+        assert_eq!(pass_rate.formula(), "P / (P+F)");
+        assert_eq!(defines.get("fail_rate").unwrap().rate(counts), Some(10.0));
+        assert_eq!(defines.get("pass_rate").unwrap().rate((0, 0, 0, 3)), None);
+
+        let threshold = StationThreshold { define: "pass_rate".to_string(), target: 95.0, warning: 85.0 };
+        let v = calc(&defines, Some(&threshold), counts);
+        assert_eq!(v.text, "90.0 %");
+        assert_eq!(v.class, "yield_warning");
+        let v = calc(&defines, Some(&threshold), (100, 80, 20, 0));
+        assert_eq!(v.class, "yield_bad");
+        let v = calc(&defines, None, (100, 99, 1, 0));
+        assert_eq!(v.class, "yield_unrated");
+
+        let threshold = StationThreshold { define: "fail_rate".to_string(), target: 2.0, warning: 5.0 };
+        assert_eq!(calc(&defines, Some(&threshold), (100, 99, 1, 0)).class, "yield_good");
+        assert_eq!(calc(&defines, Some(&threshold), (100, 96, 4, 0)).class, "yield_warning");
+        assert_eq!(calc(&defines, Some(&threshold), (100, 90, 10, 0)).class, "yield_bad");
+    }
+}
@@ -0,0 +1,107 @@
+use axum::extract;
+use axum::response::{IntoResponse, Response};
+use http::{header, HeaderMap, HeaderValue, StatusCode};
+use rust_embed::RustEmbed;
+use std::path::{Component, Path};
+use crate::analysis::config;
+use tracing::error;
+
+// the css, js, fonts and images are built into server.exe, it runs from any working directory.
+// a file under static_dir of config.toml replaces the built-in one of the same path.
+
+#[derive(RustEmbed)]
+#[folder = "static/"]
+struct Static;
+
+// the built-in files change only with a new server.exe, the browser checks the ETag after an hour
+static EMBEDDED_CACHE: &str = "public, max-age=3600";
+// the file of static_dir may be edited any time
+static OVERRIDE_CACHE: &str = "no-cache";
+
+/// "css/general.css" -> true, "../config.toml" or "/etc/passwd" -> false
+fn safe_path(path: &str) -> bool {
+    !path.is_empty() && Path::new(path).components().all(|x| matches!(x, Component::Normal(_)))
+}
+
+fn mime_of(path: &str) -> String {
+    mime_guess::from_path(path).first_or_octet_stream().to_string()
+}
+
+fn not_modified(header_map: &HeaderMap, etag: &str) -> bool {
+    header_map.get_all(header::IF_NONE_MATCH).iter()
+        .filter_map(|x| x.to_str().ok())
+        .any(|x| x.split(',').any(|t| t.trim() == etag || t.trim() == "*"))
+}
+
+fn file_response(header_map: &HeaderMap, data: Vec<u8>, mime: &str, etag: String, cache: &'static str) -> Response {
+    let mut response = if not_modified(header_map, &etag) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        let mut r = data.into_response();
+        if let Ok(v) = HeaderValue::from_str(mime) {
+            r.headers_mut().insert(header::CONTENT_TYPE, v);
+        }
+        r
+    };
+    if let Ok(v) = HeaderValue::from_str(&etag) {
+        response.headers_mut().insert(header::ETAG, v);
+    }
+    response.headers_mut().insert(header::CACHE_CONTROL, HeaderValue::from_static(cache));
+    // the body may be compressed by Accept-Encoding
+    response.headers_mut().insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+    response
+}
+
+/// the file of static_dir, None if no such file
+async fn override_file(header_map: &HeaderMap, path: &str) -> Option<Response> {
+    let dir = config::get_config().static_dir.clone()?;
+    let file = dir.join(path);
+    let meta = tokio::fs::metadata(&file).await.ok().filter(|x| x.is_file())?;
+    let data = match tokio::fs::read(&file).await {
+        Ok(d) => d,
+        Err(e) => {
+            error!("read static file {file:?} fail, {e}");
+            return None;
+        }
+    };
+    let modified = meta.modified().ok()
+        .and_then(|x| x.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|x| x.as_secs()).unwrap_or(0);
+    let etag = format!("W/\"{:x}-{:x}\"", meta.len(), modified);
+    Some(file_response(header_map, data, &mime_of(path), etag, OVERRIDE_CACHE))
+}
+
+/// GET /static/*path, compressed by the CompressionLayer of main.rs
+pub async fn static_file(extract::Path(path): extract::Path<String>, header_map: HeaderMap) -> Response {
+    if !safe_path(&path) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    if let Some(response) = override_file(&header_map, &path).await {
+        return response;
+    }
+    match Static::get(&path) {
+        Some(file) => {
+            let hash = file.metadata.sha256_hash();
+            let etag = format!("\"{}\"", hash[..8].iter().map(|x| format!("{x:02x}")).collect::<String>());
+            file_response(&header_map, file.data.into_owned(), file.metadata.mimetype(), etag, EMBEDDED_CACHE)
+        }
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_static() {
+        assert!(safe_path("css/general.css"));
+        assert!(!safe_path("../config.toml"));
+        assert!(!safe_path("/etc/passwd"));
+        assert!(!safe_path("css/../../config.toml"));
+        assert!(!safe_path(""));
+        assert!(Static::get("css/general.css").is_some());
+        assert_eq!(mime_of("css/general.css"), "text/css");
+        assert_eq!(mime_of("images/bigeasy_keyname.png"), "image/png");
+    }
+}
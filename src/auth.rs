@@ -0,0 +1,143 @@
+use axum::extract::{self, RawPathParams, Request, State};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::Form;
+use http::{header, HeaderMap, Method, StatusCode};
+use serde::Deserialize;
+use crate::analysis::{config, lang_tran};
+use crate::analysis::user::{self, Role, User};
+use crate::template::{HtmlTemplate, LoginTemplate};
+use crate::view::{cookie_value, local_path, negotiate_lang};
+use tracing::{error, info};
+
+static SESSION_COOKIE: &str = "session";
+
+fn bearer_token(header_map: &HeaderMap) -> Option<&str> {
+    header_map.get(header::AUTHORIZATION)?.to_str().ok()?.strip_prefix("Bearer ")
+}
+
+/// the api token of "Authorization: Bearer <token>" first, then the session cookie of the browser,
+/// at last the anonymous visitor if config.toml allow it
+fn visitor(header_map: &HeaderMap) -> Result<Option<User>, String> {
+    if let Some(token) = bearer_token(header_map) {
+        // a wrong token is not treated as anonymous, the script should know it
+        return user::user_of_token(token.trim()).map_err(|e| e.to_string());
+    }
+    if let Some(session) = cookie_value(header_map, SESSION_COOKIE) {
+        if let Some(u) = user::user_of_session(&session).map_err(|e| e.to_string())? {
+            return Ok(Some(u));
+        }
+    }
+    Ok(Role::from_str(&config::get_config().auth.anonymous).map(User::anonymous))
+}
+
+/// middleware for a group of routes in main.rs, the visitor should have the role at least,
+/// and be allowed to the `:line` of the path. the visitor is put into the request extensions.
+pub async fn require_role(State(role): State<Role>, path_params: Option<RawPathParams>,
+                          mut request: Request, next: Next) -> Response {
+    let visitor = match visitor(request.headers()) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("check login fail, {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+        }
+    };
+    let line = path_params.and_then(|p| p.iter().find(|(k, _)| *k == "line").map(|(_, v)| v.to_string()));
+    let allowed = match &visitor {
+        Some(v) => v.role >= role && line.as_deref().map(|l| v.can_view_line(l)).unwrap_or(true),
+        None => false,
+    };
+    if allowed {
+        request.extensions_mut().insert(visitor.unwrap());
+        return next.run(request).await;
+    }
+    let need_login = visitor.as_ref().map(|v| v.id == 0).unwrap_or(true);
+    let browser = request.method() == Method::GET && bearer_token(request.headers()).is_none();
+    if need_login && browser {
+        let next = request.uri().path_and_query().map(|x| x.as_str()).unwrap_or("/");
+        let url = format!("/login?next={}", urlencode(next));
+        return Redirect::to(&url).into_response();
+    }
+    if need_login {
+        return (StatusCode::UNAUTHORIZED, "login or api token required").into_response();
+    }
+    let msg = match line {
+        Some(line) => format!("forbidden, need role {} and access to line {line}", role.as_str()),
+        None => format!("forbidden, need role {}", role.as_str()),
+    };
+    (StatusCode::FORBIDDEN, msg).into_response()
+}
+
+fn urlencode(s: &str) -> String {
+    s.bytes().map(|b| match b {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (b as char).to_string(),
+        _ => format!("%{b:02X}"),
+    }).collect()
+}
+
+fn login_page(header_map: &HeaderMap, next: String, name: String, fail: bool) -> Response {
+    let lang = negotiate_lang(header_map);
+    let lang_map = lang_tran::get_lang_map();
+    let lang_map = lang_map.get(lang.as_str()).unwrap();
+    let tpl = LoginTemplate {
+        title: lang_map.get("tem_title_login").unwrap().to_owned(),
+        next,
+        name,
+        error: if fail { lang_map.get("tem_login_fail").unwrap() } else { "" },
+        tem_language: lang_map.get("tem_language").unwrap(),
+        tem_home: lang_map.get("tem_home").unwrap(),
+        tem_username: lang_map.get("tem_username").unwrap(),
+        tem_password: lang_map.get("tem_password").unwrap(),
+        tem_login: lang_map.get("tem_login").unwrap(),
+    };
+    let status = if fail { StatusCode::UNAUTHORIZED } else { StatusCode::OK };
+    (status, HtmlTemplate(tpl)).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginParams {
+    next: Option<String>,
+}
+
+pub async fn login_form(extract::Query(query_params): extract::Query<LoginParams>, header_map: HeaderMap) -> Response {
+    login_page(&header_map, local_path(query_params.next), "".to_string(), false)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginForm {
+    name: String,
+    password: String,
+    next: Option<String>,
+}
+
+pub async fn login(header_map: HeaderMap, Form(form): Form<LoginForm>) -> Response {
+    let next = local_path(form.next);
+    match user::login(&form.name, &form.password) {
+        Ok(Some(session)) => {
+            let config = config::get_config();
+            let max_age = config.auth.session_hours as u64 * 3600;
+            let secure = if config.tls.is_some() { "; Secure" } else { "" };
+            let cookie = format!("{SESSION_COOKIE}={session}; Path=/; Max-Age={max_age}; HttpOnly; SameSite=Lax{secure}");
+            info!("user {} login", form.name);
+            ([(header::SET_COOKIE, cookie)], Redirect::to(&next)).into_response()
+        }
+        Ok(None) => {
+            info!("user {} login fail", form.name);
+            login_page(&header_map, next, form.name, true)
+        }
+        Err(e) => {
+            error!("login fail, {e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+pub async fn logout(header_map: HeaderMap) -> Response {
+    if let Some(session) = cookie_value(&header_map, SESSION_COOKIE) {
+        if let Err(e) = user::logout(&session) {
+            error!("logout fail, {e}");
+        }
+    }
+    let cookie = format!("{SESSION_COOKIE}=; Path=/; Max-Age=0; HttpOnly; SameSite=Lax");
+    ([(header::SET_COOKIE, cookie)], Redirect::to("/")).into_response()
+}
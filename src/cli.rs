@@ -0,0 +1,102 @@
+use clap::{Parser, Subcommand};
+use std::env::current_exe;
+use std::path::PathBuf;
+
+/// Web server to view the test record of the Automation BST / FST lines.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    /// path of config.toml, default is config.toml beside the executable
+    #[arg(long, env = "SERVER_CONFIG", global = true)]
+    pub config: Option<PathBuf>,
+    /// dir of the db files, default is db/ beside config.toml
+    #[arg(long, env = "SERVER_DB_DIR", global = true)]
+    pub db_dir: Option<PathBuf>,
+    /// address to listen on
+    #[arg(long, env = "SERVER_BIND", default_value = "0.0.0.0", global = true)]
+    pub bind: String,
+    /// port to listen on, overwrite the port in config.toml
+    #[arg(long, env = "SERVER_PORT", global = true)]
+    pub port: Option<u16>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub enum Command {
+    /// start the web server, the default command
+    Serve,
+    /// check config.toml, locales and every db file, then exit without serving
+    Validate,
+    /// manage the login users in the server db
+    User {
+        #[command(subcommand)]
+        action: UserAction,
+    },
+    /// manage the api tokens for scripts, send it by header "Authorization: Bearer <token>"
+    Token {
+        #[command(subcommand)]
+        action: TokenAction,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub enum UserAction {
+    /// add a user, the password is read from env SERVER_PASSWORD or prompted
+    Add {
+        name: String,
+        #[arg(long, default_value = "viewer", value_parser = ROLES)]
+        role: String,
+        /// the lines allowed, like "fst1,fst2", all lines if not given
+        #[arg(long, value_delimiter = ',', value_parser = LINES)]
+        lines: Vec<String>,
+    },
+    /// change the password, the user is logged out everywhere
+    Passwd { name: String },
+    /// change the role and the lines allowed
+    Role {
+        name: String,
+        #[arg(value_parser = ROLES)]
+        role: String,
+        #[arg(long, value_delimiter = ',', value_parser = LINES)]
+        lines: Vec<String>,
+    },
+    /// delete the user and the api tokens of the user
+    Del { name: String },
+    List,
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub enum TokenAction {
+    /// create a token with the role and lines of the user, the token is printed only once
+    Add { user: String, name: String },
+    Del { id: i64 },
+    List,
+}
+
+static ROLES: [&str; 3] = ["viewer", "engineer", "admin"];
+static LINES: [&str; 4] = ["bst1", "bst2", "fst1", "fst2"];
+
+/// the resolved paths, the project organization is described in config.toml
+pub struct Paths {
+    pub exe_path: PathBuf,
+    pub config_path: PathBuf,
+    pub db_dir: PathBuf,
+    pub locales_dir: PathBuf,
+}
+
+impl Cli {
+    pub fn paths(&self) -> Paths {
+        let exe_path = current_exe().unwrap();
+        let config_path = match &self.config {
+            Some(p) => p.to_owned(),
+            None => exe_path.parent().unwrap().join("config.toml"),
+        };
+        let config_dir = config_path.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+        let db_dir = match &self.db_dir {
+            Some(p) => p.to_owned(),
+            None => config_dir.join("db"),
+        };
+        Paths { exe_path, config_path, db_dir, locales_dir: config_dir.join("locales") }
+    }
+}
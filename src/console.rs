@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use axum::extract::{self, ws::{Message, WebSocket, WebSocketUpgrade}};
+use axum::response::{IntoResponse, Response};
+use http::StatusCode;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, mpsc};
+use crate::analysis::{config, fixture, lang_tran, mytime, port_config};
+use crate::analysis::fixture::PortLock;
+use crate::analysis::user::User;
+use crate::template::{ConsoleTemplate, HtmlTemplate};
+use tracing::{error, info};
+
+// the console of a fixture port in the browser, websocket <-> tcp of the terminal server.
+// the sessions of a same port share one tcp connection, one of them can write at a time,
+// the others watch. every console is saved in fixture.transcript_dir of config.toml.
+
+static CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+enum Output {
+    Data(Vec<u8>),
+    // someone joined or left
+    Notice(String),
+    Closed(String),
+}
+
+enum Input {
+    // (user, bytes to the port)
+    Data(String, Vec<u8>),
+    Notice(String),
+}
+
+/// the tcp connection of a port shared by the sessions, closed when the last session left
+struct Hub {
+    output: broadcast::Sender<Output>,
+    input: mpsc::Sender<Input>,
+    // (session id, user) of the writer
+    writer: Mutex<Option<(u64, String)>>,
+}
+
+impl Hub {
+    /// the first session typing is the writer until it left, Err(writer) for the others
+    fn claim(&self, id: u64, user: &str) -> Result<(), String> {
+        let mut writer = self.writer.lock().unwrap();
+        match writer.as_ref() {
+            Some((w, name)) if *w != id => Err(name.to_owned()),
+            _ => {
+                *writer = Some((id, user.to_string()));
+                Ok(())
+            }
+        }
+    }
+
+    fn release(&self, id: u64) {
+        let mut writer = self.writer.lock().unwrap();
+        if writer.as_ref().map(|x| x.0) == Some(id) {
+            *writer = None;
+        }
+    }
+}
+
+// (host, port)
+type PortKey = (String, u16);
+
+// the opened console of the port
+static HUBS: Lazy<Mutex<HashMap<PortKey, Weak<Hub>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static SESSION_ID: AtomicU64 = AtomicU64::new(1);
+
+struct Session {
+    id: u64,
+    user: String,
+    hub: Arc<Hub>,
+    output: broadcast::Receiver<Output>,
+}
+
+/// transcripts/fst1_CELL_69_2018_20240104_204822.log
+fn transcript_path(line: &str, name: &str, port: u16) -> PathBuf {
+    let name: String = name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect();
+    let time = mytime::now_vn().replace(['-', ':'], "").replace(' ', "_");
+    config::get_config().fixture.transcript_dir.join(format!("{line}_{name}_{port}_{time}.log"))
+}
+
+/// copy between the tcp connection and the sessions until the port is closed or every session left
+async fn pump(mut stream: TcpStream, mut input: mpsc::Receiver<Input>, output: broadcast::Sender<Output>,
+              mut transcript: File, key: PortKey, _lock: PortLock) {
+    let mut buf = [0u8; 1024];
+    let reason = loop {
+        tokio::select! {
+            n = stream.read(&mut buf) => match n {
+                Ok(0) => break "closed by the terminal server".to_string(),
+                Ok(n) => {
+                    let _ = transcript.write_all(&buf[..n]);
+                    let _ = output.send(Output::Data(buf[..n].to_vec()));
+                }
+                Err(e) => break format!("read fail, {e}"),
+            },
+            x = input.recv() => match x {
+                Some(Input::Data(user, bytes)) => {
+                    let text = String::from_utf8_lossy(&bytes);
+                    let _ = writeln!(transcript, "\n[{} {user}] {}", mytime::now_vn(), text.trim_end());
+                    if let Err(e) = stream.write_all(&bytes).await {
+                        break format!("write fail, {e}");
+                    }
+                }
+                Some(Input::Notice(text)) => {
+                    let _ = writeln!(transcript, "\n[{}] {text}", mytime::now_vn());
+                    let _ = output.send(Output::Notice(text));
+                }
+                None => break "every session left".to_string(),
+            },
+        }
+    };
+    let _ = writeln!(transcript, "\n[{}] console closed, {reason}", mytime::now_vn());
+    info!("console of {}:{} closed, {reason}", key.0, key.1);
+    // the port is still locked here, no other hub of the same port
+    HUBS.lock().unwrap().remove(&key);
+    let _ = output.send(Output::Closed(reason));
+}
+
+async fn open_hub(line: &str, name: &str, target: &fixture::Target) -> Result<Arc<Hub>, (StatusCode, String)> {
+    let lock = fixture::lock_port(&target.host, target.port).ok_or_else(|| {
+        (StatusCode::CONFLICT, format!("{}:{} is in use by a fixture command, try later", target.host, target.port))
+    })?;
+    let stream = match tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect((target.host.as_str(), target.port))).await {
+        Ok(Ok(s)) => s,
+        Ok(Err(e)) => { return Err((StatusCode::BAD_GATEWAY, format!("connect {}:{} fail, {e}", target.host, target.port))); }
+        Err(_) => { return Err((StatusCode::GATEWAY_TIMEOUT, format!("connect {}:{} timeout", target.host, target.port))); }
+    };
+    let path = transcript_path(line, name, target.port);
+    let transcript = path.parent().map(fs::create_dir_all).unwrap_or(Ok(())).and_then(|_| File::create(&path))
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("create transcript {path:?} fail, {e}")))?;
+    let (output, _) = broadcast::channel(256);
+    let (input, rx) = mpsc::channel(16);
+    let hub = Arc::new(Hub { output: output.clone(), input, writer: Mutex::new(None) });
+    let key = (target.host.to_owned(), target.port);
+    tokio::spawn(pump(stream, rx, output, transcript, key, lock));
+    Ok(hub)
+}
+
+/// join the console of the port, open it if nobody there
+async fn join(line: &str, name: &str, port_index: usize, user: &str) -> Result<Session, (StatusCode, String)> {
+    let target = fixture::resolve(line, name, port_index, None).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let key = (target.host.to_owned(), target.port);
+    let opened = HUBS.lock().unwrap().get(&key).and_then(|x| x.upgrade());
+    let hub = match opened {
+        Some(hub) => hub,
+        None => {
+            let hub = open_hub(line, name, &target).await?;
+            HUBS.lock().unwrap().insert(key, Arc::downgrade(&hub));
+            hub
+        }
+    };
+    let output = hub.output.subscribe();
+    Ok(Session { id: SESSION_ID.fetch_add(1, Ordering::Relaxed), user: user.to_string(), hub, output })
+}
+
+/// a text message of the websocket is a line typed, sent with fixture.line_end
+async fn run(mut socket: WebSocket, mut session: Session, line_end: String) {
+    let _ = session.hub.input.send(Input::Notice(format!("{} joined", session.user))).await;
+    loop {
+        tokio::select! {
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    if let Err(writer) = session.hub.claim(session.id, &session.user) {
+                        let notice = format!("\n*** read only, {writer} is writing\n");
+                        if socket.send(Message::Text(notice)).await.is_err() {
+                            break;
+                        }
+                        continue;
+                    }
+                    let bytes = format!("{text}{line_end}").into_bytes();
+                    if session.hub.input.send(Input::Data(session.user.to_owned(), bytes)).await.is_err() {
+                        break;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            out = session.output.recv() => {
+                let (text, closed) = match out {
+                    Ok(Output::Data(bytes)) => (String::from_utf8_lossy(&bytes).to_string(), false),
+                    Ok(Output::Notice(notice)) => (format!("\n*** {notice}\n"), false),
+                    Ok(Output::Closed(reason)) => (format!("\n*** console closed, {reason}\n"), true),
+                    Err(broadcast::error::RecvError::Lagged(n)) => (format!("\n*** {n} messages skipped\n"), false),
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if socket.send(Message::Text(text)).await.is_err() || closed {
+                    break;
+                }
+            }
+        }
+    }
+    session.hub.release(session.id);
+    let _ = session.hub.input.send(Input::Notice(format!("{} left", session.user))).await;
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConsoleParams {
+    // the cell or the name of the port config
+    name: String,
+    // which one of "2002/2010", 0 is the first
+    #[serde(default)]
+    port_index: usize,
+}
+
+pub async fn console_ws(
+    extract::Path((_lang, line)): extract::Path<(String, String)>,
+    extract::Query(params): extract::Query<ConsoleParams>,
+    extract::Extension(user): extract::Extension<User>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let session = match join(&line, &params.name, params.port_index, &user.name).await {
+        Ok(s) => s,
+        Err(e) => { return e.into_response(); }
+    };
+    info!("{} open console of {line} {}", user.name, params.name);
+    let line_end = config::get_config().fixture.line_end.to_owned();
+    ws.on_upgrade(move |socket| run(socket, session, line_end))
+}
+
+pub async fn console_page(
+    extract::Path((lang, line)): extract::Path<(String, String)>,
+    extract::Query(params): extract::Query<ConsoleParams>,
+) -> Response {
+    assert!(lang_tran::has_lang(&lang));
+    let ports = match port_config::ports_of(&line) {
+        Ok(p) => p.into_iter().find(|x| x.0 == params.name).map(|x| x.1),
+        Err(e) => {
+            error!("read port config fail, {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    };
+    let ports = match ports {
+        Some(p) => p,
+        None => { return (StatusCode::NOT_FOUND, format!("{} is not in the port config", params.name)).into_response(); }
+    };
+    let ports = ports.split('/').enumerate()
+        .map(|(i, p)| (i.to_string(), p.to_string(), if i == params.port_index { "selected" } else { "" }))
+        .collect();
+    let config = config::get_config();
+    let hostname = config.get_hostname(&line).unwrap().to_owned();
+    let lang_map = lang_tran::get_lang_map();
+    let lang_map = lang_map.get(lang.as_str()).unwrap();
+    let tpl = ConsoleTemplate {
+        name: params.name,
+        ports,
+        title: lang_map.get("tem_title_console").unwrap().to_owned(),
+        line,
+        hostname,
+        update_time: mytime::now_vn(),
+        tem_local_time: lang_map.get("tem_local_time").unwrap(),
+        tem_language: lang_map.get("tem_language").unwrap(),
+        tem_quantity_of_pass_fail: lang_map.get("tem_quantity_of_pass_fail").unwrap(),
+        tem_yield_of_shift: lang_map.get("tem_yield_of_shift").unwrap(),
+        tem_fail_record_details: lang_map.get("tem_fail_record_details").unwrap(),
+        tem_query_400_records_of_cell: lang_map.get("tem_query_400_records_of_cell").unwrap(),
+        tem_query_sn_history: lang_map.get("tem_query_sn_history").unwrap(),
+        tem_maintenance_log: lang_map.get("tem_maintenance_log").unwrap(),
+        tem_key_name_of_bgibest: lang_map.get("tem_key_name_of_bgibest").unwrap(),
+        tem_port_config_of_terminal_server: lang_map
+            .get("tem_port_config_of_terminal_server")
+            .unwrap(),
+        tem_submit: lang_map.get("tem_submit").unwrap(),
+    };
+    HtmlTemplate(tpl).into_response()
+}
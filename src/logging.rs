@@ -0,0 +1,85 @@
+use once_cell::sync::OnceCell;
+use std::time::Duration;
+use tracing::{error, info};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use crate::analysis::config;
+
+// the log goes to stdout, and as json lines to [log] file of config.toml if set.
+// the level is [log] level, replaced by watch_level() when config.toml is reloaded, env RUST_LOG overrides it.
+
+static HANDLE: OnceCell<reload::Handle<EnvFilter, Registry>> = OnceCell::new();
+
+// how often watch_level() check for a reloaded config
+static WATCH_INTERVAL: Duration = Duration::from_secs(3);
+
+/// RUST_LOG if set, else the level of config.toml
+fn filter_of(level: &str) -> EnvFilter {
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level))
+}
+
+fn rotation_of(rotation: &str) -> Rotation {
+    match rotation {
+        "minutely" => Rotation::MINUTELY,
+        "hourly" => Rotation::HOURLY,
+        "never" => Rotation::NEVER,
+        _ => Rotation::DAILY,
+    }
+}
+
+/// set the global subscriber, keep the guard until exit, or the last lines of the file are lost
+pub fn init() -> Result<Option<WorkerGuard>, String> {
+    let log = config::get_config().log.clone();
+    let (filter, handle) = reload::Layer::new(filter_of(&log.level));
+    let (file_layer, guard) = match &log.file {
+        None => (None, None),
+        Some(file) => {
+            let dir = file.parent().ok_or(format!("log.file {file:?} has no dir"))?;
+            let prefix = file.file_name().ok_or(format!("log.file {file:?} has no file name"))?;
+            std::fs::create_dir_all(dir).map_err(|e| format!("create log dir {dir:?} fail, {e}"))?;
+            let appender = RollingFileAppender::builder()
+                .rotation(rotation_of(&log.rotation))
+                .filename_prefix(prefix.to_string_lossy())
+                .max_log_files(log.max_files.max(1))
+                .build(dir)
+                .map_err(|e| format!("open log file {file:?} fail, {e}"))?;
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let layer = fmt::layer().json().with_current_span(true).with_span_list(true).with_writer(writer);
+            (Some(layer), Some(guard))
+        }
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer())
+        .with(file_layer)
+        .try_init()
+        .map_err(|e| format!("init tracing fail, {e}"))?;
+    let _ = HANDLE.set(handle);
+    Ok(guard)
+}
+
+/// apply the new log.level of a reloaded config.toml, the file and rotation take effect after restart
+pub async fn watch_level() {
+    let mut generation = config::generation();
+    let mut level = config::get_config().log.level.clone();
+    loop {
+        tokio::time::sleep(WATCH_INTERVAL).await;
+        if config::generation() == generation {
+            continue;
+        }
+        generation = config::generation();
+        let new = config::get_config().log.level.clone();
+        if new == level {
+            continue;
+        }
+        let Some(handle) = HANDLE.get() else { return };
+        match handle.reload(filter_of(&new)) {
+            Ok(_) => info!("log level changed from {level} to {new}"),
+            Err(e) => error!("change log level to {new} fail, {e}"),
+        }
+        level = new;
+    }
+}
@@ -1,54 +1,296 @@
 mod analysis;
+mod assets;
+mod auth;
+mod cli;
+mod console;
+mod logging;
 mod template;
+mod tls;
 mod view;
 
+use std::process::ExitCode;
 use axum::extract::Request;
-use axum::{routing, Router, ServiceExt};
+use clap::Parser;
+use axum::{middleware, routing, Router, ServiceExt};
+use tower_http::compression::CompressionLayer;
 use tower_http::normalize_path::NormalizePathLayer;
-use tower_http::services::fs::ServeDir;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::{DefaultOnResponse, TraceLayer};
+use tower_http::LatencyUnit;
 use tower_layer::Layer;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use view::{homepage, line_page, keyname, query_sn, portconfig, query_cell, pf_data, day_yield, fail_detail,
-           pre_day, pre_shift, json_today};
-use crate::analysis::config::CONFIG;
+use tracing::{error, info, Level};
+use view::{homepage, db_paths, line_page, keyname, query_sn, portconfig, query_cell, pf_data, day_yield, fail_detail,
+           pre_day, pre_shift, json_today, json_languages, reload_lang, set_lang, lang_redirect,
+           reload_config, maintenance, add_maintenance, add_fail_tag, set_terminal_server, set_fixture_port,
+           set_fixture_command, set_key_name, port_status, fixture_command, json_fixture_command, make_shift_report,
+           compare, cross_line, data_quality, kiosk};
+use crate::analysis::{config, db, lang_tran, port_check, server_db, shift_report, tst_record, user};
+use crate::analysis::user::Role;
+use crate::cli::{Cli, Command, TokenAction, UserAction};
+
+/// `server validate`, check every db of config.toml, return false if any error
+fn validate() -> bool {
+    let config = config::get_config();
+    let mut ok = true;
+    for (area, hostname, db_path) in config.get_all_db() {
+        match db::check_db(db_path) {
+            Ok(s) => println!("OK    {area} {hostname} {db_path:?}: {s}"),
+            Err(e) => {
+                println!("ERROR {area} {hostname} {db_path:?}: {e}");
+                ok = false;
+            }
+        }
+    }
+    ok
+}
+
+/// password from env SERVER_PASSWORD for scripting, or prompted twice without echo
+fn read_password() -> Result<String, String> {
+    if let Ok(password) = std::env::var("SERVER_PASSWORD") {
+        return Ok(password);
+    }
+    let password = rpassword::prompt_password("password: ").map_err(|e| e.to_string())?;
+    let again = rpassword::prompt_password("password again: ").map_err(|e| e.to_string())?;
+    if password != again {
+        return Err("the passwords are not same".to_string());
+    }
+    if password.is_empty() {
+        return Err("the password is empty".to_string());
+    }
+    Ok(password)
+}
+
+/// `server user ...`, return false if any error
+fn manage_user(action: UserAction) -> bool {
+    let result = match action {
+        UserAction::Add { name, role, lines } => read_password().and_then(|password| {
+            user::add_user(&name, &password, Role::from_str(&role).unwrap(), &lines).map_err(|e| e.to_string())
+        }).map(|_| format!("user {name} added")),
+        UserAction::Passwd { name } => read_password().and_then(|password| {
+            user::set_password(&name, &password).map_err(|e| e.to_string())
+        }).and_then(|found| if found { Ok(format!("password of {name} changed")) } else { Err(format!("user {name} not found")) }),
+        UserAction::Role { name, role, lines } => user::set_role(&name, Role::from_str(&role).unwrap(), &lines)
+            .map_err(|e| e.to_string())
+            .and_then(|found| if found { Ok(format!("role of {name} changed")) } else { Err(format!("user {name} not found")) }),
+        UserAction::Del { name } => user::del_user(&name)
+            .map_err(|e| e.to_string())
+            .and_then(|found| if found { Ok(format!("user {name} deleted")) } else { Err(format!("user {name} not found")) }),
+        UserAction::List => user::list_users().map_err(|e| e.to_string()).map(|users| {
+            users.iter()
+                .map(|u| format!("{:<20} {:<10} {}", u.name, u.role.as_str(), if u.lines.is_empty() { "all lines".to_string() } else { u.lines.join(",") }))
+                .collect::<Vec<String>>()
+                .join("\n")
+        }),
+    };
+    match result {
+        Ok(msg) => {
+            println!("{msg}");
+            true
+        }
+        Err(e) => {
+            eprintln!("Error: {e}");
+            false
+        }
+    }
+}
+
+/// `server token ...`, return false if any error
+fn manage_token(action: TokenAction) -> bool {
+    let result = match action {
+        TokenAction::Add { user, name } => user::add_token(&user, &name)
+            .map_err(|e| e.to_string())
+            .and_then(|token| token.ok_or(format!("user {user} not found"))),
+        TokenAction::Del { id } => user::del_token(id)
+            .map_err(|e| e.to_string())
+            .and_then(|found| if found { Ok(format!("token {id} deleted")) } else { Err(format!("token {id} not found")) }),
+        TokenAction::List => user::list_tokens().map_err(|e| e.to_string()).map(|tokens| {
+            tokens.iter()
+                .map(|(id, user, name, last_used)| format!("{id:<6} {user:<20} {name:<20} last used: {}",
+                                                           last_used.map(|x| x.to_string()).unwrap_or("never".to_string())))
+                .collect::<Vec<String>>()
+                .join("\n")
+        }),
+    };
+    match result {
+        Ok(msg) => {
+            println!("{msg}");
+            true
+        }
+        Err(e) => {
+            eprintln!("Error: {e}");
+            false
+        }
+    }
+}
 
 #[tokio::main]
-async fn main() {
-    analysis::config::init_config();
-    analysis::lang_tran::init_lang_map();
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "example_templates=debug".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-    println!("tracing init complete");
-    let app = Router::new()
-        .nest_service("/static", ServeDir::new("static"))
-        .route("/", routing::get(homepage))
-        .route("/json/today", routing::get(json_today))
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let paths = cli.paths();
+    if let Err(e) = config::init_config(&paths) {
+        eprintln!("Error: {e}");
+        return ExitCode::FAILURE;
+    }
+    if let Err(e) = lang_tran::init_lang_map() {
+        eprintln!("Error: {e}");
+        return ExitCode::FAILURE;
+    }
+    // validate only reads the config and the dbs, server.db is not created
+    if !matches!(cli.command, Some(Command::Validate)) {
+        if let Err(e) = server_db::init_server_db() {
+            eprintln!("Error: {e}");
+            return ExitCode::FAILURE;
+        }
+    }
+    let ok = match cli.command.clone() {
+        None | Some(Command::Serve) => true,
+        Some(Command::Validate) => validate(),
+        Some(Command::User { action }) => manage_user(action),
+        Some(Command::Token { action }) => manage_token(action),
+    };
+    if !ok {
+        return ExitCode::FAILURE;
+    }
+    if !matches!(cli.command, None | Some(Command::Serve)) {
+        return ExitCode::SUCCESS;
+    }
+    let _log_guard = match logging::init() {
+        Ok(g) => g,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    tokio::spawn(logging::watch_level());
+    // an older sequencer may not write every column, logged once here, the rows are read anyway
+    tst_record::check_schemas();
+    // the read-only yield tables, the role of anonymous visitor is auth.anonymous in config.toml
+    let viewer_routes = Router::new()
         .route("/:lang/:line", routing::get(line_page))
         .route("/:lang/:line/portconfig", routing::get(portconfig))
+        .route("/:lang/:line/port_status", routing::get(port_status))
         .route("/:lang/:line/keyname", routing::get(keyname))
-        .route("/:lang/:line/query_sn", routing::get(query_sn))
         .route("/:lang/:line/pf_data", routing::get(pf_data))
         .route("/:lang/:line/day_yield", routing::get(day_yield))
+        .route("/:lang/:line/compare", routing::get(compare))
+        .route("/:lang/:line/cross_line", routing::get(cross_line))
+        .route("/:lang/:line/:item/preday", routing::get(pre_day))
+        .route("/:lang/:line/:item/preshift", routing::get(pre_shift))
+        .route("/:lang/:line/maintenance", routing::get(maintenance))
+        .route_layer(middleware::from_fn_with_state(Role::Viewer, auth::require_role));
+    // the raw test records
+    let engineer_routes = Router::new()
+        .route("/:lang/:line/query_sn", routing::get(query_sn))
         .route("/:lang/:line/fail_detail", routing::get(fail_detail))
         .route("/:lang/:line/query_cell", routing::get(query_cell))
-        .route("/:lang/:line/:item/preday", routing::get(pre_day))
-        .route("/:lang/:line/:item/preshift", routing::get(pre_shift));
+        .route("/:lang/:line/maintenance/add", routing::post(add_maintenance))
+        .route("/:lang/:line/fail_tag", routing::post(add_fail_tag))
+        .route("/:lang/:line/fixture_command", routing::post(fixture_command))
+        .route("/:lang/:line/json/fixture_command", routing::post(json_fixture_command))
+        .route("/:lang/:line/console", routing::get(console::console_page))
+        .route("/:lang/:line/console/ws", routing::get(console::console_ws))
+        .route("/:lang/:line/data_quality", routing::get(data_quality))
+        .route_layer(middleware::from_fn_with_state(Role::Engineer, auth::require_role));
+    // the port config and key names shown to everyone
+    let admin_line_routes = Router::new()
+        .route("/:lang/:line/portconfig/server", routing::post(set_terminal_server))
+        .route("/:lang/:line/portconfig/port", routing::post(set_fixture_port))
+        .route("/:lang/:line/portconfig/command", routing::post(set_fixture_command))
+        .route("/:lang/:line/keyname/key", routing::post(set_key_name))
+        .route_layer(middleware::from_fn_with_state(Role::Admin, auth::require_role));
+    let admin_routes = Router::new()
+        .route("/admin/reload_lang", routing::post(reload_lang))
+        .route("/admin/reload_config", routing::post(reload_config))
+        .route("/admin/shift_report", routing::post(make_shift_report))
+        .route("/admin/paths", routing::get(db_paths))
+        .route_layer(middleware::from_fn_with_state(Role::Admin, auth::require_role));
+    // the overview of every line the visitor can view, and the wall-mounted display of kiosk.js
+    let home_routes = Router::new()
+        .route("/", routing::get(homepage))
+        .route("/kiosk/:name", routing::get(kiosk))
+        .route_layer(middleware::from_fn_with_state(Role::Viewer, auth::require_role));
+    let lang_routes = Router::new()
+        .merge(viewer_routes)
+        .merge(engineer_routes)
+        .merge(admin_line_routes)
+        .route_layer(middleware::from_fn(lang_redirect));
+    let app = Router::new()
+        .route("/static/*path", routing::get(assets::static_file).layer(CompressionLayer::new()))
+        .route("/json/today", routing::get(json_today))
+        .route("/json/languages", routing::get(json_languages))
+        .route("/set_lang/:lang", routing::get(set_lang))
+        .route("/login", routing::get(auth::login_form).post(auth::login))
+        .route("/logout", routing::get(auth::logout))
+        .merge(home_routes)
+        .merge(admin_routes)
+        .merge(lang_routes)
+        // every request is a span of its x-request-id, the response is logged with the latency
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(TraceLayer::new_for_http()
+            .make_span_with(|request: &Request| {
+                let request_id = request.headers().get("x-request-id")
+                    .and_then(|x| x.to_str().ok()).unwrap_or_default();
+                tracing::info_span!("request", method = %request.method(), uri = %request.uri(), request_id)
+            })
+            .on_response(DefaultOnResponse::new().level(Level::INFO).latency_unit(LatencyUnit::Millis)))
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid));
 
     let app = NormalizePathLayer::trim_trailing_slash().layer(app);
 
     // let app = app.fallback(handler_404);
-    let port = CONFIG.get().unwrap().port;
-    let addr = format!("0.0.0.0:{port}");
-    let listener = tokio::net::TcpListener::bind(addr)
-        .await
-        .unwrap();
-    println!("listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, ServiceExt::<Request>::into_make_service(app))
-        .await
-        .unwrap();
+    tokio::spawn(config::watch_config());
+    tokio::spawn(port_check::watch_ports());
+    tokio::spawn(shift_report::watch_shifts());
+    let port = cli.port.unwrap_or(config::get_config().port);
+    let addr = format!("{}:{port}", cli.bind);
+    let listener = match tokio::net::TcpListener::bind(&addr).await {
+        Ok(l) => l,
+        Err(e) => {
+            error!("bind {addr} fail, {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let app = ServiceExt::<Request>::into_make_service(app);
+    let tls = match config::get_config().tls.clone() {
+        None => {
+            info!("listening on http://{}", listener.local_addr().unwrap());
+            if let Err(e) = axum::serve(listener, app).await {
+                error!("server stopped, {e}");
+                return ExitCode::FAILURE;
+            }
+            return ExitCode::SUCCESS;
+        }
+        Some(tls) => tls,
+    };
+    let rustls_config = match tls::rustls_config(&tls).await {
+        Ok(c) => c,
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(redirect_port) = tls.redirect_port {
+        let addr = format!("{}:{redirect_port}", cli.bind);
+        let redirect_listener = match tokio::net::TcpListener::bind(&addr).await {
+            Ok(l) => l,
+            Err(e) => {
+                error!("bind {addr} fail, {e}");
+                return ExitCode::FAILURE;
+            }
+        };
+        info!("listening on http://{addr}, redirect to https");
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(redirect_listener, tls::redirect_app(port)).await {
+                error!("http redirect server stopped, {e}");
+            }
+        });
+    }
+    tokio::spawn(tls::watch_cert(rustls_config.clone(), tls));
+    info!("listening on https://{}", listener.local_addr().unwrap());
+    let listener = listener.into_std().unwrap();
+    if let Err(e) = axum_server::from_tcp_rustls(listener, rustls_config).serve(app).await {
+        error!("server stopped, {e}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
 }
@@ -1,7 +1,20 @@
+
 use askama::Template;
 use axum::http::StatusCode;
 use axum::response::{Html, IntoResponse, Response};
-use crate::analysis::db::{Rcell, Rdetail, Rpf, Rrecord};
+use crate::analysis::db::{FailRecord, RdayYield, Rpf, Rrecord, StationCell};
+use crate::analysis::compare::StationCompare;
+use crate::analysis::config::KioskPage;
+use crate::analysis::cross_line::CrossStation;
+use crate::analysis::data_quality::DbQuality;
+use crate::analysis::fail_class::Rcategory;
+use crate::analysis::fixture::RcommandLog;
+use crate::analysis::key_name::{Rheat, Rkey};
+use crate::analysis::maintenance::Rmaintenance;
+use crate::analysis::overview::LineTile;
+use crate::analysis::port_check::{Rbadge, Routage, RportBadge};
+use crate::analysis::port_config::Rcommand;
+use crate::analysis::yield_rate::YieldView;
 
 mod filters {
     pub fn zero2space<T: std::fmt::Display>(s: T) -> askama::Result<String> {
@@ -35,6 +48,32 @@ impl<T> IntoResponse for HtmlTemplate<T>
 #[derive(Template)]
 #[template(path = "homepage.html")]
 pub struct HomepageTemplate<'a> {
+    // the lines the visitor can view
+    pub tiles: Vec<LineTile>,
+    // "2024-01-04 DAY"
+    pub shift: String,
+    pub update_time: String,
+    pub user_name: String,
+    pub is_engineer: bool,
+    pub is_admin: bool,
+    // base.html
+    // all tem variant
+    pub tem_language: &'a str,
+    pub tem_colon: &'a str,
+    pub tem_quantity_of_pass_fail: &'a str,
+    pub tem_yield_of_shift: &'a str,
+    pub tem_fail_record_details: &'a str,
+    pub tem_query_400_records_of_cell: &'a str,
+    pub tem_query_sn_history: &'a str,
+    pub tem_maintenance_log: &'a str,
+    pub tem_key_name_of_bgibest: &'a str,
+    pub tem_port_config_of_terminal_server: &'a str,
+    pub tem_local_time: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "paths.html")]
+pub struct PathsTemplate<'a> {
     pub current_exe_path: &'a str,
     pub current_config_path: &'a str,
     pub current_db_path: &'a str,
@@ -61,34 +100,52 @@ pub struct HomepageTemplate<'a> {
     // base.html
     // all tem variant
     pub tem_language: &'a str,
-    pub tem_day: &'a str,
-    pub tem_night: &'a str,
-    pub tem_previous_day: &'a str,
-    pub tem_previous_shift: &'a str,
-    pub tem_today: &'a str,
-    pub tem_viewing_data: &'a str,
-    pub tem_colon: &'a str,
-    pub tem_home: &'a str,
     pub tem_quantity_of_pass_fail: &'a str,
     pub tem_yield_of_shift: &'a str,
     pub tem_fail_record_details: &'a str,
     pub tem_query_400_records_of_cell: &'a str,
     pub tem_query_sn_history: &'a str,
-    pub tem_query_sn_history_all: &'a str,
+    pub tem_maintenance_log: &'a str,
     pub tem_key_name_of_bgibest: &'a str,
     pub tem_port_config_of_terminal_server: &'a str,
-    pub tem_line_0: &'a str,
-    pub tem_line_1: &'a str,
-    pub tem_line_2: &'a str,
-    pub tem_line_3: &'a str,
-    pub tem_submit: &'a str,
-    pub tem_local_time: &'a str,
-    pub tem_sort_able: &'a str,
 }
 
 #[derive(Template)]
 #[template(path = "portconfig.html")]
 pub struct PortconfigTemplate<'a> {
+    // (host, note)
+    pub server: (String, String),
+    // ("DIAG", ports of the DIAG cells), "other" is the port not a cell, e.g. robot
+    pub port_groups: Vec<(&'a str, Vec<RportBadge>)>,
+    pub outages: Vec<Routage>,
+    pub command_groups: Vec<(&'a str, Vec<Rcommand>)>,
+    pub command_log: Vec<RcommandLog>,
+    // engineer can send the fixture command
+    pub can_send: bool,
+    pub is_admin: bool,
+    pub title: String,
+    pub line: String,
+    pub hostname: String,
+    pub update_time: String,
+    pub tem_local_time: &'a str,
+    pub tem_language: &'a str,
+    pub tem_quantity_of_pass_fail: &'a str,
+    pub tem_yield_of_shift: &'a str,
+    pub tem_fail_record_details: &'a str,
+    pub tem_query_400_records_of_cell: &'a str,
+    pub tem_query_sn_history: &'a str,
+    pub tem_maintenance_log: &'a str,
+    pub tem_key_name_of_bgibest: &'a str,
+    pub tem_port_config_of_terminal_server: &'a str,
+    pub tem_submit: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "console.html")]
+pub struct ConsoleTemplate<'a> {
+    pub name: String,
+    // (index, port, "selected")
+    pub ports: Vec<(String, String, &'a str)>,
     pub title: String,
     pub line: String,
     pub hostname: String,
@@ -100,18 +157,24 @@ pub struct PortconfigTemplate<'a> {
     pub tem_fail_record_details: &'a str,
     pub tem_query_400_records_of_cell: &'a str,
     pub tem_query_sn_history: &'a str,
-    pub tem_query_sn_history_all: &'a str,
+    pub tem_maintenance_log: &'a str,
     pub tem_key_name_of_bgibest: &'a str,
     pub tem_port_config_of_terminal_server: &'a str,
-    pub tem_line_1: &'a str,
-    pub tem_line_2: &'a str,
-    pub tem_line_3: &'a str,
-    pub tem_line_4: &'a str,
+    pub tem_submit: &'a str,
 }
 
 #[derive(Template)]
 #[template(path = "keyname.html")]
 pub struct KeynameTemplate<'a> {
+    pub keys: Vec<Rkey>,
+    pub heat: Vec<Rheat>,
+    // (cell, "selected")
+    pub cells: Vec<(&'a str, &'a str)>,
+    pub date_from: String,
+    pub date_to: String,
+    // the KEYPAD fail records in the window
+    pub fail_quantity: usize,
+    pub is_admin: bool,
     pub title: String,
     pub line: String,
     pub hostname: String,
@@ -123,18 +186,16 @@ pub struct KeynameTemplate<'a> {
     pub tem_fail_record_details: &'a str,
     pub tem_query_400_records_of_cell: &'a str,
     pub tem_query_sn_history: &'a str,
-    pub tem_query_sn_history_all: &'a str,
+    pub tem_maintenance_log: &'a str,
     pub tem_key_name_of_bgibest: &'a str,
     pub tem_port_config_of_terminal_server: &'a str,
-    pub tem_line_1: &'a str,
-    pub tem_line_2: &'a str,
-    pub tem_line_3: &'a str,
+    pub tem_submit: &'a str,
 }
 
 #[derive(Template)]
 #[template(path = "all_cell_record.html")]
 pub struct StationYieldTemplate<'a> {
-    pub station_yield: Vec<(&'a str, u16, u16, u16, u16, String)>,
+    pub station_yield: Vec<StationCell<'a>>,
     // base.html
     pub title: String,
     pub line: String,
@@ -142,27 +203,14 @@ pub struct StationYieldTemplate<'a> {
     pub update_time: String,
     // all tem variant
     pub tem_language: &'a str,
-    pub tem_day: &'a str,
-    pub tem_night: &'a str,
-    pub tem_previous_day: &'a str,
-    pub tem_previous_shift: &'a str,
-    pub tem_today: &'a str,
-    pub tem_viewing_data: &'a str,
-    pub tem_colon: &'a str,
-    pub tem_home: &'a str,
     pub tem_quantity_of_pass_fail: &'a str,
     pub tem_yield_of_shift: &'a str,
     pub tem_fail_record_details: &'a str,
     pub tem_query_400_records_of_cell: &'a str,
     pub tem_query_sn_history: &'a str,
-    pub tem_query_sn_history_all: &'a str,
+    pub tem_maintenance_log: &'a str,
     pub tem_key_name_of_bgibest: &'a str,
     pub tem_port_config_of_terminal_server: &'a str,
-    pub tem_line_0: &'a str,
-    pub tem_line_1: &'a str,
-    pub tem_line_2: &'a str,
-    pub tem_line_3: &'a str,
-    pub tem_submit: &'a str,
     pub tem_local_time: &'a str,
     pub tem_sort_able: &'a str,
 }
@@ -174,7 +222,25 @@ pub struct CellFstTemplate<'a> {
     pub query_count: u16,
     pub cell: &'a str,
     pub res_yield: (u16, u16, u16, u16),
-    pub record: Vec<Rcell>,
+    pub cell_yield: YieldView,
+    pub record: Vec<FailRecord>,
+    pub station: &'a str,
+    pub causes: &'a [&'a str],
+    // fails by category, see [[fail_rule]] of config.toml
+    pub category: Vec<Rcategory>,
+    // the fails tagged as fixture caused in the record
+    pub fixture_fails: usize,
+    pub maintenance: Vec<Rmaintenance>,
+    // None if the cell is not in the port config
+    pub port_badge: Option<Rbadge>,
+    pub outages: Vec<Routage>,
+    // the ports of the cell in the port config, and the commands of the station to send, empty if not engineer
+    pub ports: String,
+    pub commands: Vec<Rcommand>,
+    pub command_log: Vec<RcommandLog>,
+    pub actions: &'a [&'a str],
+    // default time of the maintenance form, html datetime-local "2024-01-04T20:48"
+    pub now: String,
     // base.html
     pub title: String,
     pub line: String,
@@ -182,26 +248,14 @@ pub struct CellFstTemplate<'a> {
     pub update_time: String,
     // all tem variant
     pub tem_language: &'a str,
-    pub tem_day: &'a str,
-    pub tem_night: &'a str,
-    pub tem_previous_day: &'a str,
-    pub tem_previous_shift: &'a str,
-    pub tem_today: &'a str,
-    pub tem_viewing_data: &'a str,
-    pub tem_colon: &'a str,
-    pub tem_home: &'a str,
     pub tem_quantity_of_pass_fail: &'a str,
     pub tem_yield_of_shift: &'a str,
     pub tem_fail_record_details: &'a str,
     pub tem_query_400_records_of_cell: &'a str,
     pub tem_query_sn_history: &'a str,
-    pub tem_query_sn_history_all: &'a str,
+    pub tem_maintenance_log: &'a str,
     pub tem_key_name_of_bgibest: &'a str,
     pub tem_port_config_of_terminal_server: &'a str,
-    pub tem_line_0: &'a str,
-    pub tem_line_1: &'a str,
-    pub tem_line_2: &'a str,
-    pub tem_line_3: &'a str,
     pub tem_submit: &'a str,
     pub tem_local_time: &'a str,
     pub tem_sort_able: &'a str,
@@ -213,7 +267,25 @@ pub struct CellBstTemplate<'a> {
     pub query_count: u16,
     pub cell: &'a str,
     pub res_yield: (u16, u16, u16, u16),
-    pub record: Vec<Rcell>,
+    pub cell_yield: YieldView,
+    pub record: Vec<FailRecord>,
+    pub station: &'a str,
+    pub causes: &'a [&'a str],
+    // fails by category, see [[fail_rule]] of config.toml
+    pub category: Vec<Rcategory>,
+    // the fails tagged as fixture caused in the record
+    pub fixture_fails: usize,
+    pub maintenance: Vec<Rmaintenance>,
+    // None if the cell is not in the port config
+    pub port_badge: Option<Rbadge>,
+    pub outages: Vec<Routage>,
+    // the ports of the cell in the port config, and the commands of the station to send, empty if not engineer
+    pub ports: String,
+    pub commands: Vec<Rcommand>,
+    pub command_log: Vec<RcommandLog>,
+    pub actions: &'a [&'a str],
+    // default time of the maintenance form, html datetime-local "2024-01-04T20:48"
+    pub now: String,
     // base.html
     pub title: String,
     pub line: String,
@@ -221,28 +293,16 @@ pub struct CellBstTemplate<'a> {
     pub update_time: String,
     // all tem variant
     pub tem_language: &'a str,
-    pub tem_day: &'a str,
-    pub tem_night: &'a str,
-    pub tem_previous_day: &'a str,
-    pub tem_previous_shift: &'a str,
-    pub tem_today: &'a str,
-    pub tem_viewing_data: &'a str,
-    pub tem_colon: &'a str,
-    pub tem_home: &'a str,
     pub tem_quantity_of_pass_fail: &'a str,
     pub tem_yield_of_shift: &'a str,
     pub tem_fail_record_details: &'a str,
     pub tem_query_400_records_of_cell: &'a str,
     pub tem_query_sn_history: &'a str,
-    pub tem_query_sn_history_all: &'a str,
+    pub tem_maintenance_log: &'a str,
     pub tem_key_name_of_bgibest: &'a str,
     pub tem_port_config_of_terminal_server: &'a str,
-    pub tem_line_0: &'a str,
-    pub tem_line_1: &'a str,
-    pub tem_line_2: &'a str,
-    pub tem_line_3: &'a str,
-    pub tem_submit: &'a str,
     pub tem_local_time: &'a str,
+    pub tem_submit: &'a str,
     pub tem_sort_able: &'a str,
 }
 
@@ -251,9 +311,12 @@ pub struct CellBstTemplate<'a> {
 pub struct DetailFstTemplate<'a> {
     pub data_day: &'a str,
     pub data_shift: &'a str,
-    pub lcdled: Vec<Rdetail>,
-    pub diag: Vec<Rdetail>,
-    pub keypad: Vec<Rdetail>,
+    pub lcdled: Vec<FailRecord>,
+    pub diag: Vec<FailRecord>,
+    pub keypad: Vec<FailRecord>,
+    pub causes: &'a [&'a str],
+    // fails by category, see [[fail_rule]] of config.toml
+    pub category: Vec<Rcategory>,
     // base.html
     pub title: String,
     pub line: String,
@@ -268,20 +331,14 @@ pub struct DetailFstTemplate<'a> {
     pub tem_today: &'a str,
     pub tem_viewing_data: &'a str,
     pub tem_colon: &'a str,
-    pub tem_home: &'a str,
     pub tem_quantity_of_pass_fail: &'a str,
     pub tem_yield_of_shift: &'a str,
     pub tem_fail_record_details: &'a str,
     pub tem_query_400_records_of_cell: &'a str,
     pub tem_query_sn_history: &'a str,
-    pub tem_query_sn_history_all: &'a str,
+    pub tem_maintenance_log: &'a str,
     pub tem_key_name_of_bgibest: &'a str,
     pub tem_port_config_of_terminal_server: &'a str,
-    pub tem_line_0: &'a str,
-    pub tem_line_1: &'a str,
-    pub tem_line_2: &'a str,
-    pub tem_line_3: &'a str,
-    pub tem_submit: &'a str,
     pub tem_local_time: &'a str,
     pub tem_sort_able: &'a str,
 }
@@ -291,7 +348,10 @@ pub struct DetailFstTemplate<'a> {
 pub struct DetailBstTemplate<'a> {
     pub data_day: &'a str,
     pub data_shift: &'a str,
-    pub bst: Vec<Rdetail>,
+    pub bst: Vec<FailRecord>,
+    pub causes: &'a [&'a str],
+    // fails by category, see [[fail_rule]] of config.toml
+    pub category: Vec<Rcategory>,
     // base.html
     pub title: String,
     pub line: String,
@@ -306,20 +366,14 @@ pub struct DetailBstTemplate<'a> {
     pub tem_today: &'a str,
     pub tem_viewing_data: &'a str,
     pub tem_colon: &'a str,
-    pub tem_home: &'a str,
     pub tem_quantity_of_pass_fail: &'a str,
     pub tem_yield_of_shift: &'a str,
     pub tem_fail_record_details: &'a str,
     pub tem_query_400_records_of_cell: &'a str,
     pub tem_query_sn_history: &'a str,
-    pub tem_query_sn_history_all: &'a str,
+    pub tem_maintenance_log: &'a str,
     pub tem_key_name_of_bgibest: &'a str,
     pub tem_port_config_of_terminal_server: &'a str,
-    pub tem_line_0: &'a str,
-    pub tem_line_1: &'a str,
-    pub tem_line_2: &'a str,
-    pub tem_line_3: &'a str,
-    pub tem_submit: &'a str,
     pub tem_local_time: &'a str,
     pub tem_sort_able: &'a str,
 }
@@ -335,26 +389,14 @@ pub struct SnRecordTemplate<'a> {
     pub update_time: String,
     // all tem variant
     pub tem_language: &'a str,
-    pub tem_day: &'a str,
-    pub tem_night: &'a str,
-    pub tem_previous_day: &'a str,
-    pub tem_previous_shift: &'a str,
-    pub tem_today: &'a str,
-    pub tem_viewing_data: &'a str,
-    pub tem_colon: &'a str,
-    pub tem_home: &'a str,
     pub tem_quantity_of_pass_fail: &'a str,
     pub tem_yield_of_shift: &'a str,
     pub tem_fail_record_details: &'a str,
     pub tem_query_400_records_of_cell: &'a str,
     pub tem_query_sn_history: &'a str,
-    pub tem_query_sn_history_all: &'a str,
+    pub tem_maintenance_log: &'a str,
     pub tem_key_name_of_bgibest: &'a str,
     pub tem_port_config_of_terminal_server: &'a str,
-    pub tem_line_0: &'a str,
-    pub tem_line_1: &'a str,
-    pub tem_line_2: &'a str,
-    pub tem_line_3: &'a str,
     pub tem_submit: &'a str,
     pub tem_local_time: &'a str,
     pub tem_sort_able: &'a str,
@@ -365,7 +407,7 @@ pub struct SnRecordTemplate<'a> {
 pub struct YiedlBstTemplate<'a> {
     pub data_day: &'a str,
     pub data_shift: &'a str,
-    pub bst: [(&'a str, u16, u16, u16, u16); 13],
+    pub bst: RdayYield<'a>,
     // base.html
     pub title: String,
     pub line: String,
@@ -380,22 +422,15 @@ pub struct YiedlBstTemplate<'a> {
     pub tem_today: &'a str,
     pub tem_viewing_data: &'a str,
     pub tem_colon: &'a str,
-    pub tem_home: &'a str,
     pub tem_quantity_of_pass_fail: &'a str,
     pub tem_yield_of_shift: &'a str,
     pub tem_fail_record_details: &'a str,
     pub tem_query_400_records_of_cell: &'a str,
     pub tem_query_sn_history: &'a str,
-    pub tem_query_sn_history_all: &'a str,
+    pub tem_maintenance_log: &'a str,
     pub tem_key_name_of_bgibest: &'a str,
     pub tem_port_config_of_terminal_server: &'a str,
-    pub tem_line_0: &'a str,
-    pub tem_line_1: &'a str,
-    pub tem_line_2: &'a str,
-    pub tem_line_3: &'a str,
-    pub tem_submit: &'a str,
     pub tem_local_time: &'a str,
-    pub tem_sort_able: &'a str,
 }
 
 
@@ -404,9 +439,9 @@ pub struct YiedlBstTemplate<'a> {
 pub struct YiedlFstTemplate<'a> {
     pub data_day: &'a str,
     pub data_shift: &'a str,
-    pub lcdled: [(&'a str, u16, u16, u16, u16); 13],
-    pub diag: [(&'a str, u16, u16, u16, u16); 13],
-    pub keypad: [(&'a str, u16, u16, u16, u16); 13],
+    pub lcdled: RdayYield<'a>,
+    pub diag: RdayYield<'a>,
+    pub keypad: RdayYield<'a>,
     // base.html
     pub title: String,
     pub line: String,
@@ -421,22 +456,15 @@ pub struct YiedlFstTemplate<'a> {
     pub tem_today: &'a str,
     pub tem_viewing_data: &'a str,
     pub tem_colon: &'a str,
-    pub tem_home: &'a str,
     pub tem_quantity_of_pass_fail: &'a str,
     pub tem_yield_of_shift: &'a str,
     pub tem_fail_record_details: &'a str,
     pub tem_query_400_records_of_cell: &'a str,
     pub tem_query_sn_history: &'a str,
-    pub tem_query_sn_history_all: &'a str,
+    pub tem_maintenance_log: &'a str,
     pub tem_key_name_of_bgibest: &'a str,
     pub tem_port_config_of_terminal_server: &'a str,
-    pub tem_line_0: &'a str,
-    pub tem_line_1: &'a str,
-    pub tem_line_2: &'a str,
-    pub tem_line_3: &'a str,
-    pub tem_submit: &'a str,
     pub tem_local_time: &'a str,
-    pub tem_sort_able: &'a str,
 }
 
 
@@ -460,22 +488,16 @@ pub struct PfBstTemplate<'a> {
     pub tem_today: &'a str,
     pub tem_viewing_data: &'a str,
     pub tem_colon: &'a str,
-    pub tem_home: &'a str,
     pub tem_quantity_of_pass_fail: &'a str,
     pub tem_yield_of_shift: &'a str,
     pub tem_fail_record_details: &'a str,
     pub tem_query_400_records_of_cell: &'a str,
     pub tem_query_sn_history: &'a str,
-    pub tem_query_sn_history_all: &'a str,
+    pub tem_maintenance_log: &'a str,
     pub tem_key_name_of_bgibest: &'a str,
     pub tem_port_config_of_terminal_server: &'a str,
-    pub tem_line_0: &'a str,
-    pub tem_line_1: &'a str,
-    pub tem_line_2: &'a str,
-    pub tem_line_3: &'a str,
     pub tem_submit: &'a str,
     pub tem_local_time: &'a str,
-    pub tem_sort_able: &'a str,
 }
 
 
@@ -501,20 +523,172 @@ pub struct PfFstTemplate<'a> {
     pub tem_today: &'a str,
     pub tem_viewing_data: &'a str,
     pub tem_colon: &'a str,
-    pub tem_home: &'a str,
     pub tem_quantity_of_pass_fail: &'a str,
     pub tem_yield_of_shift: &'a str,
     pub tem_fail_record_details: &'a str,
     pub tem_query_400_records_of_cell: &'a str,
     pub tem_query_sn_history: &'a str,
-    pub tem_query_sn_history_all: &'a str,
+    pub tem_maintenance_log: &'a str,
+    pub tem_key_name_of_bgibest: &'a str,
+    pub tem_port_config_of_terminal_server: &'a str,
+    pub tem_submit: &'a str,
+    pub tem_local_time: &'a str,
+}
+#[derive(Template)]
+#[template(path = "maintenance.html")]
+pub struct MaintenanceTemplate<'a> {
+    pub query_count: u16,
+    // ("DIAG", "selected")
+    pub stations: Vec<(&'a str, &'a str)>,
+    pub date_from: String,
+    pub date_to: String,
+    pub record: Vec<Rmaintenance>,
+    // base.html
+    pub title: String,
+    pub line: String,
+    pub hostname: String,
+    pub update_time: String,
+    // all tem variant
+    pub tem_language: &'a str,
+    pub tem_viewing_data: &'a str,
+    pub tem_colon: &'a str,
+    pub tem_quantity_of_pass_fail: &'a str,
+    pub tem_yield_of_shift: &'a str,
+    pub tem_fail_record_details: &'a str,
+    pub tem_query_400_records_of_cell: &'a str,
+    pub tem_query_sn_history: &'a str,
+    pub tem_maintenance_log: &'a str,
     pub tem_key_name_of_bgibest: &'a str,
     pub tem_port_config_of_terminal_server: &'a str,
-    pub tem_line_0: &'a str,
-    pub tem_line_1: &'a str,
-    pub tem_line_2: &'a str,
-    pub tem_line_3: &'a str,
     pub tem_submit: &'a str,
     pub tem_local_time: &'a str,
     pub tem_sort_able: &'a str,
-}
\ No newline at end of file
+}
+
+#[derive(Template)]
+#[template(path = "compare.html")]
+pub struct CompareTemplate<'a> {
+    // the form of the period a and b, shift is ("DAY", "selected")
+    pub a_date: String,
+    pub a_shift: Vec<(&'a str, &'a str)>,
+    pub a_to: String,
+    pub b_date: String,
+    pub b_shift: Vec<(&'a str, &'a str)>,
+    pub b_to: String,
+    pub a_label: String,
+    pub b_label: String,
+    pub stations: Vec<StationCompare>,
+    pub errors: Vec<String>,
+    // the fail messages are for engineer
+    pub show_messages: bool,
+    // base.html
+    pub title: String,
+    pub line: String,
+    pub hostname: String,
+    pub update_time: String,
+    // all tem variant
+    pub tem_language: &'a str,
+    pub tem_viewing_data: &'a str,
+    pub tem_colon: &'a str,
+    pub tem_quantity_of_pass_fail: &'a str,
+    pub tem_yield_of_shift: &'a str,
+    pub tem_fail_record_details: &'a str,
+    pub tem_query_400_records_of_cell: &'a str,
+    pub tem_query_sn_history: &'a str,
+    pub tem_maintenance_log: &'a str,
+    pub tem_key_name_of_bgibest: &'a str,
+    pub tem_port_config_of_terminal_server: &'a str,
+    pub tem_submit: &'a str,
+    pub tem_local_time: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "cross_line.html")]
+pub struct CrossLineTemplate<'a> {
+    // ("LCDLED", "selected")
+    pub stations: Vec<(&'a str, &'a str)>,
+    pub date: String,
+    // ("DAY", "selected")
+    pub shifts: Vec<(&'a str, &'a str)>,
+    pub date_to: String,
+    pub label: String,
+    pub station: String,
+    pub cross: CrossStation,
+    // the lines of the family the visitor can not view
+    pub hidden_lines: Vec<&'a str>,
+    // the fail messages are for engineer
+    pub show_messages: bool,
+    // base.html
+    pub title: String,
+    pub line: String,
+    pub hostname: String,
+    pub update_time: String,
+    // all tem variant
+    pub tem_language: &'a str,
+    pub tem_viewing_data: &'a str,
+    pub tem_colon: &'a str,
+    pub tem_quantity_of_pass_fail: &'a str,
+    pub tem_yield_of_shift: &'a str,
+    pub tem_fail_record_details: &'a str,
+    pub tem_query_400_records_of_cell: &'a str,
+    pub tem_query_sn_history: &'a str,
+    pub tem_maintenance_log: &'a str,
+    pub tem_key_name_of_bgibest: &'a str,
+    pub tem_port_config_of_terminal_server: &'a str,
+    pub tem_submit: &'a str,
+    pub tem_local_time: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "data_quality.html")]
+pub struct DataQualityTemplate<'a> {
+    pub days: i64,
+    pub max_days: i64,
+    // "2024-01-04 07:30:00"
+    pub since: String,
+    pub dbs: Vec<DbQuality>,
+    // base.html
+    pub title: String,
+    pub line: String,
+    pub hostname: String,
+    pub update_time: String,
+    // all tem variant
+    pub tem_language: &'a str,
+    pub tem_viewing_data: &'a str,
+    pub tem_colon: &'a str,
+    pub tem_quantity_of_pass_fail: &'a str,
+    pub tem_yield_of_shift: &'a str,
+    pub tem_fail_record_details: &'a str,
+    pub tem_query_400_records_of_cell: &'a str,
+    pub tem_query_sn_history: &'a str,
+    pub tem_maintenance_log: &'a str,
+    pub tem_key_name_of_bgibest: &'a str,
+    pub tem_port_config_of_terminal_server: &'a str,
+    pub tem_submit: &'a str,
+    pub tem_local_time: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "kiosk.html")]
+pub struct KioskTemplate<'a> {
+    pub title: String,
+    // the secs of every page is resolved with kiosk.interval_secs
+    pub pages: Vec<KioskPage>,
+    pub refresh_secs: u64,
+    pub retry_secs: u64,
+    pub tem_language: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "login.html")]
+pub struct LoginTemplate<'a> {
+    pub title: String,
+    pub next: String,
+    pub name: String,
+    pub error: &'a str,
+    pub tem_language: &'a str,
+    pub tem_home: &'a str,
+    pub tem_username: &'a str,
+    pub tem_password: &'a str,
+    pub tem_login: &'a str,
+}
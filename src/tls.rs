@@ -0,0 +1,84 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use axum::extract::Host;
+use axum::response::{IntoResponse, Redirect};
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use http::Uri;
+use crate::analysis::config::Tls;
+use tracing::{error, info};
+
+// how often watch_cert() check the modified time of the certificate and key
+static WATCH_INTERVAL: Duration = Duration::from_secs(60);
+
+pub async fn rustls_config(tls: &Tls) -> Result<RustlsConfig, String> {
+    RustlsConfig::from_pem_file(&tls.cert, &tls.key)
+        .await
+        .map_err(|e| format!("load certificate {:?} and key {:?} fail, {e}", tls.cert, tls.key))
+}
+
+fn modified_time(cert: &PathBuf, key: &PathBuf) -> (Option<SystemTime>, Option<SystemTime>) {
+    let m = |p: &PathBuf| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+    (m(cert), m(key))
+}
+
+/// re-read the certificate and key when they are renewed, the running ones are kept if the new files are broken
+pub async fn watch_cert(rustls_config: RustlsConfig, tls: Tls) {
+    let mut last = modified_time(&tls.cert, &tls.key);
+    loop {
+        tokio::time::sleep(WATCH_INTERVAL).await;
+        let now = modified_time(&tls.cert, &tls.key);
+        if now == last {
+            continue;
+        }
+        // the key and the certificate may be written one after another, retry at next check if not match
+        match rustls_config.reload_from_pem_file(&tls.cert, &tls.key).await {
+            Ok(_) => {
+                last = now;
+                info!("certificate {:?} changed, reload success", tls.cert);
+            }
+            Err(e) => error!("certificate {:?} changed, reload fail, keep the old one: {e}", tls.cert),
+        }
+    }
+}
+
+/// "fxvapp16:8080" -> "fxvapp16", "[::1]:8080" -> "[::1]"
+fn strip_port(host: &str) -> &str {
+    if host.starts_with('[') {
+        return match host.find(']') {
+            Some(i) => &host[..=i],
+            None => host,
+        };
+    }
+    match host.rsplit_once(':') {
+        Some((h, _)) => h,
+        None => host,
+    }
+}
+
+/// every plain http request go to the same path of https
+pub fn redirect_app(https_port: u16) -> Router {
+    Router::new().fallback(move |Host(host): Host, uri: Uri| async move {
+        let path = uri.path_and_query().map(|x| x.as_str()).unwrap_or("/");
+        let host = strip_port(&host);
+        let url = if https_port == 443 {
+            format!("https://{host}{path}")
+        } else {
+            format!("https://{host}:{https_port}{path}")
+        };
+        Redirect::temporary(&url).into_response()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_port() {
+        assert_eq!(strip_port("fxvapp16:8080"), "fxvapp16");
+        assert_eq!(strip_port("fxvapp16"), "fxvapp16");
+        assert_eq!(strip_port("[::1]:8080"), "[::1]");
+        assert_eq!(strip_port("10.1.1.6"), "10.1.1.6");
+    }
+}
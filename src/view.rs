@@ -1,32 +1,208 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::string::ToString;
-use crate::analysis::config::CONFIG;
-use crate::analysis::{db, mytime};
-use crate::analysis::lang_tran::LANG_MAP;
+use std::time::Duration;
+use crate::analysis::config;
+use crate::analysis::{compare, cross_line, data_quality, db, fail_class, fail_tag, fixture, key_name, lang_tran, maintenance, mytime, overview, port_check, port_config,
+                     query_cache,
+                     shift_report, yield_rate};
+use crate::analysis::user::{Role, User};
 use crate::template;
 
-use http::HeaderMap;
+use http::{header, HeaderMap, StatusCode, Uri};
 use axum::{extract, Json};
+use axum::extract::Request;
+use axum::middleware::Next;
 use axum::response::{IntoResponse, Redirect, Response};
 use serde::{Deserialize, Serialize};
+use once_cell::sync::Lazy;
 use regex::Regex;
-use template::{CellBstTemplate, CellFstTemplate, StationYieldTemplate, HomepageTemplate, HtmlTemplate, KeynameTemplate,
+use template::{CellBstTemplate, CellFstTemplate, StationYieldTemplate, HomepageTemplate, PathsTemplate, HtmlTemplate, KeynameTemplate,
                PortconfigTemplate, DetailBstTemplate, DetailFstTemplate, YiedlBstTemplate,
-               YiedlFstTemplate, PfBstTemplate, PfFstTemplate, SnRecordTemplate};
+               YiedlFstTemplate, PfBstTemplate, PfFstTemplate, SnRecordTemplate, MaintenanceTemplate,
+               CompareTemplate, CrossLineTemplate, DataQualityTemplate, KioskTemplate};
+use tracing::{debug, error, info};
 
 
-const LANG_CODE: [&str; 3] = ["en-US", "zh-CN", "vi-VN"];
 const LINES: [&str; 4] = ["bst1", "bst2", "fst1", "fst2"];
 static DAY: &str = "DAY";
 static NIGHT: &str = "NIGHT";
 
+static LANG_COOKIE: &str = "lang";
+
+// the year of a date typed in a form, the db of the lines is never out of it
+static FORM_YEARS: std::ops::RangeInclusive<i32> = 2000..=2100;
+
+// a part of the ETag, the page of a new build may differ with the same data
+static STARTED: Lazy<i64> = Lazy::new(mytime::now_ts);
+
 trait QueryParaValid {
     fn valid(&self) -> bool; //todo, use serde ?
 }
 
-pub async fn homepage() -> impl IntoResponse {
-    let config = CONFIG.get().unwrap();
-    let lang_map = LANG_MAP.get().unwrap();
-    let lang_map = lang_map.get("en-US").unwrap();
+/// value of the cookie in request header "Cookie: lang=vi-VN; other=1"
+pub fn cookie_value(header_map: &HeaderMap, name: &str) -> Option<String> {
+    for cookie in header_map.get_all(header::COOKIE) {
+        let cookie = match cookie.to_str() {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+        for pair in cookie.split(';') {
+            if let Some((k, v)) = pair.trim().split_once('=') {
+                if k == name {
+                    return Some(v.to_string());
+                }
+            }
+        }
+    }
+    None
+}
+
+/// language by the remembered cookie, then Accept-Language, then en-US
+pub fn negotiate_lang(header_map: &HeaderMap) -> String {
+    let cookie_lang = cookie_value(header_map, LANG_COOKIE);
+    let accept_language = header_map.get(header::ACCEPT_LANGUAGE).and_then(|x| x.to_str().ok());
+    lang_tran::negotiate(cookie_lang.as_deref(), accept_language)
+}
+
+/// replace the first segment of the path with lang, keep the query.
+/// "/fr-FR/fst1/pf_data?shift=DAY" -> "/en-US/fst1/pf_data?shift=DAY"
+fn replace_lang_of_uri(uri: &Uri, lang: &str) -> String {
+    let path = uri.path().trim_start_matches('/');
+    let rest = match path.split_once('/') {
+        Some((_, rest)) => format!("/{rest}"),
+        None => "".to_string(),
+    };
+    match uri.query() {
+        Some(q) => format!("/{lang}{rest}?{q}"),
+        None => format!("/{lang}{rest}"),
+    }
+}
+
+/// middleware of /:lang/:line/..., an unknown language code redirect to the negotiated one
+pub async fn lang_redirect(request: Request, next: Next) -> Response {
+    let code = request.uri().path().trim_start_matches('/').split('/').next().unwrap_or("");
+    if lang_tran::has_lang(code) {
+        return next.run(request).await;
+    }
+    let lang = match lang_tran::match_lang(code) {
+        Some(lang) => lang,
+        None => negotiate_lang(request.headers()),
+    };
+    let url = replace_lang_of_uri(request.uri(), &lang);
+    debug!("unknown language {code}, redirect to: {url}");
+    Redirect::to(&url).into_response()
+}
+
+/// only the local path, avoid redirect to other site by "//example.com"
+pub fn local_path(next: Option<String>) -> String {
+    match next {
+        Some(next) if next.starts_with('/') && !next.starts_with("//") && !next.starts_with("/\\") => next,
+        _ => "/".to_string(),
+    }
+}
+
+/// weak ETag of a page of the shift, the lang and query are in the uri. a closed shift never change, the current
+/// shift change with the latest id of every db of the line. the reload of config.toml or the catalogs and the fail
+/// tags change it too
+fn shift_etag(uri: &Uri, line: &str, end_ts: i64, with_tags: bool) -> String {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    (uri.to_string(), *STARTED, config::generation(), lang_tran::generation()).hash(&mut hasher);
+    if !query_cache::is_closed(end_ts) {
+        let stations = db::stations_of(line);
+        for station in stations {
+            db::last_id(line, station).ok().flatten().hash(&mut hasher);
+        }
+    }
+    if with_tags {
+        fail_tag::version(line).ok().hash(&mut hasher);
+    }
+    format!("W/\"{:016x}\"", hasher.finish())
+}
+
+/// 304 when the page of the browser is the same, by If-None-Match
+fn not_modified(header_map: &HeaderMap, etag: &str) -> Option<Response> {
+    let matched = header_map.get_all(header::IF_NONE_MATCH).iter()
+        .filter_map(|x| x.to_str().ok())
+        .any(|x| x.split(',').any(|t| t.trim() == etag || t.trim() == "*"));
+    if matched {
+        Some((StatusCode::NOT_MODIFIED, [(header::ETAG, etag.to_string())]).into_response())
+    } else {
+        None
+    }
+}
+
+/// the browser should check the ETag before using the cached page
+fn with_etag(etag: &str, mut response: Response) -> Response {
+    if response.status() == StatusCode::OK {
+        if let Ok(v) = etag.parse() {
+            response.headers_mut().insert(header::ETAG, v);
+        }
+        response.headers_mut().insert(header::CACHE_CONTROL, header::HeaderValue::from_static("no-cache"));
+    }
+    response
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetLangParams {
+    next: Option<String>,
+}
+
+/// the language switcher, remember the language in cookie and go back to the same page
+pub async fn set_lang(extract::Path(lang): extract::Path<String>,
+                      extract::Query(query_params): extract::Query<SetLangParams>,
+                      header_map: HeaderMap) -> Response {
+    let lang = match lang_tran::match_lang(&lang) {
+        Some(lang) => lang,
+        None => negotiate_lang(&header_map),
+    };
+    let next = local_path(query_params.next);
+    let url = match next.parse::<Uri>() {
+        Ok(uri) if uri.path() != "/" => replace_lang_of_uri(&uri, &lang),
+        _ => "/".to_string(),
+    };
+    let cookie = format!("{LANG_COOKIE}={lang}; Path=/; Max-Age=31536000; SameSite=Lax");
+    ([(header::SET_COOKIE, cookie)], Redirect::to(&url)).into_response()
+}
+
+/// one tile of every line the visitor can view, the current shift
+pub async fn homepage(header_map: HeaderMap, extract::Extension(user): extract::Extension<User>) -> impl IntoResponse {
+    let lang = negotiate_lang(&header_map);
+    let lang_map = lang_tran::get_lang_map();
+    let lang_map = lang_map.get(lang.as_str()).unwrap();
+    let tiles = LINES.into_iter().filter(|x| user.can_view_line(x)).map(overview::line_tile).collect();
+    let current = mytime::current_shift();
+    let tpl = HomepageTemplate {
+        tiles,
+        shift: format!("{} {}", current.date(), current.shift()),
+        update_time: mytime::now_vn(),
+        user_name: if user.id == 0 { "anonymous".to_string() } else { user.name.to_owned() },
+        is_engineer: user.role >= Role::Engineer,
+        is_admin: user.role >= Role::Admin,
+        // all tem variant
+        tem_language: lang_map.get("tem_language").unwrap(),
+        tem_colon: lang_map.get("tem_colon").unwrap(),
+        tem_quantity_of_pass_fail: lang_map.get("tem_quantity_of_pass_fail").unwrap(),
+        tem_yield_of_shift: lang_map.get("tem_yield_of_shift").unwrap(),
+        tem_fail_record_details: lang_map.get("tem_fail_record_details").unwrap(),
+        tem_query_400_records_of_cell: lang_map.get("tem_query_400_records_of_cell").unwrap(),
+        tem_query_sn_history: lang_map.get("tem_query_sn_history").unwrap(),
+        tem_maintenance_log: lang_map.get("tem_maintenance_log").unwrap(),
+        tem_key_name_of_bgibest: lang_map.get("tem_key_name_of_bgibest").unwrap(),
+        tem_port_config_of_terminal_server: lang_map
+            .get("tem_port_config_of_terminal_server")
+            .unwrap(),
+        tem_local_time: lang_map.get("tem_local_time").unwrap(),
+    };
+    HtmlTemplate(tpl).into_response()
+}
+
+/// the exe, config and db paths of config.toml, for the admin to check a wrong path
+pub async fn db_paths(header_map: HeaderMap) -> impl IntoResponse {
+    let config = config::get_config();
+    let lang = negotiate_lang(&header_map);
+    let lang_map = lang_tran::get_lang_map();
+    let lang_map = lang_map.get(lang.as_str()).unwrap();
     let bst1_db_path_exist = if config.bst1.bst_db.exists() { "" } else { " (not exist !)" };
     let bst2_db_path_exist = if config.bst2.bst_db.exists() { "" } else { " (not exist !)" };
     let fst1_lcd_db_path_exist = if config.fst1.lcd_db.exists() { "" } else { " (not exist !)" };
@@ -35,7 +211,7 @@ pub async fn homepage() -> impl IntoResponse {
     let fst2_lcd_db_path_exist = if config.fst2.lcd_db.exists() { "" } else { " (not exist !)" };
     let fst2_diag_db_path_exist = if config.fst2.diag_db.exists() { "" } else { " (not exist !)" };
     let fst2_key_db_path_exist = if config.fst2.key_db.exists() { "" } else { " (not exist !)" };
-    let tpl = HomepageTemplate {
+    let tpl = PathsTemplate {
         current_exe_path: config.current_exe_path.to_str().unwrap(),
         current_config_path: config.current_config_path.to_str().unwrap(),
         current_db_path: config.current_db_path.to_str().unwrap(),
@@ -62,33 +238,18 @@ pub async fn homepage() -> impl IntoResponse {
         // base.html
         // all tem variant
         tem_language: lang_map.get("tem_language").unwrap(),
-        tem_day: lang_map.get("tem_day").unwrap(),
-        tem_night: lang_map.get("tem_night").unwrap(),
-        tem_previous_day: lang_map.get("tem_previous_day").unwrap(),
-        tem_previous_shift: lang_map.get("tem_previous_shift").unwrap(),
-        tem_today: lang_map.get("tem_today").unwrap(),
-        tem_viewing_data: lang_map.get("tem_viewing_data").unwrap(),
-        tem_colon: lang_map.get("tem_colon").unwrap(),
-        tem_home: lang_map.get("tem_home").unwrap(),
         tem_quantity_of_pass_fail: lang_map.get("tem_quantity_of_pass_fail").unwrap(),
         tem_yield_of_shift: lang_map.get("tem_yield_of_shift").unwrap(),
         tem_fail_record_details: lang_map.get("tem_fail_record_details").unwrap(),
         tem_query_400_records_of_cell: lang_map.get("tem_query_400_records_of_cell").unwrap(),
         tem_query_sn_history: lang_map.get("tem_query_sn_history").unwrap(),
-        tem_query_sn_history_all: lang_map.get("tem_query_sn_history_all").unwrap(),
+        tem_maintenance_log: lang_map.get("tem_maintenance_log").unwrap(),
         tem_key_name_of_bgibest: lang_map.get("tem_key_name_of_bgibest").unwrap(),
         tem_port_config_of_terminal_server: lang_map
             .get("tem_port_config_of_terminal_server")
             .unwrap(),
-        tem_line_0: lang_map.get("tem_line_0").unwrap(),
-        tem_line_1: lang_map.get("tem_line_1").unwrap(),
-        tem_line_2: lang_map.get("tem_line_2").unwrap(),
-        tem_line_3: lang_map.get("tem_line_3").unwrap(),
-        tem_submit: lang_map.get("tem_submit").unwrap(),
-        tem_local_time: lang_map.get("tem_local_time").unwrap(),
-        tem_sort_able: lang_map.get("tem_sort_able").unwrap(),
     };
-    HtmlTemplate(tpl)
+    HtmlTemplate(tpl).into_response()
 }
 
 pub async fn line_page(extract::Path((lang, line)): extract::Path<(String, String)>) -> impl IntoResponse {
@@ -96,15 +257,51 @@ pub async fn line_page(extract::Path((lang, line)): extract::Path<(String, Strin
     Redirect::to(&url).into_response()
 }
 
-pub async fn portconfig(extract::Path((lang, line)): extract::Path<(String, String)>) -> impl IntoResponse {
-    assert!(LANG_CODE.contains(&lang.as_str()));
-    let config = CONFIG.get().unwrap();
+pub async fn portconfig(
+    extract::Path((lang, line)): extract::Path<(String, String)>,
+    extract::Extension(user): extract::Extension<User>,
+) -> Response {
+    assert!(lang_tran::has_lang(&lang));
+    assert!(LINES.contains(&line.as_str()));
+    let server = port_config::server_of(&line);
+    let ports = port_config::ports_of(&line);
+    let commands = port_config::commands();
+    let outages = port_check::outages(&line, "", 50);
+    let command_log = fixture::logs(&line, "", 20);
+    let (server, ports, commands, outages, command_log) = match (server, ports, commands, outages, command_log) {
+        (Ok(s), Ok(p), Ok(c), Ok(o), Ok(l)) => (s.unwrap_or_default(), p, c, o, l),
+        (Err(e), ..) | (_, Err(e), ..) | (_, _, Err(e), ..) | (_, _, _, Err(e), _) | (.., Err(e)) => {
+            error!("read port config fail, {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    };
+    let stations = db::stations_of(&line);
+    let mut badges = port_check::badges_of(&line);
+    let mut port_groups: Vec<(&str, Vec<_>)> = stations.iter().map(|x| (*x, Vec::new())).collect();
+    port_groups.push(("other", Vec::new()));
+    for (name, ports) in ports {
+        let station = db::cell2station(&name).filter(|x| stations.contains(x)).unwrap_or("other");
+        let badge = badges.remove(&name).unwrap_or_else(port_check::unknown_badge);
+        port_groups.iter_mut().find(|x| x.0 == station).unwrap().1.push((name, ports, badge));
+    }
+    port_groups.retain(|x| !x.1.is_empty());
+    let command_groups = stations.iter()
+        .map(|station| (*station, commands.iter().filter(|x| x.0 == *station).cloned().collect()))
+        .collect();
+    let config = config::get_config();
     let hostname = config.get_hostname(&line).unwrap();
     let hostname = hostname.to_owned();
-    let lang_map = LANG_MAP.get().unwrap();
+    let lang_map = lang_tran::get_lang_map();
     let lang_map = lang_map.get(lang.as_str()).unwrap();
-    let title = "Port Config".to_string();
+    let title = lang_map.get("tem_title_port_config").unwrap().to_owned();
     let tpl = PortconfigTemplate {
+        server,
+        port_groups,
+        outages,
+        command_groups,
+        command_log,
+        can_send: user.role >= Role::Engineer,
+        is_admin: user.role >= Role::Admin,
         title,
         line,
         hostname,
@@ -116,32 +313,73 @@ pub async fn portconfig(extract::Path((lang, line)): extract::Path<(String, Stri
         tem_fail_record_details: lang_map.get("tem_fail_record_details").unwrap(),
         tem_query_400_records_of_cell: lang_map.get("tem_query_400_records_of_cell").unwrap(),
         tem_query_sn_history: lang_map.get("tem_query_sn_history").unwrap(),
-        tem_query_sn_history_all: lang_map.get("tem_query_sn_history_all").unwrap(),
+        tem_maintenance_log: lang_map.get("tem_maintenance_log").unwrap(),
         tem_key_name_of_bgibest: lang_map.get("tem_key_name_of_bgibest").unwrap(),
         tem_port_config_of_terminal_server: lang_map
             .get("tem_port_config_of_terminal_server")
             .unwrap(),
-        tem_line_1: lang_map.get("tem_line_1").unwrap(),
-        tem_line_2: lang_map.get("tem_line_2").unwrap(),
-        tem_line_3: lang_map.get("tem_line_3").unwrap(),
-        tem_line_4: lang_map.get("tem_line_3").unwrap(),
+        tem_submit: lang_map.get("tem_submit").unwrap(),
     };
-    HtmlTemplate(tpl)
+    HtmlTemplate(tpl).into_response()
 }
 
-pub async fn keyname(extract::Path((lang, line)): extract::Path<(String, String)>) -> impl IntoResponse {
-    assert!(LANG_CODE.contains(&lang.as_str()));
-    let config = CONFIG.get().unwrap();
+#[derive(Debug, Deserialize)]
+pub struct KeynameParams {
+    cell: Option<String>,
+    date_from: Option<String>,
+    date_to: Option<String>,
+}
+
+/// the key names, and the heatmap of the KEYPAD fails by key of a cell, the last 7 days by default
+pub async fn keyname(
+    extract::Path((lang, line)): extract::Path<(String, String)>,
+    extract::Query(query_params): extract::Query<KeynameParams>,
+    extract::Extension(user): extract::Extension<User>,
+) -> Response {
+    assert!(lang_tran::has_lang(&lang));
+    assert!(LINES.contains(&line.as_str()));
+    let keys = match key_name::keys_of(&line) {
+        Ok(k) => k,
+        Err(e) => {
+            error!("read key name fail, {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    };
+    let config = config::get_config();
     let hostname = config.get_hostname(&line).unwrap();
     let hostname = hostname.to_owned();
-    let lang_map = LANG_MAP.get().unwrap();
+    let update_time = mytime::now_vn();
+    let cells = if line.contains("bst") { vec![] } else { db::get_cells("KEYPAD") };
+    let cell = query_params.cell.filter(|x| cells.contains(&x.as_str())).unwrap_or_default();
+    let cells = cells.into_iter().map(|x| (x, if x == cell { "selected" } else { "" })).collect();
+    let (date_from, date_to, start, end) = match date_window(query_params.date_from, query_params.date_to, 7) {
+        Ok(x) => x,
+        Err(e) => { return (StatusCode::BAD_REQUEST, e).into_response(); }
+    };
+    let fails = match db::keypad_fails(&line, &cell, start, end) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("read KEYPAD fails of {line} fail, {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    };
+    let counts = key_name::count_fails(&keys, fails.iter().map(|x| (x.1.as_str(), x.2.as_str())));
+    let heat = key_name::heat_of(keys.clone(), &counts);
+    let lang_map = lang_tran::get_lang_map();
     let lang_map = lang_map.get(lang.as_str()).unwrap();
-    let title = "Key Name".to_string();
+    let title = lang_map.get("tem_title_key_name").unwrap().to_owned();
     let tpl = KeynameTemplate {
+        keys,
+        heat,
+        cells,
+        date_from,
+        date_to,
+        fail_quantity: fails.len(),
+        is_admin: user.role >= Role::Admin,
         title,
         line,
         hostname,
-        update_time: mytime::now_vn(),
+        update_time,
         tem_local_time: lang_map.get("tem_local_time").unwrap(),
         tem_language: lang_map.get("tem_language").unwrap(),
         tem_quantity_of_pass_fail: lang_map.get("tem_quantity_of_pass_fail").unwrap(),
@@ -149,16 +387,14 @@ pub async fn keyname(extract::Path((lang, line)): extract::Path<(String, String)
         tem_fail_record_details: lang_map.get("tem_fail_record_details").unwrap(),
         tem_query_400_records_of_cell: lang_map.get("tem_query_400_records_of_cell").unwrap(),
         tem_query_sn_history: lang_map.get("tem_query_sn_history").unwrap(),
-        tem_query_sn_history_all: lang_map.get("tem_query_sn_history_all").unwrap(),
+        tem_maintenance_log: lang_map.get("tem_maintenance_log").unwrap(),
         tem_key_name_of_bgibest: lang_map.get("tem_key_name_of_bgibest").unwrap(),
         tem_port_config_of_terminal_server: lang_map
             .get("tem_port_config_of_terminal_server")
             .unwrap(),
-        tem_line_1: lang_map.get("tem_line_1").unwrap(),
-        tem_line_2: lang_map.get("tem_line_2").unwrap(),
-        tem_line_3: lang_map.get("tem_line_3").unwrap(),
+        tem_submit: lang_map.get("tem_submit").unwrap(),
     };
-    HtmlTemplate(tpl)
+    HtmlTemplate(tpl).into_response()
 }
 
 #[derive(Debug, Deserialize)]
@@ -180,26 +416,70 @@ pub async fn query_cell(
     extract::Path((lang, line)): extract::Path<(String, String)>,
     extract::Query(query_params): extract::Query<QueryCellParams>,
 ) -> Response {
-    assert!(LANG_CODE.contains(&lang.as_str()));
+    assert!(lang_tran::has_lang(&lang));
     assert!(LINES.contains(&line.as_str()));
     let query_count = 400;
-    let config = CONFIG.get().unwrap();
+    let config = config::get_config();
     let hostname = config.get_hostname(&line).unwrap();
     let hostname = hostname.to_owned();
     let update_time = mytime::now_vn();
-    let lang_map = LANG_MAP.get().unwrap();
+    let lang_map = lang_tran::get_lang_map();
     let lang_map = lang_map.get(lang.as_str()).unwrap();
-    let title = "Query Cell".to_string();
+    let title = lang_map.get("tem_title_query_cell").unwrap().to_owned();
     if query_params.valid() {
         let cell = query_params.cell.unwrap();
-        let (res_yield, fail_data) = db::query_cell(&line, &cell, query_count).unwrap();
+        let (res_yield, fail_data) = match db::query_cell(&line, &cell, query_count) {
+            Ok(x) => x,
+            Err(e) => {
+                error!("query cell {cell} of {line} fail, {e}");
+                return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+            }
+        };
+        let station = db::cell2station(&cell).unwrap();
+        let cell_yield = yield_rate::station_yield(station, res_yield);
+        let fixture_fails = fail_data.iter().filter(|x| x.cause == "fixture").count();
+        let category = fail_class::group(fail_data.iter().map(|x| (station, &x.class)));
+        // the server db
+        let maintenance = maintenance::of_cell(&line, &cell, 50);
+        let ports = port_config::ports_of(&line);
+        let outages = port_check::outages(&line, &cell, 20);
+        let commands = port_config::commands();
+        let command_log = fixture::logs(&line, &cell, 10);
+        let (maintenance, ports, outages, commands, command_log) =
+            match (maintenance, ports, outages, commands, command_log) {
+                (Ok(m), Ok(p), Ok(o), Ok(c), Ok(l)) => (m, p, o, c, l),
+                (Err(e), ..) | (_, Err(e), ..) | (_, _, Err(e), ..) | (_, _, _, Err(e), _) | (.., Err(e)) => {
+                    error!("read the server db of cell {cell} fail, {e}");
+                    return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+                }
+            };
+        let ports = ports.into_iter().find(|x| x.0 == cell).map(|x| x.1);
+        let port_badge = ports.as_ref().map(|_| port_check::badge_of(&line, &cell));
+        let ports = ports.unwrap_or_default();
+        // query_cell is an engineer page
+        let commands = commands.into_iter().filter(|x| x.0 == station).collect();
+        // "2024-01-04 20:48:22" -> "2024-01-04T20:48" for html datetime-local
+        let now = update_time[..16].replace(' ', "T");
         let resp = if line.contains("bst") {
             let tpl =
                 CellBstTemplate {
                     query_count,
                     cell: &cell,
                     res_yield,
+                    cell_yield,
                     record: fail_data,
+                    station,
+                    causes: &fail_tag::CAUSES,
+                    category,
+                    fixture_fails,
+                    maintenance,
+                    port_badge,
+                    outages,
+                    ports,
+                    commands,
+                    command_log,
+                    actions: &maintenance::ACTIONS,
+                    now,
                     // base.html
                     title,
                     line,
@@ -207,28 +487,16 @@ pub async fn query_cell(
                     update_time,
                     // all tem variant
                     tem_language: lang_map.get("tem_language").unwrap(),
-                    tem_day: lang_map.get("tem_day").unwrap(),
-                    tem_night: lang_map.get("tem_night").unwrap(),
-                    tem_previous_day: lang_map.get("tem_previous_day").unwrap(),
-                    tem_previous_shift: lang_map.get("tem_previous_shift").unwrap(),
-                    tem_today: lang_map.get("tem_today").unwrap(),
-                    tem_viewing_data: lang_map.get("tem_viewing_data").unwrap(),
-                    tem_colon: lang_map.get("tem_colon").unwrap(),
-                    tem_home: lang_map.get("tem_home").unwrap(),
                     tem_quantity_of_pass_fail: lang_map.get("tem_quantity_of_pass_fail").unwrap(),
                     tem_yield_of_shift: lang_map.get("tem_yield_of_shift").unwrap(),
                     tem_fail_record_details: lang_map.get("tem_fail_record_details").unwrap(),
                     tem_query_400_records_of_cell: lang_map.get("tem_query_400_records_of_cell").unwrap(),
                     tem_query_sn_history: lang_map.get("tem_query_sn_history").unwrap(),
-                    tem_query_sn_history_all: lang_map.get("tem_query_sn_history_all").unwrap(),
+                    tem_maintenance_log: lang_map.get("tem_maintenance_log").unwrap(),
                     tem_key_name_of_bgibest: lang_map.get("tem_key_name_of_bgibest").unwrap(),
                     tem_port_config_of_terminal_server: lang_map
                         .get("tem_port_config_of_terminal_server")
                         .unwrap(),
-                    tem_line_0: lang_map.get("tem_line_0").unwrap(),
-                    tem_line_1: lang_map.get("tem_line_1").unwrap(),
-                    tem_line_2: lang_map.get("tem_line_2").unwrap(),
-                    tem_line_3: lang_map.get("tem_line_3").unwrap(),
                     tem_submit: lang_map.get("tem_submit").unwrap(),
                     tem_local_time: lang_map.get("tem_local_time").unwrap(),
                     tem_sort_able: lang_map.get("tem_sort_able").unwrap(),
@@ -240,7 +508,20 @@ pub async fn query_cell(
                     query_count,
                     cell: &cell,
                     res_yield,
+                    cell_yield,
                     record: fail_data,
+                    station,
+                    causes: &fail_tag::CAUSES,
+                    category,
+                    fixture_fails,
+                    maintenance,
+                    port_badge,
+                    outages,
+                    ports,
+                    commands,
+                    command_log,
+                    actions: &maintenance::ACTIONS,
+                    now,
                     // base.html
                     title,
                     line,
@@ -248,28 +529,16 @@ pub async fn query_cell(
                     update_time,
                     // all tem variant
                     tem_language: lang_map.get("tem_language").unwrap(),
-                    tem_day: lang_map.get("tem_day").unwrap(),
-                    tem_night: lang_map.get("tem_night").unwrap(),
-                    tem_previous_day: lang_map.get("tem_previous_day").unwrap(),
-                    tem_previous_shift: lang_map.get("tem_previous_shift").unwrap(),
-                    tem_today: lang_map.get("tem_today").unwrap(),
-                    tem_viewing_data: lang_map.get("tem_viewing_data").unwrap(),
-                    tem_colon: lang_map.get("tem_colon").unwrap(),
-                    tem_home: lang_map.get("tem_home").unwrap(),
                     tem_quantity_of_pass_fail: lang_map.get("tem_quantity_of_pass_fail").unwrap(),
                     tem_yield_of_shift: lang_map.get("tem_yield_of_shift").unwrap(),
                     tem_fail_record_details: lang_map.get("tem_fail_record_details").unwrap(),
                     tem_query_400_records_of_cell: lang_map.get("tem_query_400_records_of_cell").unwrap(),
                     tem_query_sn_history: lang_map.get("tem_query_sn_history").unwrap(),
-                    tem_query_sn_history_all: lang_map.get("tem_query_sn_history_all").unwrap(),
+                    tem_maintenance_log: lang_map.get("tem_maintenance_log").unwrap(),
                     tem_key_name_of_bgibest: lang_map.get("tem_key_name_of_bgibest").unwrap(),
                     tem_port_config_of_terminal_server: lang_map
                         .get("tem_port_config_of_terminal_server")
                         .unwrap(),
-                    tem_line_0: lang_map.get("tem_line_0").unwrap(),
-                    tem_line_1: lang_map.get("tem_line_1").unwrap(),
-                    tem_line_2: lang_map.get("tem_line_2").unwrap(),
-                    tem_line_3: lang_map.get("tem_line_3").unwrap(),
                     tem_submit: lang_map.get("tem_submit").unwrap(),
                     tem_local_time: lang_map.get("tem_local_time").unwrap(),
                     tem_sort_able: lang_map.get("tem_sort_able").unwrap(),
@@ -278,11 +547,16 @@ pub async fn query_cell(
         };
         return resp;
     }
-    let stations = if line.contains("bst") { vec!["BST"] } else { vec!["LCDLED", "DIAG", "KEYPAD"] };
+    let stations = db::stations_of(&line);
     let mut station_yield = Vec::new();
     for s in stations {
-        let mut a = db::query_station_yield(&line, s, query_count).unwrap();
-        station_yield.append(&mut a);
+        match db::query_station_yield(&line, s, query_count) {
+            Ok(mut a) => station_yield.append(&mut a),
+            Err(e) => {
+                error!("query the cells of {line} {s} fail, {e}");
+                return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+            }
+        }
     }
     let tpl = StationYieldTemplate {
         station_yield,
@@ -293,28 +567,99 @@ pub async fn query_cell(
         update_time,
         // all tem variant
         tem_language: lang_map.get("tem_language").unwrap(),
-        tem_day: lang_map.get("tem_day").unwrap(),
-        tem_night: lang_map.get("tem_night").unwrap(),
-        tem_previous_day: lang_map.get("tem_previous_day").unwrap(),
-        tem_previous_shift: lang_map.get("tem_previous_shift").unwrap(),
-        tem_today: lang_map.get("tem_today").unwrap(),
+        tem_quantity_of_pass_fail: lang_map.get("tem_quantity_of_pass_fail").unwrap(),
+        tem_yield_of_shift: lang_map.get("tem_yield_of_shift").unwrap(),
+        tem_fail_record_details: lang_map.get("tem_fail_record_details").unwrap(),
+        tem_query_400_records_of_cell: lang_map.get("tem_query_400_records_of_cell").unwrap(),
+        tem_query_sn_history: lang_map.get("tem_query_sn_history").unwrap(),
+        tem_maintenance_log: lang_map.get("tem_maintenance_log").unwrap(),
+        tem_key_name_of_bgibest: lang_map.get("tem_key_name_of_bgibest").unwrap(),
+        tem_port_config_of_terminal_server: lang_map
+            .get("tem_port_config_of_terminal_server")
+            .unwrap(),
+        tem_local_time: lang_map.get("tem_local_time").unwrap(),
+        tem_sort_able: lang_map.get("tem_sort_able").unwrap(),
+    };
+    HtmlTemplate(tpl).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceParams {
+    station: Option<String>,
+    date_from: Option<String>,
+    date_to: Option<String>,
+}
+
+/// (date_from, date_to, start, end) of the whole days of a date form, date_to is today if empty,
+/// date_from is the days before date_to if empty. Err if a date is not "2024-01-04" of FORM_YEARS
+fn date_window(date_from: Option<String>, date_to: Option<String>, days: i64)
+               -> Result<(String, String, i64, i64), String> {
+    let parse = |x: &str| mytime::vn_date2ts(x, &FORM_YEARS).ok_or(format!("date {x} is invalid"));
+    let end = match date_to.as_deref().map(str::trim).filter(|x| !x.is_empty()) {
+        Some(x) => parse(x)?,
+        None => parse(&mytime::ts2vn_date(mytime::now_ts()))?,
+    } + 24 * 3600;
+    let start = match date_from.as_deref().map(str::trim).filter(|x| !x.is_empty()) {
+        Some(x) => parse(x)?,
+        None => end - days * 24 * 3600,
+    };
+    Ok((mytime::ts2vn_date(start), mytime::ts2vn_date(end - 24 * 3600), start, end))
+}
+
+/// search the maintenance log of the line by station and date, the last 30 days by default
+pub async fn maintenance(
+    extract::Path((lang, line)): extract::Path<(String, String)>,
+    extract::Query(query_params): extract::Query<MaintenanceParams>,
+) -> Response {
+    assert!(lang_tran::has_lang(&lang));
+    assert!(LINES.contains(&line.as_str()));
+    let query_count = 400;
+    let config = config::get_config();
+    let hostname = config.get_hostname(&line).unwrap();
+    let hostname = hostname.to_owned();
+    let update_time = mytime::now_vn();
+    let lang_map = lang_tran::get_lang_map();
+    let lang_map = lang_map.get(lang.as_str()).unwrap();
+    let title = lang_map.get("tem_title_maintenance").unwrap().to_owned();
+    let stations = db::stations_of(&line);
+    let station = query_params.station.filter(|x| stations.contains(&x.as_str())).unwrap_or_default();
+    let stations = stations.into_iter().map(|x| (x, if x == station { "selected" } else { "" })).collect();
+    let (date_from, date_to, start, end) = match date_window(query_params.date_from, query_params.date_to, 31) {
+        Ok(x) => x,
+        Err(e) => { return (StatusCode::BAD_REQUEST, e).into_response(); }
+    };
+    let record = match maintenance::search(&line, &station, start, end, query_count) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("search maintenance log fail, {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    };
+    let tpl = MaintenanceTemplate {
+        query_count,
+        stations,
+        date_from,
+        date_to,
+        record,
+        // base.html
+        title,
+        line,
+        hostname,
+        update_time,
+        // all tem variant
+        tem_language: lang_map.get("tem_language").unwrap(),
         tem_viewing_data: lang_map.get("tem_viewing_data").unwrap(),
         tem_colon: lang_map.get("tem_colon").unwrap(),
-        tem_home: lang_map.get("tem_home").unwrap(),
         tem_quantity_of_pass_fail: lang_map.get("tem_quantity_of_pass_fail").unwrap(),
         tem_yield_of_shift: lang_map.get("tem_yield_of_shift").unwrap(),
         tem_fail_record_details: lang_map.get("tem_fail_record_details").unwrap(),
         tem_query_400_records_of_cell: lang_map.get("tem_query_400_records_of_cell").unwrap(),
         tem_query_sn_history: lang_map.get("tem_query_sn_history").unwrap(),
-        tem_query_sn_history_all: lang_map.get("tem_query_sn_history_all").unwrap(),
+        tem_maintenance_log: lang_map.get("tem_maintenance_log").unwrap(),
         tem_key_name_of_bgibest: lang_map.get("tem_key_name_of_bgibest").unwrap(),
         tem_port_config_of_terminal_server: lang_map
             .get("tem_port_config_of_terminal_server")
             .unwrap(),
-        tem_line_0: lang_map.get("tem_line_0").unwrap(),
-        tem_line_1: lang_map.get("tem_line_1").unwrap(),
-        tem_line_2: lang_map.get("tem_line_2").unwrap(),
-        tem_line_3: lang_map.get("tem_line_3").unwrap(),
         tem_submit: lang_map.get("tem_submit").unwrap(),
         tem_local_time: lang_map.get("tem_local_time").unwrap(),
         tem_sort_able: lang_map.get("tem_sort_able").unwrap(),
@@ -322,6 +667,315 @@ pub async fn query_cell(
     HtmlTemplate(tpl).into_response()
 }
 
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceForm {
+    cell: String,
+    time: String,
+    action: String,
+    notes: String,
+}
+
+/// record a repair of the cell by the login engineer, then go back to the query_cell page
+pub async fn add_maintenance(
+    extract::Path((lang, line)): extract::Path<(String, String)>,
+    extract::Extension(user): extract::Extension<User>,
+    extract::Form(form): extract::Form<MaintenanceForm>,
+) -> Response {
+    assert!(LINES.contains(&line.as_str()));
+    let config = config::get_config();
+    let station = match db::cell2station(&form.cell) {
+        Some(s) if config.get_db(&line, s).is_some() => s,
+        _ => { return (StatusCode::BAD_REQUEST, format!("cell {} is not in line {line}", form.cell)).into_response(); }
+    };
+    let time_int = match mytime::vn_str2ts(&form.time) {
+        Some(t) => t,
+        None => { return (StatusCode::BAD_REQUEST, format!("time {} is invalid", form.time)).into_response(); }
+    };
+    if !maintenance::ACTIONS.contains(&form.action.as_str()) {
+        return (StatusCode::BAD_REQUEST, format!("action {} is invalid", form.action)).into_response();
+    }
+    let notes: String = form.notes.trim().chars().take(maintenance::NOTES_MAX_LEN).collect();
+    if let Err(e) = maintenance::add(&line, station, &form.cell, time_int, &form.action, &notes, &user.name) {
+        error!("add maintenance log fail, {e}");
+        return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+    }
+    info!("{} add maintenance log of {line} {}: {}", user.name, form.cell, form.action);
+    let url = format!("/{lang}/{line}/query_cell/?cell={}", form.cell);
+    Redirect::to(&url).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FailTagForm {
+    station: String,
+    record_id: i64,
+    cell: String,
+    cause: String,
+    comment: String,
+}
+
+/// tag the root cause of a fail record by the login engineer, then go back to the page of the table
+pub async fn add_fail_tag(
+    extract::Path((lang, line)): extract::Path<(String, String)>,
+    extract::Extension(user): extract::Extension<User>,
+    header_map: HeaderMap,
+    extract::Form(form): extract::Form<FailTagForm>,
+) -> Response {
+    assert!(LINES.contains(&line.as_str()));
+    let config = config::get_config();
+    let db_path = match config.get_db(&line, &form.station) {
+        Some(p) if db::cell2station(&form.cell) == Some(form.station.as_str()) => p,
+        _ => { return (StatusCode::BAD_REQUEST, format!("cell {} is not in line {line}", form.cell)).into_response(); }
+    };
+    if !form.cause.is_empty() && !fail_tag::CAUSES.contains(&form.cause.as_str()) {
+        return (StatusCode::BAD_REQUEST, format!("cause {} is invalid", form.cause)).into_response();
+    }
+    let comment: String = form.comment.trim().chars().take(fail_tag::COMMENT_MAX_LEN).collect();
+    if let Err(e) = fail_tag::set(&line, &form.station, db_path, form.record_id, &form.cell, &form.cause, &comment, &user.name) {
+        error!("tag fail record fail, {e}");
+        return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+    }
+    info!("{} tag {line} {} record {} as {}", user.name, form.station, form.record_id, form.cause);
+    // back to the same fail_detail or query_cell page
+    back_to(&header_map, &lang, &line, "fail_detail")
+}
+
+/// redirect to the referer page of the same line, or the page of the line if no referer
+fn back_to(header_map: &HeaderMap, lang: &str, line: &str, page: &str) -> Response {
+    let referer = header_map.get(header::REFERER).and_then(|x| x.to_str().ok()).and_then(|x| x.parse::<Uri>().ok());
+    let next = referer.and_then(|x| x.path_and_query().map(|p| p.as_str().to_string()));
+    let url = match next {
+        Some(next) if next.starts_with(&format!("/{lang}/{line}/")) => next,
+        _ => format!("/{lang}/{line}/{page}"),
+    };
+    Redirect::to(&url).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FixtureCommandForm {
+    // the cell or the name of the port config
+    name: String,
+    command: String,
+    // which one of "2002/2010", 0 is the first
+    #[serde(default)]
+    port_index: usize,
+}
+
+/// send the command to the fixture port and log it, the error is the response of the bad request
+async fn run_fixture_command(line: &str, form: &FixtureCommandForm, user: &User)
+                             -> Result<fixture::CommandResult, Response> {
+    let target = fixture::resolve(line, &form.name, form.port_index, Some(&form.command))
+        .map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+    let _lock = fixture::lock_port(&target.host, target.port).ok_or_else(|| {
+        let msg = format!("{}:{} is in use, try later", target.host, target.port);
+        (StatusCode::CONFLICT, msg).into_response()
+    })?;
+    let config = config::get_config();
+    let timeout = Duration::from_millis(config.fixture.command_timeout_ms);
+    let result = fixture::send(&target, &form.command, &config.fixture.line_end, timeout).await;
+    info!("{} send {} to {line} {} {}:{}, {} in {}ms", user.name, form.command, form.name, target.host, target.port,
+             result.result, result.elapsed_ms);
+    if let Err(e) = fixture::log(line, &form.name, &target, &form.command, &result, &user.name) {
+        error!("log fixture command fail, {e}");
+    }
+    Ok(result)
+}
+
+/// send a fixture command from the page, the result is in the command log of the same page
+pub async fn fixture_command(
+    extract::Path((lang, line)): extract::Path<(String, String)>,
+    extract::Extension(user): extract::Extension<User>,
+    header_map: HeaderMap,
+    extract::Form(form): extract::Form<FixtureCommandForm>,
+) -> Response {
+    assert!(LINES.contains(&line.as_str()));
+    match run_fixture_command(&line, &form, &user).await {
+        Ok(_) => back_to(&header_map, &lang, &line, "portconfig"),
+        Err(resp) => resp,
+    }
+}
+
+/// the api of fixture_command, {"name": "CELL_69", "command": "MODE1#"} -> {"result": "ok", ...}
+pub async fn json_fixture_command(
+    extract::Path((_lang, line)): extract::Path<(String, String)>,
+    extract::Extension(user): extract::Extension<User>,
+    Json(form): Json<FixtureCommandForm>,
+) -> Response {
+    assert!(LINES.contains(&line.as_str()));
+    match run_fixture_command(&line, &form, &user).await {
+        Ok(result) => Json(result).into_response(),
+        Err(resp) => resp,
+    }
+}
+
+/// name -> (css class, text, title) of the port badge, polled by port_status.js
+pub async fn port_status(
+    extract::Path((_lang, line)): extract::Path<(String, String)>,
+) -> Json<HashMap<String, port_check::Rbadge>> {
+    Json(port_check::badges_of(&line))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TerminalServerForm {
+    host: String,
+    note: String,
+}
+
+/// change the terminal server of the line by admin
+pub async fn set_terminal_server(
+    extract::Path((lang, line)): extract::Path<(String, String)>,
+    extract::Extension(user): extract::Extension<User>,
+    extract::Form(form): extract::Form<TerminalServerForm>,
+) -> Response {
+    assert!(LINES.contains(&line.as_str()));
+    let host = form.host.trim();
+    if host.is_empty() || host.len() > port_config::HOST_MAX_LEN || host.contains(char::is_whitespace) {
+        return (StatusCode::BAD_REQUEST, format!("host {host} is invalid")).into_response();
+    }
+    let note: String = form.note.trim().chars().take(port_config::TEXT_MAX_LEN).collect();
+    if let Err(e) = port_config::set_server(&line, host, &note) {
+        error!("set terminal server fail, {e}");
+        return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+    }
+    info!("{} set terminal server of {line} to {host}", user.name);
+    Redirect::to(&format!("/{lang}/{line}/portconfig")).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PortForm {
+    name: String,
+    #[serde(default)]
+    ports: String,
+    // any value is delete
+    del: Option<String>,
+}
+
+/// add, change or delete a fixture port of the line by admin
+pub async fn set_fixture_port(
+    extract::Path((lang, line)): extract::Path<(String, String)>,
+    extract::Extension(user): extract::Extension<User>,
+    extract::Form(form): extract::Form<PortForm>,
+) -> Response {
+    assert!(LINES.contains(&line.as_str()));
+    let name = form.name.trim();
+    if name.is_empty() || name.len() > port_config::NAME_MAX_LEN {
+        return (StatusCode::BAD_REQUEST, format!("name {name} is invalid")).into_response();
+    }
+    let result = if form.del.is_some() {
+        port_config::del_port(&line, name).map(|_| ())
+    } else {
+        if port_config::parse_ports(&form.ports).is_none() {
+            return (StatusCode::BAD_REQUEST, format!("ports {} is invalid, e.g. 2002/2010", form.ports)).into_response();
+        }
+        let ports = form.ports.split('/').map(|x| x.trim()).collect::<Vec<_>>().join("/");
+        port_config::set_port(&line, name, &ports)
+    };
+    if let Err(e) = result {
+        error!("set fixture port fail, {e}");
+        return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+    }
+    info!("{} {} fixture port {name} of {line}", user.name, if form.del.is_some() { "delete" } else { "set" });
+    Redirect::to(&format!("/{lang}/{line}/portconfig")).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommandForm {
+    station: String,
+    command: String,
+    #[serde(default)]
+    reply: String,
+    #[serde(default)]
+    note: String,
+    // any value is delete
+    del: Option<String>,
+}
+
+/// add, change or delete a fixture command by admin, the commands are same for every line
+pub async fn set_fixture_command(
+    extract::Path((lang, line)): extract::Path<(String, String)>,
+    extract::Extension(user): extract::Extension<User>,
+    extract::Form(form): extract::Form<CommandForm>,
+) -> Response {
+    assert!(LINES.contains(&line.as_str()));
+    if config::get_config().get_db(&line, &form.station).is_none() {
+        return (StatusCode::BAD_REQUEST, format!("station {} is not in line {line}", form.station)).into_response();
+    }
+    let command = form.command.trim();
+    if command.is_empty() || command.len() > port_config::COMMAND_MAX_LEN {
+        return (StatusCode::BAD_REQUEST, format!("command {command} is invalid")).into_response();
+    }
+    let result = if form.del.is_some() {
+        port_config::del_command(&form.station, command).map(|_| ())
+    } else {
+        let reply: String = form.reply.trim().chars().take(port_config::COMMAND_MAX_LEN).collect();
+        let note: String = form.note.trim().chars().take(port_config::TEXT_MAX_LEN).collect();
+        port_config::set_command(&form.station, command, &reply, &note)
+    };
+    if let Err(e) = result {
+        error!("set fixture command fail, {e}");
+        return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+    }
+    info!("{} {} fixture command {} {command}", user.name, if form.del.is_some() { "delete" } else { "set" }, form.station);
+    Redirect::to(&format!("/{lang}/{line}/portconfig")).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KeyForm {
+    key_code: String,
+    #[serde(default)]
+    name: String,
+    // empty is not marked on the image
+    #[serde(default)]
+    x: String,
+    #[serde(default)]
+    y: String,
+    // any value is delete
+    del: Option<String>,
+}
+
+/// percent position on the image, "" is None
+fn parse_percent(s: &str) -> Result<Option<f64>, ()> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Ok(None);
+    }
+    match s.parse::<f64>() {
+        Ok(x) if (0.0..=100.0).contains(&x) => Ok(Some(x)),
+        _ => Err(()),
+    }
+}
+
+/// add, change or delete a keypad key name of the line by admin
+pub async fn set_key_name(
+    extract::Path((lang, line)): extract::Path<(String, String)>,
+    extract::Extension(user): extract::Extension<User>,
+    extract::Form(form): extract::Form<KeyForm>,
+) -> Response {
+    assert!(LINES.contains(&line.as_str()));
+    let key_code = form.key_code.trim();
+    if key_code.is_empty() || key_code.len() > key_name::KEY_MAX_LEN {
+        return (StatusCode::BAD_REQUEST, format!("key code {key_code} is invalid")).into_response();
+    }
+    let result = if form.del.is_some() {
+        key_name::del_key(&line, key_code).map(|_| ())
+    } else {
+        let name = form.name.trim();
+        let (x, y) = match (parse_percent(&form.x), parse_percent(&form.y)) {
+            (Ok(x), Ok(y)) if x.is_some() == y.is_some() => (x, y),
+            _ => { return (StatusCode::BAD_REQUEST, "x and y should be both empty or 0~100").into_response(); }
+        };
+        if name.is_empty() || name.len() > key_name::KEY_MAX_LEN {
+            return (StatusCode::BAD_REQUEST, format!("name {name} is invalid")).into_response();
+        }
+        key_name::set_key(&line, key_code, name, x, y)
+    };
+    if let Err(e) = result {
+        error!("set key name fail, {e}");
+        return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+    }
+    info!("{} {} key name {key_code} of {line}", user.name, if form.del.is_some() { "delete" } else { "set" });
+    Redirect::to(&format!("/{lang}/{line}/keyname")).into_response()
+}
+
 #[derive(Debug, Deserialize)]
 pub struct QueryDateParams {
     querydate: Option<String>,
@@ -375,16 +1029,18 @@ impl QueryParaValid for QueryDateParams {
 pub async fn fail_detail(
     extract::Path((lang, line)): extract::Path<(String, String)>,
     extract::Query(query_params): extract::Query<QueryDateParams>,
+    uri: Uri,
+    header_map: HeaderMap,
 ) -> Response {
-    assert!(LANG_CODE.contains(&lang.as_str()));
+    assert!(lang_tran::has_lang(&lang));
     assert!(LINES.contains(&line.as_str()));
-    let config = CONFIG.get().unwrap();
+    let config = config::get_config();
     let hostname = config.get_hostname(&line).unwrap();
     let hostname = hostname.to_owned();
     let update_time = mytime::now_vn();
-    let lang_map = LANG_MAP.get().unwrap();
+    let lang_map = lang_tran::get_lang_map();
     let lang_map = lang_map.get(lang.as_str()).unwrap();
-    let title = "Fail Detail".to_string();
+    let title = lang_map.get("tem_title_fail_detail").unwrap().to_owned();
     let dateshift = query_params.des();
     if dateshift.is_none() {
         let current_shift = mytime::current_shift();
@@ -393,14 +1049,27 @@ pub async fn fail_detail(
         return Redirect::to(&url).into_response();
     };
     let dateshift = dateshift.unwrap();
+    let etag = shift_etag(&uri, &line, mytime::start_end_of_shift(&dateshift).1, true);
+    if let Some(response) = not_modified(&header_map, &etag) {
+        return response;
+    }
     let date = dateshift.date();
     let shift = dateshift.shift().to_string();
     if line.contains("bst") {
-        let bst = db::fail_detail(&line, "BST", &dateshift).unwrap();
+        let bst = match db::fail_detail(&line, "BST", &dateshift) {
+            Ok(x) => x,
+            Err(e) => {
+                error!("fail detail of {line} BST fail, {e}");
+                return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+            }
+        };
+        let category = fail_class::group(bst.iter().map(|x| ("BST", &x.class)));
         let tpl = DetailBstTemplate {
             data_day: &date,
             data_shift: &shift,
             bst,
+            causes: &fail_tag::CAUSES,
+            category,
             // base.html
             title,
             line,
@@ -415,36 +1084,42 @@ pub async fn fail_detail(
             tem_today: lang_map.get("tem_today").unwrap(),
             tem_viewing_data: lang_map.get("tem_viewing_data").unwrap(),
             tem_colon: lang_map.get("tem_colon").unwrap(),
-            tem_home: lang_map.get("tem_home").unwrap(),
             tem_quantity_of_pass_fail: lang_map.get("tem_quantity_of_pass_fail").unwrap(),
             tem_yield_of_shift: lang_map.get("tem_yield_of_shift").unwrap(),
             tem_fail_record_details: lang_map.get("tem_fail_record_details").unwrap(),
             tem_query_400_records_of_cell: lang_map.get("tem_query_400_records_of_cell").unwrap(),
             tem_query_sn_history: lang_map.get("tem_query_sn_history").unwrap(),
-            tem_query_sn_history_all: lang_map.get("tem_query_sn_history_all").unwrap(),
+            tem_maintenance_log: lang_map.get("tem_maintenance_log").unwrap(),
             tem_key_name_of_bgibest: lang_map.get("tem_key_name_of_bgibest").unwrap(),
             tem_port_config_of_terminal_server: lang_map
                 .get("tem_port_config_of_terminal_server")
                 .unwrap(),
-            tem_line_0: lang_map.get("tem_line_0").unwrap(),
-            tem_line_1: lang_map.get("tem_line_1").unwrap(),
-            tem_line_2: lang_map.get("tem_line_2").unwrap(),
-            tem_line_3: lang_map.get("tem_line_3").unwrap(),
-            tem_submit: lang_map.get("tem_submit").unwrap(),
             tem_local_time: lang_map.get("tem_local_time").unwrap(),
             tem_sort_able: lang_map.get("tem_sort_able").unwrap(),
         };
-        HtmlTemplate(tpl).into_response()
+        with_etag(&etag, HtmlTemplate(tpl).into_response())
     } else {
-        let lcdled = db::fail_detail(&line, "LCDLED", &dateshift).unwrap();
-        let diag = db::fail_detail(&line, "DIAG", &dateshift).unwrap();
-        let keypad = db::fail_detail(&line, "KEYPAD", &dateshift).unwrap();
+        let lcdled = db::fail_detail(&line, "LCDLED", &dateshift);
+        let diag = db::fail_detail(&line, "DIAG", &dateshift);
+        let keypad = db::fail_detail(&line, "KEYPAD", &dateshift);
+        let (lcdled, diag, keypad) = match (lcdled, diag, keypad) {
+            (Ok(l), Ok(d), Ok(k)) => (l, d, k),
+            (Err(e), ..) | (_, Err(e), _) | (.., Err(e)) => {
+                error!("fail detail of {line} fail, {e}");
+                return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+            }
+        };
+        let category = fail_class::group(lcdled.iter().map(|x| ("LCDLED", &x.class))
+            .chain(diag.iter().map(|x| ("DIAG", &x.class)))
+            .chain(keypad.iter().map(|x| ("KEYPAD", &x.class))));
         let tpl = DetailFstTemplate {
             data_day: &date,
             data_shift: &shift,
             lcdled,
             diag,
             keypad,
+            causes: &fail_tag::CAUSES,
+            category,
             // base.html
             title,
             line,
@@ -459,56 +1134,62 @@ pub async fn fail_detail(
             tem_today: lang_map.get("tem_today").unwrap(),
             tem_viewing_data: lang_map.get("tem_viewing_data").unwrap(),
             tem_colon: lang_map.get("tem_colon").unwrap(),
-            tem_home: lang_map.get("tem_home").unwrap(),
             tem_quantity_of_pass_fail: lang_map.get("tem_quantity_of_pass_fail").unwrap(),
             tem_yield_of_shift: lang_map.get("tem_yield_of_shift").unwrap(),
             tem_fail_record_details: lang_map.get("tem_fail_record_details").unwrap(),
             tem_query_400_records_of_cell: lang_map.get("tem_query_400_records_of_cell").unwrap(),
             tem_query_sn_history: lang_map.get("tem_query_sn_history").unwrap(),
-            tem_query_sn_history_all: lang_map.get("tem_query_sn_history_all").unwrap(),
+            tem_maintenance_log: lang_map.get("tem_maintenance_log").unwrap(),
             tem_key_name_of_bgibest: lang_map.get("tem_key_name_of_bgibest").unwrap(),
             tem_port_config_of_terminal_server: lang_map
                 .get("tem_port_config_of_terminal_server")
                 .unwrap(),
-            tem_line_0: lang_map.get("tem_line_0").unwrap(),
-            tem_line_1: lang_map.get("tem_line_1").unwrap(),
-            tem_line_2: lang_map.get("tem_line_2").unwrap(),
-            tem_line_3: lang_map.get("tem_line_3").unwrap(),
-            tem_submit: lang_map.get("tem_submit").unwrap(),
             tem_local_time: lang_map.get("tem_local_time").unwrap(),
             tem_sort_able: lang_map.get("tem_sort_able").unwrap(),
         };
-        HtmlTemplate(tpl).into_response()
+        with_etag(&etag, HtmlTemplate(tpl).into_response())
     }
 }
 
 pub async fn pf_data(extract::Path((lang, line)): extract::Path<(String, String)>,
                      extract::Query(query_params): extract::Query<QueryDateParams>,
+                     uri: Uri,
+                     header_map: HeaderMap,
 ) -> Response {
-    assert!(LANG_CODE.contains(&lang.as_str()));
+    assert!(lang_tran::has_lang(&lang));
     assert!(LINES.contains(&line.as_str()));
-    let config = CONFIG.get().unwrap();
+    let config = config::get_config();
     let hostname = config.get_hostname(&line).unwrap();
     let hostname = hostname.to_owned();
     let update_time = mytime::now_vn();
-    let lang_map = LANG_MAP.get().unwrap();
+    let lang_map = lang_tran::get_lang_map();
     let lang_map = lang_map.get(lang.as_str()).unwrap();
-    let title = "Pass | Fail data".to_string();
+    let title = lang_map.get("tem_title_pf_data").unwrap().to_owned();
     let dateshift = query_params.des();
     if dateshift.is_none() {
         let current_shift = mytime::current_shift();
         let (date, shift) = (current_shift.date(), current_shift.shift());
         let url = format!("/{lang}/{line}/pf_data/?querydate={date}&shift={shift}");
-        println!("pf_data, redirect to: {url}");
+        debug!("pf_data, redirect to: {url}");
         return Redirect::to(&url).into_response();
     };
     let dateshift = dateshift.unwrap();
+    let etag = shift_etag(&uri, &line, mytime::start_end_of_shift(&dateshift).1, false);
+    if let Some(response) = not_modified(&header_map, &etag) {
+        return response;
+    }
     let date = dateshift.date();
     let shift = dateshift.shift().to_string();
     let hours_str = mytime::hours_str(dateshift.shift(), true);
     let ts_per_hour = mytime::ts_per_hour_shift(&dateshift);
     if line.contains("bst") {
-        let bst = db::pf_data(&line, "BST", ts_per_hour, &hours_str).unwrap();
+        let bst = match db::pf_data(&line, "BST", ts_per_hour, &hours_str) {
+            Ok(x) => x,
+            Err(e) => {
+                error!("pf data of {line} BST fail, {e}");
+                return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+            }
+        };
         let tpl = PfBstTemplate {
             data_day: &date,
             data_shift: &shift,
@@ -527,30 +1208,31 @@ pub async fn pf_data(extract::Path((lang, line)): extract::Path<(String, String)
             tem_today: lang_map.get("tem_today").unwrap(),
             tem_viewing_data: lang_map.get("tem_viewing_data").unwrap(),
             tem_colon: lang_map.get("tem_colon").unwrap(),
-            tem_home: lang_map.get("tem_home").unwrap(),
             tem_quantity_of_pass_fail: lang_map.get("tem_quantity_of_pass_fail").unwrap(),
             tem_yield_of_shift: lang_map.get("tem_yield_of_shift").unwrap(),
             tem_fail_record_details: lang_map.get("tem_fail_record_details").unwrap(),
             tem_query_400_records_of_cell: lang_map.get("tem_query_400_records_of_cell").unwrap(),
             tem_query_sn_history: lang_map.get("tem_query_sn_history").unwrap(),
-            tem_query_sn_history_all: lang_map.get("tem_query_sn_history_all").unwrap(),
+            tem_maintenance_log: lang_map.get("tem_maintenance_log").unwrap(),
             tem_key_name_of_bgibest: lang_map.get("tem_key_name_of_bgibest").unwrap(),
             tem_port_config_of_terminal_server: lang_map
                 .get("tem_port_config_of_terminal_server")
                 .unwrap(),
-            tem_line_0: lang_map.get("tem_line_0").unwrap(),
-            tem_line_1: lang_map.get("tem_line_1").unwrap(),
-            tem_line_2: lang_map.get("tem_line_2").unwrap(),
-            tem_line_3: lang_map.get("tem_line_3").unwrap(),
             tem_submit: lang_map.get("tem_submit").unwrap(),
             tem_local_time: lang_map.get("tem_local_time").unwrap(),
-            tem_sort_able: lang_map.get("tem_sort_able").unwrap(),
         };
-        HtmlTemplate(tpl).into_response()
+        with_etag(&etag, HtmlTemplate(tpl).into_response())
     } else {
-        let lcdled = db::pf_data(&line, "LCDLED", ts_per_hour, &hours_str).unwrap();
-        let diag = db::pf_data(&line, "DIAG", ts_per_hour, &hours_str).unwrap();
-        let keypad = db::pf_data(&line, "KEYPAD", ts_per_hour, &hours_str).unwrap();
+        let lcdled = db::pf_data(&line, "LCDLED", ts_per_hour, &hours_str);
+        let diag = db::pf_data(&line, "DIAG", ts_per_hour, &hours_str);
+        let keypad = db::pf_data(&line, "KEYPAD", ts_per_hour, &hours_str);
+        let (lcdled, diag, keypad) = match (lcdled, diag, keypad) {
+            (Ok(l), Ok(d), Ok(k)) => (l, d, k),
+            (Err(e), ..) | (_, Err(e), _) | (.., Err(e)) => {
+                error!("pf data of {line} fail, {e}");
+                return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+            }
+        };
         let tpl = PfFstTemplate {
             data_day: &date,
             data_shift: &shift,
@@ -571,40 +1253,36 @@ pub async fn pf_data(extract::Path((lang, line)): extract::Path<(String, String)
             tem_today: lang_map.get("tem_today").unwrap(),
             tem_viewing_data: lang_map.get("tem_viewing_data").unwrap(),
             tem_colon: lang_map.get("tem_colon").unwrap(),
-            tem_home: lang_map.get("tem_home").unwrap(),
             tem_quantity_of_pass_fail: lang_map.get("tem_quantity_of_pass_fail").unwrap(),
             tem_yield_of_shift: lang_map.get("tem_yield_of_shift").unwrap(),
             tem_fail_record_details: lang_map.get("tem_fail_record_details").unwrap(),
             tem_query_400_records_of_cell: lang_map.get("tem_query_400_records_of_cell").unwrap(),
             tem_query_sn_history: lang_map.get("tem_query_sn_history").unwrap(),
-            tem_query_sn_history_all: lang_map.get("tem_query_sn_history_all").unwrap(),
+            tem_maintenance_log: lang_map.get("tem_maintenance_log").unwrap(),
             tem_key_name_of_bgibest: lang_map.get("tem_key_name_of_bgibest").unwrap(),
             tem_port_config_of_terminal_server: lang_map
                 .get("tem_port_config_of_terminal_server")
                 .unwrap(),
-            tem_line_0: lang_map.get("tem_line_0").unwrap(),
-            tem_line_1: lang_map.get("tem_line_1").unwrap(),
-            tem_line_2: lang_map.get("tem_line_2").unwrap(),
-            tem_line_3: lang_map.get("tem_line_3").unwrap(),
             tem_submit: lang_map.get("tem_submit").unwrap(),
             tem_local_time: lang_map.get("tem_local_time").unwrap(),
-            tem_sort_able: lang_map.get("tem_sort_able").unwrap(),
         };
-        HtmlTemplate(tpl).into_response()
+        with_etag(&etag, HtmlTemplate(tpl).into_response())
     }
 }
 
 pub async fn day_yield(extract::Path((lang, line)): extract::Path<(String, String)>,
-                       extract::Query(query_params): extract::Query<QueryDateParams>) -> Response {
-    assert!(LANG_CODE.contains(&lang.as_str()));
+                       extract::Query(query_params): extract::Query<QueryDateParams>,
+                       uri: Uri,
+                       header_map: HeaderMap) -> Response {
+    assert!(lang_tran::has_lang(&lang));
     assert!(LINES.contains(&line.as_str()));
-    let config = CONFIG.get().unwrap();
+    let config = config::get_config();
     let hostname = config.get_hostname(&line).unwrap();
     let hostname = hostname.to_owned();
     let update_time = mytime::now_vn();
-    let lang_map = LANG_MAP.get().unwrap();
+    let lang_map = lang_tran::get_lang_map();
     let lang_map = lang_map.get(lang.as_str()).unwrap();
-    let title = "Day Yield".to_string();
+    let title = lang_map.get("tem_title_day_yield").unwrap().to_owned();
     let dateshift = query_params.des();
     if dateshift.is_none() {
         let current_shift = mytime::current_shift();
@@ -613,12 +1291,22 @@ pub async fn day_yield(extract::Path((lang, line)): extract::Path<(String, Strin
         return Redirect::to(&url).into_response();
     };
     let dateshift = dateshift.unwrap();
+    let etag = shift_etag(&uri, &line, mytime::start_end_of_shift(&dateshift).1, false);
+    if let Some(response) = not_modified(&header_map, &etag) {
+        return response;
+    }
     let date = dateshift.date();
     let shift = dateshift.shift().to_string();
     let hours_str = mytime::hours_str(dateshift.shift(), true);
     let ts_per_hour = mytime::ts_per_hour_shift(&dateshift);
     if line.contains("bst") {
-        let bst = db::day_yield(&line, "BST", ts_per_hour, &hours_str).unwrap();
+        let bst = match db::day_yield(&line, "BST", ts_per_hour, &hours_str) {
+            Ok(x) => x,
+            Err(e) => {
+                error!("day yield of {line} BST fail, {e}");
+                return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+            }
+        };
         let tpl = YiedlBstTemplate {
             data_day: &date,
             data_shift: &shift,
@@ -637,30 +1325,30 @@ pub async fn day_yield(extract::Path((lang, line)): extract::Path<(String, Strin
             tem_today: lang_map.get("tem_today").unwrap(),
             tem_viewing_data: lang_map.get("tem_viewing_data").unwrap(),
             tem_colon: lang_map.get("tem_colon").unwrap(),
-            tem_home: lang_map.get("tem_home").unwrap(),
             tem_quantity_of_pass_fail: lang_map.get("tem_quantity_of_pass_fail").unwrap(),
             tem_yield_of_shift: lang_map.get("tem_yield_of_shift").unwrap(),
             tem_fail_record_details: lang_map.get("tem_fail_record_details").unwrap(),
             tem_query_400_records_of_cell: lang_map.get("tem_query_400_records_of_cell").unwrap(),
             tem_query_sn_history: lang_map.get("tem_query_sn_history").unwrap(),
-            tem_query_sn_history_all: lang_map.get("tem_query_sn_history_all").unwrap(),
+            tem_maintenance_log: lang_map.get("tem_maintenance_log").unwrap(),
             tem_key_name_of_bgibest: lang_map.get("tem_key_name_of_bgibest").unwrap(),
             tem_port_config_of_terminal_server: lang_map
                 .get("tem_port_config_of_terminal_server")
                 .unwrap(),
-            tem_line_0: lang_map.get("tem_line_0").unwrap(),
-            tem_line_1: lang_map.get("tem_line_1").unwrap(),
-            tem_line_2: lang_map.get("tem_line_2").unwrap(),
-            tem_line_3: lang_map.get("tem_line_3").unwrap(),
-            tem_submit: lang_map.get("tem_submit").unwrap(),
             tem_local_time: lang_map.get("tem_local_time").unwrap(),
-            tem_sort_able: lang_map.get("tem_sort_able").unwrap(),
         };
-        HtmlTemplate(tpl).into_response()
+        with_etag(&etag, HtmlTemplate(tpl).into_response())
     } else {
-        let lcdled = db::day_yield(&line, "LCDLED", ts_per_hour, &hours_str).unwrap();
-        let diag = db::day_yield(&line, "DIAG", ts_per_hour, &hours_str).unwrap();
-        let keypad = db::day_yield(&line, "KEYPAD", ts_per_hour, &hours_str).unwrap();
+        let lcdled = db::day_yield(&line, "LCDLED", ts_per_hour, &hours_str);
+        let diag = db::day_yield(&line, "DIAG", ts_per_hour, &hours_str);
+        let keypad = db::day_yield(&line, "KEYPAD", ts_per_hour, &hours_str);
+        let (lcdled, diag, keypad) = match (lcdled, diag, keypad) {
+            (Ok(l), Ok(d), Ok(k)) => (l, d, k),
+            (Err(e), ..) | (_, Err(e), _) | (.., Err(e)) => {
+                error!("day yield of {line} fail, {e}");
+                return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+            }
+        };
         let tpl = YiedlFstTemplate {
             data_day: &date,
             data_shift: &shift,
@@ -681,33 +1369,26 @@ pub async fn day_yield(extract::Path((lang, line)): extract::Path<(String, Strin
             tem_today: lang_map.get("tem_today").unwrap(),
             tem_viewing_data: lang_map.get("tem_viewing_data").unwrap(),
             tem_colon: lang_map.get("tem_colon").unwrap(),
-            tem_home: lang_map.get("tem_home").unwrap(),
             tem_quantity_of_pass_fail: lang_map.get("tem_quantity_of_pass_fail").unwrap(),
             tem_yield_of_shift: lang_map.get("tem_yield_of_shift").unwrap(),
             tem_fail_record_details: lang_map.get("tem_fail_record_details").unwrap(),
             tem_query_400_records_of_cell: lang_map.get("tem_query_400_records_of_cell").unwrap(),
             tem_query_sn_history: lang_map.get("tem_query_sn_history").unwrap(),
-            tem_query_sn_history_all: lang_map.get("tem_query_sn_history_all").unwrap(),
+            tem_maintenance_log: lang_map.get("tem_maintenance_log").unwrap(),
             tem_key_name_of_bgibest: lang_map.get("tem_key_name_of_bgibest").unwrap(),
             tem_port_config_of_terminal_server: lang_map
                 .get("tem_port_config_of_terminal_server")
                 .unwrap(),
-            tem_line_0: lang_map.get("tem_line_0").unwrap(),
-            tem_line_1: lang_map.get("tem_line_1").unwrap(),
-            tem_line_2: lang_map.get("tem_line_2").unwrap(),
-            tem_line_3: lang_map.get("tem_line_3").unwrap(),
-            tem_submit: lang_map.get("tem_submit").unwrap(),
             tem_local_time: lang_map.get("tem_local_time").unwrap(),
-            tem_sort_able: lang_map.get("tem_sort_able").unwrap(),
         };
-        HtmlTemplate(tpl).into_response()
+        with_etag(&etag, HtmlTemplate(tpl).into_response())
     }
 }
 
 pub async fn pre_day(
     extract::Path((lang, line, item)): extract::Path<(String, String, String)>,
     header_map: HeaderMap) -> Response {
-    assert!(LANG_CODE.contains(&lang.as_str()));
+    assert!(lang_tran::has_lang(&lang));
     assert!(LINES.contains(&line.as_str()));
     let referer = header_map.get("referer");
     match referer {
@@ -716,13 +1397,13 @@ pub async fn pre_day(
             let pre = cur.pre_day();
             let (date, shift) = (pre.date(), pre.shift());
             let url = format!("/{lang}/{line}/{item}/?querydate={date}&shift={shift}");
-            println!("cap4 non redirect to: {url}");
+            debug!("cap4 non redirect to: {url}");
             Redirect::to(&url).into_response()
         }
         Some(refer) => {
             // http://localhost:7890/en-US/fst2/fail_detail
             // http://localhost:7890/en-US/fst2/fail_detail/?querydate=2024-01-04&shift=DAY
-            let re_url = Regex::new(r"http://[\w.]+:\d+?/(\w\w-\w\w)/(\w+)/(\w+)(/\?querydate=((20\d{2})-(0\d|1[12])-([012]\d|3[01]))&shift=(\w+))?$").unwrap();
+            let re_url = Regex::new(r"https?://[\w.-]+(?::\d+)?/(\w\w-\w\w)/(\w+)/(\w+)(/\?querydate=((20\d{2})-(0\d|1[12])-([012]\d|3[01]))&shift=(\w+))?$").unwrap();
             if let Some(cap) = re_url.captures(refer.to_str().unwrap()) {
                 let (lang, line, item) = (&cap[1], &cap[2], &cap[3]);
                 match cap.get(4) {
@@ -731,14 +1412,14 @@ pub async fn pre_day(
                         let pre = cur.pre_day();
                         let (date, shift) = (pre.date(), pre.shift());
                         let url = format!("/{lang}/{line}/{item}/?querydate={date}&shift={shift}");
-                        println!("cap4 non redirect to: {url}");
+                        debug!("cap4 non redirect to: {url}");
                         return Redirect::to(&url).into_response();
                     }
                     Some(_) => {
                         let (year, month, day, shift) = (&cap[6], &cap[7], &cap[8], &cap[9]);
                         let date = mytime::pre_day_str2date(year, month, day);
                         let url = format!("/{lang}/{line}/{item}/?querydate={date}&shift={shift}");
-                        println!("cap4 some redirect to: {url}");
+                        debug!("cap4 some redirect to: {url}");
                         return Redirect::to(&url).into_response();
                     }
                 }
@@ -751,7 +1432,7 @@ pub async fn pre_day(
 pub async fn pre_shift(
     extract::Path((lang, line, item)): extract::Path<(String, String, String)>,
     header_map: HeaderMap) -> Response {
-    assert!(LANG_CODE.contains(&lang.as_str()));
+    assert!(lang_tran::has_lang(&lang));
     assert!(LINES.contains(&line.as_str()));
     let referer = header_map.get("referer");
     match referer {
@@ -760,13 +1441,13 @@ pub async fn pre_shift(
             let pre = cur.pre_shift();
             let (date, shift) = (pre.date(), pre.shift());
             let url = format!("/{lang}/{line}/{item}/?querydate={date}&shift={shift}");
-            println!("cap4 non redirect to: {url}");
+            debug!("cap4 non redirect to: {url}");
             Redirect::to(&url).into_response()
         }
         Some(refer) => {
             // http://localhost:7890/en-US/fst2/fail_detail
             // http://localhost:7890/en-US/fst2/fail_detail/?querydate=2024-01-04&shift=DAY
-            let re_url = Regex::new(r"http://[\w.]+:\d+?/(\w\w-\w\w)/(\w+)/(\w+)(/\?querydate=((20\d{2})-(0\d|1[12])-([012]\d|3[01]))&shift=(\w+))?$").unwrap();
+            let re_url = Regex::new(r"https?://[\w.-]+(?::\d+)?/(\w\w-\w\w)/(\w+)/(\w+)(/\?querydate=((20\d{2})-(0\d|1[12])-([012]\d|3[01]))&shift=(\w+))?$").unwrap();
             if let Some(cap) = re_url.captures(refer.to_str().unwrap()) {
                 let (lang, line, item) = (&cap[1], &cap[2], &cap[3]);
                 match cap.get(4) {
@@ -775,14 +1456,14 @@ pub async fn pre_shift(
                         let pre = cur.pre_shift();
                         let (date, shift) = (pre.date(), pre.shift());
                         let url = format!("/{lang}/{line}/{item}/?querydate={date}&shift={shift}");
-                        println!("cap4 non redirect to: {url}");
+                        debug!("cap4 non redirect to: {url}");
                         return Redirect::to(&url).into_response();
                     }
                     Some(_) => {
                         let (year, month, day, shift) = (&cap[6], &cap[7], &cap[8], &cap[9]);
                         let (date, shift) = mytime::pre_shift_str2date(year, month, day, shift);
                         let url = format!("/{lang}/{line}/{item}/?querydate={date}&shift={shift}");
-                        println!("cap4 some redirect to: {url}");
+                        debug!("cap4 some redirect to: {url}");
                         return Redirect::to(&url).into_response();
                     }
                 }
@@ -799,15 +1480,15 @@ pub struct QuerySnParams {
 
 pub async fn query_sn(extract::Path((lang, line)): extract::Path<(String, String)>,
                       extract::Query(query_params): extract::Query<QuerySnParams>, ) -> impl IntoResponse {
-    assert!(LANG_CODE.contains(&lang.as_str()));
+    assert!(lang_tran::has_lang(&lang));
     assert!(LINES.contains(&line.as_str()));
-    let config = CONFIG.get().unwrap();
+    let config = config::get_config();
     let hostname = config.get_hostname(&line).unwrap();
     let hostname = hostname.to_owned();
     let update_time = mytime::now_vn();
-    let lang_map = LANG_MAP.get().unwrap();
+    let lang_map = lang_tran::get_lang_map();
     let lang_map = lang_map.get(lang.as_str()).unwrap();
-    let title = "Query Sn".to_string();
+    let title = lang_map.get("tem_title_query_sn").unwrap().to_owned();
     let sn = match query_params.sn {
         Some(_sn) => {
             let sn = _sn.trim().to_uppercase();
@@ -822,16 +1503,22 @@ pub async fn query_sn(extract::Path((lang, line)): extract::Path<(String, String
     };
     let record = match sn {
         Some(sn) => {
-            let r = db::sn_record(sn).unwrap();
+            let r = match db::sn_record(sn) {
+                Ok(r) => r,
+                Err(e) => {
+                    error!("query sn record fail, {e}");
+                    return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+                }
+            };
             if !r.is_empty() {
                 r
             } else {
                 vec![("".to_string(), "".to_string(), "".to_string(), "".to_string(),
-                      "", "".to_string(), "", "".to_string(), "".to_string(), "".to_string())]
+                      "", "".to_string(), "".to_string(), "".to_string(), "".to_string(), "".to_string())]
             }
         }
         None => vec![("".to_string(), "".to_string(), "".to_string(), "".to_string(),
-                      "", "".to_string(), "", "".to_string(), "".to_string(), "".to_string())]
+                      "", "".to_string(), "".to_string(), "".to_string(), "".to_string(), "".to_string())]
     };
     let tpl = SnRecordTemplate {
         record,
@@ -842,33 +1529,21 @@ pub async fn query_sn(extract::Path((lang, line)): extract::Path<(String, String
         update_time,
         // all tem variant
         tem_language: lang_map.get("tem_language").unwrap(),
-        tem_day: lang_map.get("tem_day").unwrap(),
-        tem_night: lang_map.get("tem_night").unwrap(),
-        tem_previous_day: lang_map.get("tem_previous_day").unwrap(),
-        tem_previous_shift: lang_map.get("tem_previous_shift").unwrap(),
-        tem_today: lang_map.get("tem_today").unwrap(),
-        tem_viewing_data: lang_map.get("tem_viewing_data").unwrap(),
-        tem_colon: lang_map.get("tem_colon").unwrap(),
-        tem_home: lang_map.get("tem_home").unwrap(),
         tem_quantity_of_pass_fail: lang_map.get("tem_quantity_of_pass_fail").unwrap(),
         tem_yield_of_shift: lang_map.get("tem_yield_of_shift").unwrap(),
         tem_fail_record_details: lang_map.get("tem_fail_record_details").unwrap(),
         tem_query_400_records_of_cell: lang_map.get("tem_query_400_records_of_cell").unwrap(),
         tem_query_sn_history: lang_map.get("tem_query_sn_history").unwrap(),
-        tem_query_sn_history_all: lang_map.get("tem_query_sn_history_all").unwrap(),
+        tem_maintenance_log: lang_map.get("tem_maintenance_log").unwrap(),
         tem_key_name_of_bgibest: lang_map.get("tem_key_name_of_bgibest").unwrap(),
         tem_port_config_of_terminal_server: lang_map
             .get("tem_port_config_of_terminal_server")
             .unwrap(),
-        tem_line_0: lang_map.get("tem_line_0").unwrap(),
-        tem_line_1: lang_map.get("tem_line_1").unwrap(),
-        tem_line_2: lang_map.get("tem_line_2").unwrap(),
-        tem_line_3: lang_map.get("tem_line_3").unwrap(),
         tem_submit: lang_map.get("tem_submit").unwrap(),
         tem_local_time: lang_map.get("tem_local_time").unwrap(),
         tem_sort_able: lang_map.get("tem_sort_able").unwrap(),
     };
-    HtmlTemplate(tpl)
+    HtmlTemplate(tpl).into_response()
 }
 
 #[derive(Serialize)]
@@ -877,8 +1552,367 @@ pub struct CurShift {
     shift: String,
 }
 
+#[derive(Serialize)]
+pub struct Language {
+    code: String,
+    name: String,
+}
+
+/// languages of the catalogs under locales/, used by the language select of base.html
+pub async fn json_languages() -> Json<Vec<Language>> {
+    let v = lang_tran::languages().into_iter().map(|(code, name)| Language { code, name }).collect();
+    Json(v)
+}
+
+/// reload locales/*.toml without restart, the running catalogs are kept if any file is broken
+pub async fn reload_lang() -> Response {
+    match lang_tran::reload_lang_map() {
+        Ok(langs) => Json(langs).into_response(),
+        Err(e) => {
+            error!("reload locales fail, {e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, e).into_response()
+        }
+    }
+}
+
+/// reload config.toml without restart, the running config is kept if the new file is broken
+pub async fn reload_config() -> Response {
+    match config::reload_config() {
+        Ok(_) => Json("config.toml reload success").into_response(),
+        Err(e) => {
+            error!("reload config.toml fail, {e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, e).into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompareParams {
+    a_date: Option<String>,
+    a_shift: Option<String>,
+    a_to: Option<String>,
+    b_date: Option<String>,
+    b_shift: Option<String>,
+    b_to: Option<String>,
+}
+
+// a period scans every record of the days, a month at most
+static COMPARE_MAX_DAYS: i64 = 31;
+
+/// the counts and yields of two periods side by side, the previous shift and the current shift by default.
+/// a period is a shift (DAY, NIGHT) of a date, or the dates from date to date_to (DATES)
+pub async fn compare(
+    extract::Path((lang, line)): extract::Path<(String, String)>,
+    extract::Query(query_params): extract::Query<CompareParams>,
+    extract::Extension(user): extract::Extension<User>,
+) -> Response {
+    assert!(lang_tran::has_lang(&lang));
+    assert!(LINES.contains(&line.as_str()));
+    let current = mytime::current_shift();
+    let previous = current.pre_shift();
+    let a_date = query_params.a_date.unwrap_or(previous.date());
+    let a_shift = query_params.a_shift.unwrap_or(previous.shift().to_string());
+    let a_to = query_params.a_to.unwrap_or(a_date.to_owned());
+    let b_date = query_params.b_date.unwrap_or(current.date());
+    let b_shift = query_params.b_shift.unwrap_or(current.shift().to_string());
+    let b_to = query_params.b_to.unwrap_or(b_date.to_owned());
+    let (a, b) = match (compare::period(&a_date, &a_shift, &a_to), compare::period(&b_date, &b_shift, &b_to)) {
+        (Some(a), Some(b)) => (a, b),
+        _ => { return (StatusCode::BAD_REQUEST, "date or shift of the period is invalid").into_response(); }
+    };
+    if a.end - a.start > COMPARE_MAX_DAYS * 24 * 3600 || b.end - b.start > COMPARE_MAX_DAYS * 24 * 3600 {
+        return (StatusCode::BAD_REQUEST, format!("a period is longer than {COMPARE_MAX_DAYS} days")).into_response();
+    }
+    let stations = db::stations_of(&line);
+    let (scan_line, scan_a, scan_b) = (line.to_owned(), a.clone(), b.clone());
+    let (compared, errors) = match tokio::task::spawn_blocking(move || {
+        let mut compared = Vec::new();
+        let mut errors = Vec::new();
+        for station in stations {
+            match compare::compare_station(&scan_line, station, &scan_a, &scan_b) {
+                Ok(x) => compared.push(x),
+                Err(e) => {
+                    error!("compare {scan_line} {station} fail, {e}");
+                    errors.push(format!("{station}: {e}"));
+                }
+            }
+        }
+        (compared, errors)
+    }).await {
+        Ok(c) => c,
+        Err(e) => {
+            error!("compare {line} fail, {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "compare fail").into_response();
+        }
+    };
+    let shifts = |selected: &str| ["DAY", "NIGHT", "DATES"].into_iter()
+        .map(|x| (x, if x == selected { "selected" } else { "" })).collect();
+    let config = config::get_config();
+    let hostname = config.get_hostname(&line).unwrap();
+    let hostname = hostname.to_owned();
+    let lang_map = lang_tran::get_lang_map();
+    let lang_map = lang_map.get(lang.as_str()).unwrap();
+    let title = lang_map.get("tem_title_compare").unwrap().to_owned();
+    let tpl = CompareTemplate {
+        a_shift: shifts(&a_shift),
+        a_date,
+        a_to,
+        b_shift: shifts(&b_shift),
+        b_date,
+        b_to,
+        a_label: a.label,
+        b_label: b.label,
+        stations: compared,
+        errors,
+        show_messages: user.role >= Role::Engineer,
+        // base.html
+        title,
+        line,
+        hostname,
+        update_time: mytime::now_vn(),
+        // all tem variant
+        tem_language: lang_map.get("tem_language").unwrap(),
+        tem_viewing_data: lang_map.get("tem_viewing_data").unwrap(),
+        tem_colon: lang_map.get("tem_colon").unwrap(),
+        tem_quantity_of_pass_fail: lang_map.get("tem_quantity_of_pass_fail").unwrap(),
+        tem_yield_of_shift: lang_map.get("tem_yield_of_shift").unwrap(),
+        tem_fail_record_details: lang_map.get("tem_fail_record_details").unwrap(),
+        tem_query_400_records_of_cell: lang_map.get("tem_query_400_records_of_cell").unwrap(),
+        tem_query_sn_history: lang_map.get("tem_query_sn_history").unwrap(),
+        tem_maintenance_log: lang_map.get("tem_maintenance_log").unwrap(),
+        tem_key_name_of_bgibest: lang_map.get("tem_key_name_of_bgibest").unwrap(),
+        tem_port_config_of_terminal_server: lang_map
+            .get("tem_port_config_of_terminal_server")
+            .unwrap(),
+        tem_submit: lang_map.get("tem_submit").unwrap(),
+        tem_local_time: lang_map.get("tem_local_time").unwrap(),
+    };
+    HtmlTemplate(tpl).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CrossLineParams {
+    station: Option<String>,
+    date: Option<String>,
+    shift: Option<String>,
+    date_to: Option<String>,
+}
+
+/// a station of the line beside the same station of the other line (fst1 and fst2, bst1 and bst2), cell by cell.
+/// the current shift by default, the line the visitor can not view is not shown
+pub async fn cross_line(
+    extract::Path((lang, line)): extract::Path<(String, String)>,
+    extract::Query(query_params): extract::Query<CrossLineParams>,
+    extract::Extension(user): extract::Extension<User>,
+) -> Response {
+    assert!(lang_tran::has_lang(&lang));
+    assert!(LINES.contains(&line.as_str()));
+    let stations = db::stations_of(&line);
+    let station = query_params.station.filter(|x| stations.contains(&x.as_str()))
+        .unwrap_or(stations[0].to_string());
+    let stations = stations.into_iter().map(|x| (x, if x == station { "selected" } else { "" })).collect();
+    let current = mytime::current_shift();
+    let date = query_params.date.unwrap_or(current.date());
+    let shift = query_params.shift.unwrap_or(current.shift().to_string());
+    let date_to = query_params.date_to.unwrap_or(date.to_owned());
+    let period = match compare::period(&date, &shift, &date_to) {
+        Some(p) => p,
+        None => { return (StatusCode::BAD_REQUEST, "date or shift is invalid").into_response(); }
+    };
+    if period.end - period.start > COMPARE_MAX_DAYS * 24 * 3600 {
+        return (StatusCode::BAD_REQUEST, format!("the period is longer than {COMPARE_MAX_DAYS} days")).into_response();
+    }
+    let (lines, hidden_lines): (Vec<&str>, Vec<&str>) = cross_line::family(&line).into_iter()
+        .partition(|x| user.can_view_line(x));
+    let (scan_station, scan_period) = (station.to_owned(), period.clone());
+    let cross = match tokio::task::spawn_blocking(move || {
+        cross_line::cross_station(&lines, &scan_station, &scan_period)
+    }).await {
+        Ok(c) => c,
+        Err(e) => {
+            error!("cross line {line} fail, {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "cross line fail").into_response();
+        }
+    };
+    for e in cross.errors.iter() {
+        error!("cross line {e}");
+    }
+    let config = config::get_config();
+    let hostname = config.get_hostname(&line).unwrap();
+    let hostname = hostname.to_owned();
+    let lang_map = lang_tran::get_lang_map();
+    let lang_map = lang_map.get(lang.as_str()).unwrap();
+    let title = lang_map.get("tem_title_cross_line").unwrap().to_owned();
+    let tpl = CrossLineTemplate {
+        stations,
+        shifts: ["DAY", "NIGHT", "DATES"].into_iter().map(|x| (x, if x == shift { "selected" } else { "" })).collect(),
+        date,
+        date_to,
+        label: period.label,
+        station,
+        cross,
+        hidden_lines,
+        show_messages: user.role >= Role::Engineer,
+        // base.html
+        title,
+        line,
+        hostname,
+        update_time: mytime::now_vn(),
+        // all tem variant
+        tem_language: lang_map.get("tem_language").unwrap(),
+        tem_viewing_data: lang_map.get("tem_viewing_data").unwrap(),
+        tem_colon: lang_map.get("tem_colon").unwrap(),
+        tem_quantity_of_pass_fail: lang_map.get("tem_quantity_of_pass_fail").unwrap(),
+        tem_yield_of_shift: lang_map.get("tem_yield_of_shift").unwrap(),
+        tem_fail_record_details: lang_map.get("tem_fail_record_details").unwrap(),
+        tem_query_400_records_of_cell: lang_map.get("tem_query_400_records_of_cell").unwrap(),
+        tem_query_sn_history: lang_map.get("tem_query_sn_history").unwrap(),
+        tem_maintenance_log: lang_map.get("tem_maintenance_log").unwrap(),
+        tem_key_name_of_bgibest: lang_map.get("tem_key_name_of_bgibest").unwrap(),
+        tem_port_config_of_terminal_server: lang_map
+            .get("tem_port_config_of_terminal_server")
+            .unwrap(),
+        tem_submit: lang_map.get("tem_submit").unwrap(),
+        tem_local_time: lang_map.get("tem_local_time").unwrap(),
+    };
+    HtmlTemplate(tpl).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DataQualityParams {
+    days: Option<i64>,
+}
+
+// the report scans every record of the days, a month at most
+static DATA_QUALITY_MAX_DAYS: i64 = 31;
+
+/// the rows of the db of every station of the line that the yield can not count, with samples
+pub async fn data_quality(
+    extract::Path((lang, line)): extract::Path<(String, String)>,
+    extract::Query(query_params): extract::Query<DataQualityParams>,
+) -> Response {
+    assert!(lang_tran::has_lang(&lang));
+    assert!(LINES.contains(&line.as_str()));
+    let days = query_params.days.unwrap_or(7).clamp(1, DATA_QUALITY_MAX_DAYS);
+    let start_ts = mytime::now_ts() - days * 24 * 3600;
+    let stations = db::stations_of(&line);
+    let scan_line = line.to_owned();
+    let dbs = match tokio::task::spawn_blocking(move || {
+        stations.into_iter().map(|x| data_quality::db_quality(&scan_line, x, start_ts)).collect::<Vec<_>>()
+    }).await {
+        Ok(d) => d,
+        Err(e) => {
+            error!("data quality of {line} fail, {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "data quality fail").into_response();
+        }
+    };
+    for q in dbs.iter().filter(|x| !x.error.is_empty()) {
+        error!("data quality of {line} {} {}", q.station, q.error);
+    }
+    let config = config::get_config();
+    let hostname = config.get_hostname(&line).unwrap();
+    let hostname = hostname.to_owned();
+    let lang_map = lang_tran::get_lang_map();
+    let lang_map = lang_map.get(lang.as_str()).unwrap();
+    let title = lang_map.get("tem_title_data_quality").unwrap().to_owned();
+    let tpl = DataQualityTemplate {
+        days,
+        max_days: DATA_QUALITY_MAX_DAYS,
+        since: mytime::ts2vn_str(start_ts),
+        dbs,
+        // base.html
+        title,
+        line,
+        hostname,
+        update_time: mytime::now_vn(),
+        // all tem variant
+        tem_language: lang_map.get("tem_language").unwrap(),
+        tem_viewing_data: lang_map.get("tem_viewing_data").unwrap(),
+        tem_colon: lang_map.get("tem_colon").unwrap(),
+        tem_quantity_of_pass_fail: lang_map.get("tem_quantity_of_pass_fail").unwrap(),
+        tem_yield_of_shift: lang_map.get("tem_yield_of_shift").unwrap(),
+        tem_fail_record_details: lang_map.get("tem_fail_record_details").unwrap(),
+        tem_query_400_records_of_cell: lang_map.get("tem_query_400_records_of_cell").unwrap(),
+        tem_query_sn_history: lang_map.get("tem_query_sn_history").unwrap(),
+        tem_maintenance_log: lang_map.get("tem_maintenance_log").unwrap(),
+        tem_key_name_of_bgibest: lang_map.get("tem_key_name_of_bgibest").unwrap(),
+        tem_port_config_of_terminal_server: lang_map
+            .get("tem_port_config_of_terminal_server")
+            .unwrap(),
+        tem_submit: lang_map.get("tem_submit").unwrap(),
+        tem_local_time: lang_map.get("tem_local_time").unwrap(),
+    };
+    HtmlTemplate(tpl).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShiftReportParams {
+    line: String,
+    querydate: Option<String>,
+    shift: Option<String>,
+    // only show the report when false
+    #[serde(default)]
+    send: bool,
+}
+
+/// the shift report of the line, the previous shift by default. also sent to the smtp and webhooks when send=true
+pub async fn make_shift_report(extract::Query(params): extract::Query<ShiftReportParams>) -> Response {
+    if !LINES.contains(&params.line.as_str()) {
+        return (StatusCode::BAD_REQUEST, format!("line {} is invalid", params.line)).into_response();
+    }
+    let dateshift = if params.querydate.is_none() && params.shift.is_none() {
+        mytime::current_shift().pre_shift()
+    } else {
+        match (QueryDateParams { querydate: params.querydate, shift: params.shift }).des() {
+            Some(d) => d,
+            None => { return (StatusCode::BAD_REQUEST, "querydate or shift is invalid").into_response(); }
+        }
+    };
+    let report = match shift_report::build_blocking(&params.line, &dateshift).await {
+        Ok(r) => r,
+        Err(e) => {
+            error!("build shift report of {} fail, {e}", params.line);
+            return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+        }
+    };
+    let text = report.text();
+    if params.send {
+        let report_config = &config::get_config().shift_report;
+        if report_config.smtp.is_none() && report_config.webhooks.is_empty() {
+            return (StatusCode::BAD_REQUEST, "no [shift_report.smtp] or webhooks in config.toml").into_response();
+        }
+        tokio::spawn(shift_report::deliver(report));
+    }
+    text.into_response()
+}
+
 pub async fn json_today() -> Json<CurShift> {
     let cur = mytime::current_shift();
     let cs = CurShift { date: cur.date(), shift: cur.shift().to_string() };
     Json(cs)
-}
\ No newline at end of file
+}
+
+/// the wall-mounted display, the pages of kiosk.playlists.<name> in config.toml are shown in turn by kiosk.js
+pub async fn kiosk(extract::Path(name): extract::Path<String>, header_map: HeaderMap) -> Response {
+    let config = config::get_config();
+    let pages = match config.kiosk.playlists.get(&name) {
+        Some(p) => p,
+        None => { return (StatusCode::NOT_FOUND, format!("kiosk playlist {name} is not in config.toml")).into_response(); }
+    };
+    let pages = pages.iter().map(|x| config::KioskPage {
+        path: x.path.to_owned(),
+        secs: if x.secs == 0 { config.kiosk.interval_secs } else { x.secs },
+    }).collect();
+    let lang = negotiate_lang(&header_map);
+    let lang_map = lang_tran::get_lang_map();
+    let lang_map = lang_map.get(lang.as_str()).unwrap();
+    let title = lang_map.get("tem_title_kiosk").unwrap();
+    let tpl = KioskTemplate {
+        title: format!("{title} {name}"),
+        pages,
+        refresh_secs: config.kiosk.refresh_secs,
+        retry_secs: config.kiosk.retry_secs,
+        tem_language: lang_map.get("tem_language").unwrap(),
+    };
+    HtmlTemplate(tpl).into_response()
+}
@@ -0,0 +1,34 @@
+"use strict";
+
+// the websocket of the same path and query, "/en-US/fst1/console?name=CELL_69" -> "/en-US/fst1/console/ws?name=CELL_69"
+const consoleOut = document.getElementById('console_out');
+const consoleInput = document.getElementById('console_input');
+const wsScheme = window.location.protocol === 'https:' ? 'wss://' : 'ws://';
+const ws = new WebSocket(wsScheme + window.location.host + window.location.pathname + '/ws' + window.location.search);
+
+// keep the latest output only, a long session should not slow the page
+const OUT_MAX_LEN = 200000;
+
+function print(text) {
+    let out = consoleOut.textContent + text;
+    if (out.length > OUT_MAX_LEN) {
+        out = out.slice(-OUT_MAX_LEN / 2);
+    }
+    consoleOut.textContent = out;
+    consoleOut.scrollTop = consoleOut.scrollHeight;
+}
+
+ws.onmessage = e => print(e.data);
+ws.onclose = () => {
+    print('\n*** disconnected, reload the page to connect again\n');
+    consoleInput.disabled = true;
+};
+
+document.getElementById('console_form').onsubmit = e => {
+    e.preventDefault();
+    if (ws.readyState === WebSocket.OPEN) {
+        ws.send(consoleInput.value);
+        print(consoleInput.value + '\n');
+    }
+    consoleInput.value = '';
+};
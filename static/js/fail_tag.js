@@ -0,0 +1,21 @@
+"use strict";
+
+// the current cause of the tagged record, the row carry it by data-cause
+for (let form of document.querySelectorAll('form.fail_tag_form')) {
+    form.querySelector('select[name=cause]').value = form.closest('tr').dataset.cause;
+}
+
+// show the fail records of the selected cause only, "*" is all, "" is untagged
+function causeFilter() {
+    let cause = this.value;
+    for (let filter of document.querySelectorAll('select.cause_filter')) {
+        filter.value = cause;
+    }
+    for (let row of document.querySelectorAll('tr[data-cause]')) {
+        row.style.display = (cause === '*' || row.dataset.cause === cause) ? '' : 'none';
+    }
+}
+
+for (let filter of document.querySelectorAll('select.cause_filter')) {
+    filter.onchange = causeFilter;
+}
@@ -0,0 +1,78 @@
+"use strict";
+
+// cycle through the pages of the playlist, the <main> of every page is fetched and shown.
+// a failed fetch keeps the last good page of the path and is tried again after retry seconds.
+const playlist = document.getElementById('kiosk_playlist');
+const pages = Array.from(playlist.querySelectorAll('li')).map(li => ({
+    path: li.dataset.path,
+    secs: Number(li.dataset.secs),
+}));
+const refreshMs = Number(playlist.dataset.refresh) * 1000;
+const retryMs = Number(playlist.dataset.retry) * 1000;
+// path -> {main, time} of the last good fetch
+const lastGood = {};
+let current = 0;
+let shownSince = Date.now();
+
+function show(path) {
+    let good = lastGood[path];
+    let content = document.getElementById('kiosk_content');
+    if (good !== undefined) {
+        content.replaceChildren(good.main.cloneNode(true));
+    }
+    document.getElementById('kiosk_page').textContent = path;
+}
+
+function setStatus(text) {
+    document.getElementById('kiosk_status').textContent = text;
+}
+
+function load(path) {
+    return fetch(path, {cache: 'no-store'})
+        .then(resp => {
+            if (!resp.ok) {
+                throw new Error('HTTP ' + resp.status);
+            }
+            if (new URL(resp.url).pathname === '/login') {
+                throw new Error('login required');
+            }
+            return resp.text();
+        })
+        .then(html => {
+            let main = new DOMParser().parseFromString(html, 'text/html').querySelector('main');
+            if (main === null) {
+                throw new Error('no content');
+            }
+            lastGood[path] = {main: main, time: new Date()};
+            setStatus('');
+            return true;
+        })
+        .catch(e => {
+            let good = lastGood[path];
+            let since = good === undefined ? 'no data yet' : 'last good data at ' + good.time.toLocaleTimeString();
+            setStatus(`unavailable (${e.message}), ${since}, retrying`);
+            return false;
+        });
+}
+
+// show the page of the playlist now, fetch it again every refresh, go to the next page after secs
+function tick() {
+    let page = pages[current];
+    let due = shownSince + page.secs * 1000;
+    load(page.path).then(ok => {
+        show(page.path);
+        let now = Date.now();
+        if (now >= due && pages.length > 1) {
+            current = (current + 1) % pages.length;
+            shownSince = now;
+            setTimeout(tick, 0);
+            return;
+        }
+        let wait = Math.min(ok ? refreshMs : retryMs, Math.max(due - now, 0) || refreshMs);
+        setTimeout(tick, wait);
+    });
+}
+
+if (pages.length > 0) {
+    tick();
+}
@@ -4,19 +4,34 @@ let nav_dropdown = document.getElementById('language-nav');
 let before_lang = nav_dropdown.getAttribute("data-current-lang");  // before onchange
 nav_dropdown.value = before_lang;
 
-function langChange() {
-    let lang = this.value;  // after onchange
-    if (lang == "en-US") {
-        lang = "";
-    } else {
-        lang = `/${lang}`;
+// the options come from the catalogs under locales/, adding a language need no html change
+function fillLanguages(text) {
+    let langs = JSON.parse(text);
+    nav_dropdown.innerHTML = '';
+    for (let lang of langs) {
+        let option = document.createElement('option');
+        option.value = lang.code;
+        option.title = lang.name;
+        option.textContent = lang.name;
+        nav_dropdown.appendChild(option);
     }
-    let path = `/${window.location.href.split('/').slice(3).join('/')}`;
-    if (path.startsWith("/en-US/") || before_lang != "en-US") {
-        path = `/${window.location.href.split('/').slice(4).join('/')}`;
+    nav_dropdown.value = before_lang;
+}
+
+let lang_request = new XMLHttpRequest();
+lang_request.onreadystatechange = function () {
+    if (lang_request.readyState === 4 && lang_request.status === 200) {
+        fillLanguages(lang_request.responseText);
     }
+}
+lang_request.open('GET', '/json/languages');
+lang_request.send();
 
-    window.location = `${lang}${path}`;
+// the server remember the language in cookie, and redirect back to the same page, line and query
+function langChange() {
+    let lang = this.value;  // after onchange
+    let next = encodeURIComponent(window.location.pathname + window.location.search);
+    window.location = `/set_lang/${lang}?next=${next}`;
 }
 
 nav_dropdown.onchange = langChange;
@@ -0,0 +1,17 @@
+"use strict";
+
+// refresh the tiles of the homepage, the whole page is fetched again and the tiles replaced
+function refreshOverview() {
+    fetch(window.location.pathname)
+        .then(resp => resp.ok ? resp.text() : '')
+        .then(html => {
+            let doc = new DOMParser().parseFromString(html, 'text/html');
+            let tiles = doc.getElementById('overview');
+            if (tiles !== null) {
+                document.getElementById('overview').replaceWith(tiles);
+            }
+        })
+        .catch(() => {});
+}
+
+setInterval(refreshOverview, 60000);
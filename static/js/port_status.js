@@ -0,0 +1,23 @@
+"use strict";
+
+// refresh the port badge of the page, the line and language are in the path "/en-US/fst1/portconfig"
+const portStatusUrl = window.location.pathname.split('/').slice(0, 3).join('/') + '/port_status';
+
+function refreshPortStatus() {
+    fetch(portStatusUrl)
+        .then(resp => resp.ok ? resp.json() : {})
+        .then(status => {
+            for (let badge of document.querySelectorAll('span.port_badge')) {
+                let b = status[badge.dataset.port];
+                if (b === undefined) {
+                    continue;
+                }
+                badge.className = 'port_badge ' + b[0];
+                badge.textContent = b[1];
+                badge.title = b[2];
+            }
+        })
+        .catch(() => {});
+}
+
+setInterval(refreshPortStatus, 30000);